@@ -0,0 +1,86 @@
+use std::time::SystemTime;
+
+use base64::prelude::*;
+use clippy_app::encoding::{self, EncodeOptions, Encoding};
+use clippy_app::frame_source::{CapturedFrame, PixelFormat, ScreenRect};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A Retina-sized BGRA frame with row padding, like the ones ScreenCaptureKit hands over.
+/// The left half is flat "window chrome", the right half noisy "text and images",
+/// so PNG has roughly the work it would on a real screen.
+fn retina_frame() -> CapturedFrame {
+    let (width, height) = (2880u32, 1800u32);
+    let stride = width as usize * 4 + 64;
+    let mut data = vec![0u8; stride * height as usize];
+    let mut seed = 0x2545_f491u32;
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let i = y * stride + x * 4;
+            let value = if x < width as usize / 2 {
+                if (y / 40) % 2 == 0 { 235 } else { 250 }
+            } else {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                (seed >> 24) as u8
+            };
+            data[i..i + 4].copy_from_slice(&[value, value, value / 2, 255]);
+        }
+    }
+    CapturedFrame {
+        timestamp: SystemTime::now(),
+        sequence: 0,
+        width,
+        height,
+        display_id: 0,
+        screen_rect: ScreenRect {
+            x: 0.0,
+            y: 0.0,
+            width: 1440.0,
+            height: 900.0,
+        },
+        stride,
+        format: PixelFormat::Bgra,
+        data,
+    }
+}
+
+fn bench_encoding(c: &mut Criterion) {
+    let frame = retina_frame();
+    let mut group = c.benchmark_group("frame");
+    group.sample_size(10);
+
+    // Paid on every captured frame before: a full-resolution PNG, then base64.
+    group.bench_function("every_frame/full_png_base64", |b| {
+        b.iter(|| {
+            let png = encoding::encode_frame(black_box(&frame), &EncodeOptions::default()).unwrap();
+            BASE64_STANDARD.encode(png)
+        })
+    });
+
+    // Paid on every captured frame now: copying the raw pixels out.
+    group.bench_function("every_frame/raw_copy", |b| b.iter(|| black_box(&frame).clone()));
+
+    // Paid only when someone asks for an image.
+    let options = EncodeOptions {
+        max_width: Some(1024),
+        max_height: Some(1024),
+        encoding: Encoding::Jpeg { quality: 80 },
+    };
+    group.bench_function("on_demand/downscaled_jpeg", |b| {
+        b.iter(|| encoding::encode_frame(black_box(&frame), &options).unwrap())
+    });
+    let options = EncodeOptions {
+        max_width: Some(720),
+        max_height: Some(720),
+        encoding: Encoding::Png,
+    };
+    group.bench_function("on_demand/downscaled_png", |b| {
+        b.iter(|| encoding::encode_frame(black_box(&frame), &options).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_encoding);
+criterion_main!(benches);
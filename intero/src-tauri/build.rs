@@ -12,5 +12,7 @@ fn build() {
 
 fn main() {
     tauri_build::build();
-    build();
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        build();
+    }
 }
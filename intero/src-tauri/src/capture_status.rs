@@ -0,0 +1,235 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::frame_source::{CaptureError, CapturedFrame, FrameSource, Permission};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureStatus {
+    /// Capture has not been attempted yet.
+    Unknown,
+    /// Waiting on the OS permission check.
+    Requesting,
+    /// Allowed to capture, but not capturing.
+    Granted,
+    /// The user has not granted screen recording.
+    Denied,
+    /// The source was started and has not delivered a frame yet.
+    Starting,
+    Running,
+    /// Stopped for now, but meant to resume. See `CaptureController::pause`.
+    Paused,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureEvent {
+    StartRequested,
+    PermissionGranted,
+    PermissionDenied,
+    StreamStarted,
+    FirstFrame,
+    StreamFailed,
+    PauseRequested,
+    Stopped,
+}
+
+impl CaptureStatus {
+    /// The status after `event`, or `None` if the event makes no sense in this status.
+    pub fn on(self, event: CaptureEvent) -> Option<CaptureStatus> {
+        use CaptureEvent::*;
+        use CaptureStatus::*;
+        let next = match (self, event) {
+            (Unknown | Granted | Denied | Paused | Failed, StartRequested) => Requesting,
+            (Requesting | Denied, PermissionGranted) => Granted,
+            (Requesting | Starting | Running, PermissionDenied) => Denied,
+            (Granted, StreamStarted) => Starting,
+            (Starting, FirstFrame) => Running,
+            (Requesting | Granted | Starting | Running, StreamFailed) => Failed,
+            (Starting | Running, PauseRequested) => Paused,
+            (Granted | Starting | Running | Paused, Stopped) => Granted,
+            _ => return None,
+        };
+        Some(next)
+    }
+}
+
+/// Payload of the `capture-status-changed` event.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct StatusChange {
+    pub status: CaptureStatus,
+    pub previous: CaptureStatus,
+}
+
+type Listener = Box<dyn Fn(StatusChange) + Send + Sync>;
+
+/// Drives a `FrameSource` and tracks its status, telling the listener about every change.
+/// As a `FrameSource` itself it only hands out frames while running.
+pub struct CaptureController {
+    source: Box<dyn FrameSource>,
+    status: Mutex<CaptureStatus>,
+    /// When a timed pause ends.
+    resume_at: Mutex<Option<Instant>>,
+    listener: Mutex<Option<Listener>>,
+}
+
+impl CaptureController {
+    pub fn new(source: Box<dyn FrameSource>) -> Self {
+        CaptureController {
+            source,
+            status: Mutex::new(CaptureStatus::Unknown),
+            resume_at: Mutex::new(None),
+            listener: Mutex::new(None),
+        }
+    }
+
+    pub fn set_listener(&self, listener: Listener) {
+        *self.listener.lock().unwrap() = Some(listener);
+    }
+
+    pub fn status(&self) -> CaptureStatus {
+        *self.status.lock().unwrap()
+    }
+
+    /// Checks permission (prompting the first time on macOS) and starts the source.
+    /// Also resumes a paused capture. The status stays `Starting` until the source
+    /// delivers a frame, since a stream can start and still never produce one.
+    pub fn start(&self) -> Result<(), CaptureError> {
+        if matches!(self.status(), CaptureStatus::Starting | CaptureStatus::Running) {
+            return Ok(());
+        }
+        self.resume_at.lock().unwrap().take();
+        self.apply(CaptureEvent::StartRequested);
+        if self.source.request_permission() == Permission::Denied {
+            self.apply(CaptureEvent::PermissionDenied);
+            return Err(CaptureError::PermissionDenied);
+        }
+        self.apply(CaptureEvent::PermissionGranted);
+        match self.source.start() {
+            Ok(()) => {
+                self.apply(CaptureEvent::StreamStarted);
+                Ok(())
+            }
+            Err(CaptureError::PermissionDenied) => {
+                self.apply(CaptureEvent::PermissionDenied);
+                Err(CaptureError::PermissionDenied)
+            }
+            Err(err) => {
+                self.apply(CaptureEvent::StreamFailed);
+                Err(err)
+            }
+        }
+    }
+
+    pub fn stop(&self) {
+        self.resume_at.lock().unwrap().take();
+        self.source.stop();
+        self.apply(CaptureEvent::Stopped);
+    }
+
+    /// Stops the source until `start` is called, or until `duration` has passed.
+    /// Does nothing unless starting or running.
+    pub fn pause(&self, duration: Option<Duration>) {
+        if !matches!(self.status(), CaptureStatus::Starting | CaptureStatus::Running) {
+            return;
+        }
+        self.source.stop();
+        *self.resume_at.lock().unwrap() = duration.map(|d| Instant::now() + d);
+        self.apply(CaptureEvent::PauseRequested);
+    }
+
+    /// Asks the OS for permission again, e.g. after the user clicked "Grant screen recording…".
+    pub fn request_permission(&self) -> CaptureStatus {
+        if self.source.request_permission() == Permission::Granted {
+            self.apply(CaptureEvent::PermissionGranted);
+        }
+        self.status()
+    }
+
+    /// Picks up changes that happen behind our back: permission granted in System
+    /// Settings, the first frame arriving, or the stream dying. Also ends timed pauses.
+    /// Call periodically.
+    pub fn refresh(&self) -> CaptureStatus {
+        match self.status() {
+            CaptureStatus::Paused if self.resume_at.lock().unwrap().is_some_and(|at| Instant::now() >= at) => {
+                if let Err(err) = self.start() {
+                    println!("[error]: could not resume capture: {}", err);
+                }
+            }
+            CaptureStatus::Denied if self.source.permission() == Permission::Granted => {
+                self.apply(CaptureEvent::PermissionGranted);
+            }
+            status @ (CaptureStatus::Starting | CaptureStatus::Running) => match self.source.latest_frame() {
+                Ok(_) if status == CaptureStatus::Starting => {
+                    self.apply(CaptureEvent::FirstFrame);
+                }
+                Err(CaptureError::PermissionDenied) => {
+                    self.apply(CaptureEvent::PermissionDenied);
+                }
+                Err(CaptureError::Failed { message }) => {
+                    println!("[error]: capture stream failed: {}", message);
+                    self.apply(CaptureEvent::StreamFailed);
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        self.status()
+    }
+
+    /// Applies `event` and notifies the listener if the status changed.
+    /// Invalid events are logged and ignored.
+    fn apply(&self, event: CaptureEvent) {
+        let change = {
+            let mut status = self.status.lock().unwrap();
+            let previous = *status;
+            match previous.on(event) {
+                Some(next) => {
+                    *status = next;
+                    (next != previous).then_some(StatusChange { status: next, previous })
+                }
+                None => {
+                    println!("[info]: ignoring capture event {:?} while {:?}", event, previous);
+                    None
+                }
+            }
+        };
+        if let (Some(change), Some(listener)) = (change, self.listener.lock().unwrap().as_ref()) {
+            listener(change);
+        }
+    }
+}
+
+impl FrameSource for CaptureController {
+    fn start(&self) -> Result<(), CaptureError> {
+        CaptureController::start(self)
+    }
+
+    fn stop(&self) {
+        CaptureController::stop(self)
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        match self.status() {
+            CaptureStatus::Running => self.source.latest_frame(),
+            CaptureStatus::Starting => {
+                let frame = self.source.latest_frame()?;
+                self.apply(CaptureEvent::FirstFrame);
+                Ok(frame)
+            }
+            CaptureStatus::Paused => Err(CaptureError::Paused),
+            CaptureStatus::Denied => Err(CaptureError::PermissionDenied),
+            _ => Err(CaptureError::NotStarted),
+        }
+    }
+
+    fn permission(&self) -> Permission {
+        self.source.permission()
+    }
+
+    fn request_permission(&self) -> Permission {
+        self.source.request_permission()
+    }
+}
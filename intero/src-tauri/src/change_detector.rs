@@ -0,0 +1,191 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::frame_source::Rect;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ChangeDetectorConfig {
+    /// Per-pixel colour distance (0..1) above which a pixel counts as changed.
+    /// Same meaning as pixelmatch's `threshold`.
+    pub threshold: f32,
+    /// A frame only counts as changed once more than this many pixels differ.
+    pub min_changed_pixels: u32,
+    /// Side length of the square blocks changed pixels are grouped into for `regions`.
+    pub block_size: u32,
+}
+
+impl Default for ChangeDetectorConfig {
+    fn default() -> Self {
+        ChangeDetectorConfig {
+            threshold: 0.1,
+            min_changed_pixels: 10_000,
+            block_size: 32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameDiff {
+    pub changed: bool,
+    /// Fraction of pixels that changed, 0..1.
+    pub score: f32,
+    pub changed_pixels: u32,
+    /// Bounding boxes of connected groups of changed blocks, in frame pixels.
+    pub regions: Vec<Rect>,
+}
+
+/// Compares each frame against the last frame that was reported as changed.
+pub struct ChangeDetector {
+    pub config: ChangeDetectorConfig,
+    reference: Option<RgbaImage>,
+}
+
+impl ChangeDetector {
+    pub fn new(config: ChangeDetectorConfig) -> Self {
+        ChangeDetector {
+            config,
+            reference: None,
+        }
+    }
+
+    /// Diffs `frame` against the reference frame. The first frame, and any frame whose
+    /// size differs from the reference, counts as a full change.
+    pub fn observe(&mut self, frame: RgbaImage) -> FrameDiff {
+        let diff = match &self.reference {
+            Some(reference) if reference.dimensions() == frame.dimensions() => {
+                diff(reference, &frame, &self.config)
+            }
+            _ => FrameDiff {
+                changed: true,
+                score: 1.0,
+                changed_pixels: frame.width() * frame.height(),
+                regions: vec![Rect {
+                    x: 0,
+                    y: 0,
+                    width: frame.width(),
+                    height: frame.height(),
+                }],
+            },
+        };
+        if diff.changed {
+            self.reference = Some(frame);
+        }
+        diff
+    }
+
+    pub fn reset(&mut self) {
+        self.reference = None;
+    }
+}
+
+pub fn diff(a: &RgbaImage, b: &RgbaImage, config: &ChangeDetectorConfig) -> FrameDiff {
+    let (width, height) = a.dimensions();
+    let block_size = config.block_size.max(1);
+    let blocks_x = width.div_ceil(block_size);
+    let blocks_y = height.div_ceil(block_size);
+    let mut blocks = vec![false; (blocks_x * blocks_y) as usize];
+
+    // pixelmatch compares squared YIQ distance against 35215 * threshold^2.
+    let max_delta = 35215.0 * config.threshold * config.threshold;
+    let mut changed_pixels = 0u32;
+    for (x, y, pa) in a.enumerate_pixels() {
+        let pb = b.get_pixel(x, y);
+        if pa == pb {
+            continue;
+        }
+        if yiq_delta(pa.0, pb.0) > max_delta {
+            changed_pixels += 1;
+            blocks[((y / block_size) * blocks_x + x / block_size) as usize] = true;
+        }
+    }
+
+    let regions = block_regions(&blocks, blocks_x, blocks_y)
+        .into_iter()
+        .map(|r| {
+            let x = r.x * block_size;
+            let y = r.y * block_size;
+            Rect {
+                x,
+                y,
+                width: (r.width * block_size).min(width - x),
+                height: (r.height * block_size).min(height - y),
+            }
+        })
+        .collect();
+
+    let total = (width * height).max(1);
+    FrameDiff {
+        changed: changed_pixels > config.min_changed_pixels,
+        score: changed_pixels as f32 / total as f32,
+        changed_pixels,
+        regions,
+    }
+}
+
+fn yiq_delta(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let (r1, g1, b1) = blend_white(a);
+    let (r2, g2, b2) = blend_white(b);
+    let y = rgb2y(r1, g1, b1) - rgb2y(r2, g2, b2);
+    let i = rgb2i(r1, g1, b1) - rgb2i(r2, g2, b2);
+    let q = rgb2q(r1, g1, b1) - rgb2q(r2, g2, b2);
+    0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q
+}
+
+fn blend_white([r, g, b, a]: [u8; 4]) -> (f32, f32, f32) {
+    let a = a as f32 / 255.0;
+    let blend = |c: u8| 255.0 + (c as f32 - 255.0) * a;
+    (blend(r), blend(g), blend(b))
+}
+
+fn rgb2y(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.2988953 + g * 0.5866225 + b * 0.1144822
+}
+
+fn rgb2i(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.595978 - g * 0.2741761 - b * 0.3218019
+}
+
+fn rgb2q(r: f32, g: f32, b: f32) -> f32 {
+    r * 0.2114702 - g * 0.5226171 + b * 0.3111469
+}
+
+/// Bounding boxes (in block units) of 4-connected groups of set blocks.
+fn block_regions(blocks: &[bool], blocks_x: u32, blocks_y: u32) -> Vec<Rect> {
+    let mut seen = vec![false; blocks.len()];
+    let mut regions = vec![];
+    let mut stack = vec![];
+    for start in 0..blocks.len() {
+        if !blocks[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        stack.push(start);
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i as u32 % blocks_x, i as u32 / blocks_x);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < blocks_x).then(|| i + 1),
+                (y > 0).then(|| i - blocks_x as usize),
+                (y + 1 < blocks_y).then(|| i + blocks_x as usize),
+            ];
+            for n in neighbours.into_iter().flatten() {
+                if blocks[n] && !seen[n] {
+                    seen[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+        regions.push(Rect {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+        });
+    }
+    regions
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::prompt::Prompt;
+use crate::secrets::{SecretName, Secrets};
+use crate::url::url_host;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An encoded screenshot to classify.
+pub struct Image<'a> {
+    pub bytes: &'a [u8],
+    pub mime_type: &'a str,
+}
+
+/// What a backend said about an image, before any parsing.
+#[derive(Debug, Clone, Serialize)]
+pub struct Classification {
+    pub text: String,
+    /// `<backend>:<model>`.
+    pub model: String,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClassifierError {
+    /// The request never got a response.
+    Http { message: String },
+    /// The backend answered with a non-success status, e.g. a content policy rejection.
+    Status { status: u16, body: String },
+    /// The response did not have the expected shape.
+    BadResponse { message: String },
+    FrameNotFound { id: u64 },
+    FrameUnreadable { id: u64, message: String },
+    /// Frames may only go to backends on this machine, see `RedactionConfig::local_only`.
+    RemoteNotAllowed { model: String },
+}
+
+impl fmt::Display for ClassifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClassifierError::Http { message } => write!(f, "request failed: {}", message),
+            ClassifierError::Status { status, body } => write!(f, "backend returned {}: {}", status, body),
+            ClassifierError::BadResponse { message } => write!(f, "unexpected response: {}", message),
+            ClassifierError::FrameNotFound { id } => write!(f, "frame {} is not in the history", id),
+            ClassifierError::FrameUnreadable { id, message } => write!(f, "could not read frame {}: {}", id, message),
+            ClassifierError::RemoteNotAllowed { model } => {
+                write!(f, "{} is not on this machine and remote classifiers are turned off", model)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClassifierError {}
+
+impl From<reqwest::Error> for ClassifierError {
+    fn from(err: reqwest::Error) -> Self {
+        ClassifierError::Http {
+            message: err.to_string(),
+        }
+    }
+}
+
+fn bad_response(message: impl ToString) -> ClassifierError {
+    ClassifierError::BadResponse {
+        message: message.to_string(),
+    }
+}
+
+#[async_trait]
+pub trait Classifier: Send + Sync {
+    /// `<backend>:<model>`, recorded alongside every result.
+    fn model(&self) -> String;
+    /// Returns the backend's raw answer text.
+    async fn complete(&self, image: &Image<'_>, prompt: &Prompt) -> Result<String, ClassifierError>;
+
+    async fn classify(&self, image: &Image<'_>, prompt: &Prompt) -> Result<Classification, ClassifierError> {
+        let started = Instant::now();
+        let text = self.complete(image, prompt).await?;
+        Ok(Classification {
+            text,
+            model: self.model(),
+            latency_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum ClassifierConfig {
+    /// Any OpenAI-compatible chat completions API.
+    OpenAi {
+        base_url: String,
+        model: String,
+        /// Environment variable to read the API key from when none is stored in `Secrets`.
+        api_key_env: Option<String>,
+    },
+    Ollama { base_url: String, model: String },
+    /// The moondream FastAPI server, which streams its answer as server-sent events.
+    Moondream { url: String },
+}
+
+impl ClassifierConfig {
+    pub fn openai(model: impl Into<String>) -> Self {
+        ClassifierConfig::OpenAi {
+            base_url: "https://api.openai.com/v1".to_string(),
+            model: model.into(),
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+        }
+    }
+
+    pub fn ollama(model: impl Into<String>) -> Self {
+        ClassifierConfig::Ollama {
+            base_url: "http://localhost:11434".to_string(),
+            model: model.into(),
+        }
+    }
+
+    pub fn moondream() -> Self {
+        ClassifierConfig::Moondream {
+            url: "http://localhost:7861/api/inference".to_string(),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        match self {
+            ClassifierConfig::OpenAi { base_url, .. } | ClassifierConfig::Ollama { base_url, .. } => base_url,
+            ClassifierConfig::Moondream { url } => url,
+        }
+    }
+
+    /// Whether requests stay on this machine: the backend is on localhost or a loopback address.
+    pub fn is_local(&self) -> bool {
+        match url_host(self.url()) {
+            Some(host) => {
+                host.eq_ignore_ascii_case("localhost")
+                    || host.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+            }
+            None => false,
+        }
+    }
+
+    /// With `local_only`, a backend that is not on this machine is built as one that
+    /// refuses every frame, see `RemoteNotAllowed`.
+    pub fn build(&self, secrets: &Secrets, local_only: bool) -> Box<dyn Classifier> {
+        let classifier: Box<dyn Classifier> = match self.clone() {
+            ClassifierConfig::OpenAi {
+                base_url,
+                model,
+                api_key_env,
+            } => Box::new(OpenAiClassifier::new(
+                base_url,
+                model,
+                secrets
+                    .get(SecretName::OpenAi)
+                    .or_else(|| api_key_env.and_then(|name| std::env::var(name).ok())),
+            )),
+            ClassifierConfig::Ollama { base_url, model } => Box::new(OllamaClassifier::new(base_url, model)),
+            ClassifierConfig::Moondream { url } => Box::new(MoondreamClassifier::new(url)),
+        };
+        if local_only && !self.is_local() {
+            return Box::new(RemoteNotAllowed { model: classifier.model() });
+        }
+        classifier
+    }
+}
+
+/// Stands in for a remote backend while frames must stay on this machine.
+pub struct RemoteNotAllowed {
+    model: String,
+}
+
+#[async_trait]
+impl Classifier for RemoteNotAllowed {
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    async fn complete(&self, _image: &Image<'_>, _prompt: &Prompt) -> Result<String, ClassifierError> {
+        Err(ClassifierError::RemoteNotAllowed { model: self.model.clone() })
+    }
+}
+
+/// Picks a backend from `INTERO_CLASSIFIER`:
+///   - `openai` / `openai:<model>`
+///   - `ollama` / `ollama:<model>`
+///   - `moondream` (default)
+pub fn config_from_env() -> ClassifierConfig {
+    let spec = std::env::var("INTERO_CLASSIFIER").unwrap_or_default();
+    let (backend, model) = match spec.split_once(':') {
+        Some((backend, model)) => (backend, Some(model)),
+        None => (spec.as_str(), None),
+    };
+    match backend {
+        "openai" => ClassifierConfig::openai(model.unwrap_or("gpt-4-vision-preview")),
+        "ollama" => ClassifierConfig::ollama(model.unwrap_or("llava")),
+        "" | "moondream" => ClassifierConfig::moondream(),
+        _ => {
+            println!("[error]: unknown INTERO_CLASSIFIER {:?}, using moondream", spec);
+            ClassifierConfig::moondream()
+        }
+    }
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .expect("default HTTP client")
+}
+
+/// Fails with the response body on non-success statuses.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, ClassifierError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    Err(ClassifierError::Status {
+        status: status.as_u16(),
+        body: response.text().await.unwrap_or_default(),
+    })
+}
+
+// OpenAI
+
+pub struct OpenAiClassifier {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiClassifier {
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>, api_key: Option<String>) -> Self {
+        OpenAiClassifier {
+            client: client(),
+            base_url: base_url.into(),
+            model: model.into(),
+            api_key,
+        }
+    }
+
+    /// Lists the models, which fails with a 401 status if the key is wrong.
+    pub async fn check_key(&self) -> Result<(), ClassifierError> {
+        let mut request = self.client.get(format!("{}/models", self.base_url.trim_end_matches('/')));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        check_status(request.send().await?).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Classifier for OpenAiClassifier {
+    fn model(&self) -> String {
+        format!("openai:{}", self.model)
+    }
+
+    async fn complete(&self, image: &Image<'_>, prompt: &Prompt) -> Result<String, ClassifierError> {
+        let data_url = format!("data:{};base64,{}", image.mime_type, BASE64_STANDARD.encode(image.bytes));
+        let body = json!({
+            "model": self.model,
+            "max_tokens": 1500,
+            "messages": [
+                { "role": "system", "content": prompt.system },
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": prompt.user },
+                        { "type": "image_url", "image_url": { "url": data_url } },
+                    ],
+                },
+            ],
+        });
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url.trim_end_matches('/')))
+            .json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response: Value = check_status(request.send().await?).await?.json().await?;
+        response["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| bad_response("no choices[0].message.content"))
+    }
+}
+
+// Ollama
+
+pub struct OllamaClassifier {
+    client: reqwest::Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaClassifier {
+    pub fn new(base_url: impl Into<String>, model: impl Into<String>) -> Self {
+        OllamaClassifier {
+            client: client(),
+            base_url: base_url.into(),
+            model: model.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Classifier for OllamaClassifier {
+    fn model(&self) -> String {
+        format!("ollama:{}", self.model)
+    }
+
+    async fn complete(&self, image: &Image<'_>, prompt: &Prompt) -> Result<String, ClassifierError> {
+        let body = json!({
+            "model": self.model,
+            "options": { "temperature": 0 },
+            "system": prompt.system,
+            "prompt": prompt.user,
+            "format": "json",
+            "stream": false,
+            "images": [BASE64_STANDARD.encode(image.bytes)],
+        });
+        let request = self
+            .client
+            .post(format!("{}/api/generate", self.base_url.trim_end_matches('/')))
+            .json(&body);
+        let response: Value = check_status(request.send().await?).await?.json().await?;
+        response["response"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| bad_response("no response field"))
+    }
+}
+
+// Moondream
+
+pub struct MoondreamClassifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl MoondreamClassifier {
+    pub fn new(url: impl Into<String>) -> Self {
+        MoondreamClassifier {
+            client: client(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Classifier for MoondreamClassifier {
+    fn model(&self) -> String {
+        "moondream:moondream".to_string()
+    }
+
+    async fn complete(&self, image: &Image<'_>, prompt: &Prompt) -> Result<String, ClassifierError> {
+        let file = reqwest::multipart::Part::bytes(image.bytes.to_vec())
+            .file_name("screenshot")
+            .mime_str(image.mime_type)?;
+        let form = reqwest::multipart::Form::new()
+            .text("prompt", prompt.user.clone())
+            .part("file", file);
+        let request = self.client.post(&self.url).multipart(form);
+        let body = check_status(request.send().await?).await?.text().await?;
+        Ok(parse_sse_text(&body))
+    }
+}
+
+/// Concatenates the JSON-encoded string chunks of a server-sent event stream.
+/// Chunks that are not JSON strings are skipped, as the webview client did.
+pub fn parse_sse_text(body: &str) -> String {
+    let mut text = String::new();
+    for event in body.replace("\r\n", "\n").split("\n\n") {
+        let data = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if data.is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<String>(&data) {
+            Ok(chunk) => text.push_str(&chunk),
+            Err(err) => println!("[error]: skipping moondream event {:?}: {}", data, err),
+        }
+    }
+    text
+}
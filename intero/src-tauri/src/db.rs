@@ -0,0 +1,597 @@
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::foreground::ForegroundInfo;
+use crate::graph_schema;
+use crate::prompt::PromptTemplate;
+use crate::undo::Op;
+
+pub use rusqlite::Error;
+pub type Result<T> = rusqlite::Result<T>;
+
+/// Applied in order; `PRAGMA user_version` records how many have run.
+/// Only ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE nodes (
+        id TEXT PRIMARY KEY,
+        -- TNodeData as JSON, minus the derived `__` fields.
+        data TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE relations (
+        kind TEXT NOT NULL,
+        parent TEXT NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+        child TEXT NOT NULL REFERENCES nodes(id) ON DELETE CASCADE,
+        PRIMARY KEY (kind, parent, child)
+    );
+    CREATE INDEX relations_child ON relations(kind, child);
+    CREATE TABLE activity (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        activity_id TEXT NOT NULL,
+        type TEXT NOT NULL CHECK (type IN ('start', 'stop')),
+        created_at INTEGER NOT NULL,
+        end_time INTEGER
+    );
+    CREATE INDEX activity_created_at ON activity(created_at);
+    CREATE TABLE frames (
+        -- Same id as in the frame history index.
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        file TEXT NOT NULL
+    );
+    CREATE INDEX frames_timestamp ON frames(timestamp);
+    CREATE TABLE verdicts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp INTEGER NOT NULL,
+        frame_id INTEGER REFERENCES frames(id) ON DELETE SET NULL,
+        activity_id TEXT,
+        classifier TEXT NOT NULL,
+        verdict TEXT NOT NULL
+    );
+    CREATE INDEX verdicts_timestamp ON verdicts(timestamp);
+    ",
+    "
+    -- ForegroundInfo as JSON.
+    ALTER TABLE frames ADD COLUMN foreground TEXT;
+    ",
+    "
+    ALTER TABLE verdicts ADD COLUMN prompt_version TEXT;
+    CREATE TABLE prompt_templates (
+        version TEXT PRIMARY KEY,
+        system TEXT NOT NULL,
+        user TEXT NOT NULL
+    );
+    ",
+    "
+    -- Graph schema 2: edges move from each node's `children` into `depends`.
+    -- Children that do not exist are dropped.
+    INSERT OR IGNORE INTO relations (kind, parent, child)
+        SELECT 'depends', nodes.id, child.value
+        FROM nodes, json_each(nodes.data, '$.children') AS child
+        WHERE child.value IN (SELECT id FROM nodes);
+    UPDATE nodes SET data = json_remove(data, '$.children')
+        WHERE json_type(data, '$.children') IS NOT NULL;
+    ",
+    "
+    CREATE TABLE undo_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        -- Every 'undo' entry is older than every 'redo' entry.
+        stack TEXT NOT NULL CHECK (stack IN ('undo', 'redo')),
+        label TEXT NOT NULL,
+        -- undo::Op list as JSON.
+        ops TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    ",
+];
+
+/// The `user_version` of a fully migrated database.
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+/// All times are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub activity_id: String,
+    #[serde(rename = "type")]
+    pub kind: ActivityKind,
+    pub created_at: i64,
+    pub end_time: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Start,
+    Stop,
+}
+
+impl ActivityKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ActivityKind::Start => "start",
+            ActivityKind::Stop => "stop",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relation {
+    pub parent: String,
+    pub child: String,
+}
+
+/// Nodes plus relations grouped by kind, shaped like the frontend's `ToposorterStateData`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: serde_json::Map<String, Value>,
+    #[serde(default)]
+    pub relations: std::collections::BTreeMap<String, Vec<Relation>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerdictRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub timestamp: i64,
+    pub frame_id: Option<i64>,
+    pub activity_id: Option<String>,
+    /// Which classifier backend and model produced the verdict.
+    pub classifier: String,
+    /// Look up the text in `prompt_templates`.
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+    pub verdict: Value,
+}
+
+/// A prompt template as it was when verdicts were made with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptTemplateRow {
+    pub version: String,
+    pub system: String,
+    pub user: String,
+}
+
+/// A group of changes that can be undone together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoRow {
+    #[serde(default)]
+    pub id: Option<i64>,
+    pub label: String,
+    pub ops: Vec<Op>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndoStack {
+    Undo,
+    Redo,
+}
+
+impl UndoStack {
+    fn as_str(self) -> &'static str {
+        match self {
+            UndoStack::Undo => "undo",
+            UndoStack::Redo => "redo",
+        }
+    }
+}
+
+pub struct Db {
+    conn: Connection,
+}
+
+impl Db {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Db::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Db::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        let mut db = Db { conn };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let version: usize = self.conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            // Written by a newer app; running it against an older schema could lose data.
+            return Err(Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN),
+                Some(format!(
+                    "the database has schema version {}, but this app only knows up to {}",
+                    version, SCHEMA_VERSION
+                )),
+            ));
+        }
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+            println!("[info]: applied database migration {}", i + 1);
+        }
+        Ok(())
+    }
+
+    /// Runs `f` in a transaction. `f` must not start one itself.
+    pub fn atomically<T>(&self, f: impl FnOnce(&Db) -> Result<T>) -> Result<T> {
+        let tx = self.conn.unchecked_transaction()?;
+        let out = f(self)?;
+        tx.commit()?;
+        Ok(out)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT (SELECT COUNT(*) FROM nodes) + (SELECT COUNT(*) FROM activity)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count == 0)
+    }
+
+    // Nodes and relations
+
+    /// Legacy `children` in `data` become `depends` relations, skipping missing nodes.
+    pub fn put_node(&self, id: &str, data: &Value) -> Result<()> {
+        let mut data = strip_derived(data);
+        let children = data.as_object_mut().and_then(|data| data.remove("children"));
+        self.conn.execute(
+            "INSERT INTO nodes (id, data, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(id) DO UPDATE SET data = excluded.data, updated_at = excluded.updated_at",
+            params![id, data.to_string(), now_millis()],
+        )?;
+        for child in children.iter().filter_map(Value::as_array).flatten().filter_map(Value::as_str) {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO relations (kind, parent, child)
+                 SELECT 'depends', ?1, id FROM nodes WHERE id = ?2",
+                params![id, child],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_node(&self, id: &str) -> Result<Option<Value>> {
+        self.conn
+            .query_row("SELECT data FROM nodes WHERE id = ?1", [id], |row| json_column(row, 0))
+            .optional()
+    }
+
+    /// Also removes the node's relations.
+    pub fn delete_node(&self, id: &str) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM nodes WHERE id = ?1", [id])? > 0)
+    }
+
+    pub fn link(&self, kind: &str, parent: &str, child: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO relations (kind, parent, child) VALUES (?1, ?2, ?3)",
+            params![kind, parent, child],
+        )?;
+        Ok(())
+    }
+
+    pub fn unlink(&self, kind: &str, parent: &str, child: &str) -> Result<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM relations WHERE kind = ?1 AND parent = ?2 AND child = ?3",
+            params![kind, parent, child],
+        )?;
+        Ok(removed > 0)
+    }
+
+    pub fn graph(&self) -> Result<Graph> {
+        let mut graph = Graph::default();
+        let mut nodes = self.conn.prepare("SELECT id, data FROM nodes ORDER BY rowid")?;
+        for node in nodes.query_map([], |row| Ok((row.get::<_, String>(0)?, json_column(row, 1)?)))? {
+            let (id, data) = node?;
+            graph.nodes.insert(id, data);
+        }
+        let mut relations = self
+            .conn
+            .prepare("SELECT kind, parent, child FROM relations ORDER BY rowid")?;
+        for relation in relations.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, Relation { parent: row.get(1)?, child: row.get(2)? }))
+        })? {
+            let (kind, relation) = relation?;
+            graph.relations.entry(kind).or_default().push(relation);
+        }
+        Ok(graph)
+    }
+
+    /// Replaces all nodes and relations with `graph`, migrated to the current schema.
+    pub fn replace_graph(&mut self, graph: &Graph) -> Result<()> {
+        let mut graph = graph.clone();
+        let report = graph_schema::migrate(&mut graph);
+        if !report.dangling.is_empty() {
+            println!("[error]: dropped {} edges to missing nodes", report.dangling.len());
+        }
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM relations", [])?;
+        tx.execute("DELETE FROM nodes", [])?;
+        let now = now_millis();
+        for (id, data) in &graph.nodes {
+            tx.execute(
+                "INSERT INTO nodes (id, data, updated_at) VALUES (?1, ?2, ?3)",
+                params![id, strip_derived(data).to_string(), now],
+            )?;
+        }
+        for (kind, relations) in &graph.relations {
+            for relation in relations {
+                tx.execute(
+                    "INSERT OR IGNORE INTO relations (kind, parent, child) VALUES (?1, ?2, ?3)",
+                    params![kind, relation.parent, relation.child],
+                )?;
+            }
+        }
+        tx.commit()
+    }
+
+    // Activity
+
+    pub fn insert_activity(&self, row: &ActivityRow) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO activity (activity_id, type, created_at, end_time) VALUES (?1, ?2, ?3, ?4)",
+            params![row.activity_id, row.kind.as_str(), row.created_at, row.end_time],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Inserts `row` with its own id, to bring back a deleted row.
+    pub fn restore_activity(&self, row: &ActivityRow) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO activity (id, activity_id, type, created_at, end_time) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![row.id, row.activity_id, row.kind.as_str(), row.created_at, row.end_time],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_activity(&self, id: i64) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM activity WHERE id = ?1", [id])? > 0)
+    }
+
+    /// Like `insert_activity`, but skips a row that repeats the last one, since every
+    /// window mirrors the same start/stop. Returns the new row id, if any.
+    pub fn log_activity(&self, row: &ActivityRow) -> Result<Option<i64>> {
+        if let Some(last) = self.last_activity()? {
+            if last.activity_id == row.activity_id && last.kind == row.kind {
+                return Ok(None);
+            }
+        }
+        self.insert_activity(row).map(Some)
+    }
+
+    /// Rows with `from <= created_at < to`, oldest first.
+    pub fn activity(&self, from: Option<i64>, to: Option<i64>) -> Result<Vec<ActivityRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, activity_id, type, created_at, end_time FROM activity
+             WHERE created_at >= ?1 AND created_at < ?2 ORDER BY created_at, id",
+        )?;
+        let rows = stmt.query_map(
+            params![from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX)],
+            activity_row,
+        )?;
+        rows.collect()
+    }
+
+    pub fn last_activity(&self) -> Result<Option<ActivityRow>> {
+        self.conn
+            .query_row(
+                "SELECT id, activity_id, type, created_at, end_time FROM activity
+                 ORDER BY created_at DESC, id DESC LIMIT 1",
+                [],
+                activity_row,
+            )
+            .optional()
+    }
+
+    // Frames and verdicts
+
+    /// Fails if `id` is taken: ids come from the frame history, which never reuses them.
+    pub fn insert_frame(&self, id: i64, timestamp: i64, file: &str, foreground: Option<&ForegroundInfo>) -> Result<()> {
+        let foreground = foreground.map(|foreground| serde_json::to_string(foreground).unwrap_or_default());
+        self.conn.execute(
+            "INSERT INTO frames (id, timestamp, file, foreground) VALUES (?1, ?2, ?3, ?4)",
+            params![id, timestamp, file, foreground],
+        )?;
+        Ok(())
+    }
+
+    /// One more than the largest frame id ever recorded.
+    pub fn next_frame_id(&self) -> Result<i64> {
+        self.conn.query_row("SELECT COALESCE(MAX(id) + 1, 0) FROM frames", [], |row| row.get(0))
+    }
+
+    /// Forgets frames the frame history has pruned.
+    pub fn delete_frames_before(&self, timestamp: i64) -> Result<usize> {
+        self.conn.execute("DELETE FROM frames WHERE timestamp < ?1", [timestamp])
+    }
+
+    pub fn insert_verdict(&self, row: &VerdictRow) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO verdicts (timestamp, frame_id, activity_id, classifier, prompt_version, verdict)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                row.timestamp,
+                row.frame_id,
+                row.activity_id,
+                row.classifier,
+                row.prompt_version,
+                row.verdict.to_string()
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Verdicts with `from <= timestamp < to`, oldest first.
+    pub fn verdicts(&self, from: Option<i64>, to: Option<i64>) -> Result<Vec<VerdictRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, frame_id, activity_id, classifier, prompt_version, verdict FROM verdicts
+             WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp, id",
+        )?;
+        let rows = stmt.query_map(params![from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX)], |row| {
+            Ok(VerdictRow {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                frame_id: row.get(2)?,
+                activity_id: row.get(3)?,
+                classifier: row.get(4)?,
+                prompt_version: row.get(5)?,
+                verdict: json_column(row, 6)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Keeps the first text stored for each version.
+    pub fn save_prompt_templates(&self, templates: &[PromptTemplate]) -> Result<()> {
+        for template in templates {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO prompt_templates (version, system, user) VALUES (?1, ?2, ?3)",
+                params![template.version, template.system, template.user],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn prompt_templates(&self) -> Result<Vec<PromptTemplateRow>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT version, system, user FROM prompt_templates ORDER BY version")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(PromptTemplateRow {
+                version: row.get(0)?,
+                system: row.get(1)?,
+                user: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    // Undo log
+
+    /// Adds an undo entry, clears the redo stack and drops undo entries beyond `limit`.
+    pub fn push_undo(&self, entry: &UndoRow, limit: usize) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM undo_log WHERE stack = 'redo'", [])?;
+        tx.execute(
+            "INSERT INTO undo_log (stack, label, ops, created_at) VALUES ('undo', ?1, ?2, ?3)",
+            params![entry.label, serde_json::to_string(&entry.ops).expect("ops serialize"), entry.created_at],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.execute(
+            "DELETE FROM undo_log WHERE stack = 'undo' AND id NOT IN
+             (SELECT id FROM undo_log WHERE stack = 'undo' ORDER BY id DESC LIMIT ?1)",
+            [limit as i64],
+        )?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Replaces the ops of a stored entry, for groups that grow after they are stored.
+    pub fn update_undo_ops(&self, id: i64, ops: &[Op]) -> Result<()> {
+        self.conn.execute(
+            "UPDATE undo_log SET ops = ?1 WHERE id = ?2",
+            params![serde_json::to_string(ops).expect("ops serialize"), id],
+        )?;
+        Ok(())
+    }
+
+    /// The entry `undo` or `redo` would apply next.
+    pub fn undo_entry(&self, stack: UndoStack) -> Result<Option<UndoRow>> {
+        let order = match stack {
+            UndoStack::Undo => "DESC",
+            UndoStack::Redo => "ASC",
+        };
+        self.conn
+            .query_row(
+                &format!(
+                    "SELECT id, label, ops, created_at FROM undo_log WHERE stack = ?1 ORDER BY id {} LIMIT 1",
+                    order
+                ),
+                [stack.as_str()],
+                |row| {
+                    Ok(UndoRow {
+                        id: row.get(0)?,
+                        label: row.get(1)?,
+                        ops: json_column(row, 2)?,
+                        created_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    pub fn move_undo_entry(&self, id: i64, stack: UndoStack) -> Result<()> {
+        self.conn
+            .execute("UPDATE undo_log SET stack = ?1 WHERE id = ?2", params![stack.as_str(), id])?;
+        Ok(())
+    }
+
+    /// One-off import of the data the webview used to keep in `localStorage`.
+    /// Does nothing unless the database is empty, so it is safe to call on every launch.
+    pub fn import(&mut self, graph: &Graph, activity: &[ActivityRow]) -> Result<bool> {
+        if !self.is_empty()? {
+            return Ok(false);
+        }
+        self.replace_graph(graph)?;
+        let tx = self.conn.transaction()?;
+        for row in activity {
+            tx.execute(
+                "INSERT INTO activity (activity_id, type, created_at, end_time) VALUES (?1, ?2, ?3, ?4)",
+                params![row.activity_id, row.kind.as_str(), row.created_at, row.end_time],
+            )?;
+        }
+        tx.commit()?;
+        Ok(true)
+    }
+}
+
+fn activity_row(row: &Row) -> Result<ActivityRow> {
+    Ok(ActivityRow {
+        id: row.get(0)?,
+        activity_id: row.get(1)?,
+        kind: match row.get_ref(2)?.as_str()? {
+            "start" => ActivityKind::Start,
+            _ => ActivityKind::Stop,
+        },
+        created_at: row.get(3)?,
+        end_time: row.get(4)?,
+    })
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &Row, index: usize) -> Result<T> {
+    let text: String = row.get(index)?;
+    serde_json::from_str(&text)
+        .map_err(|e| Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+/// Drops the `__`-prefixed fields the frontend derives on load.
+fn strip_derived(data: &Value) -> Value {
+    match data {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| !key.starts_with("__"))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
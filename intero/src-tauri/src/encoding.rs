@@ -0,0 +1,132 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{imageops, ColorType, ImageEncoder, ImageResult, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::frame_source::{CapturedFrame, PixelFormat};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum Encoding {
+    #[default]
+    Png,
+    /// `quality` is 1-100.
+    Jpeg { quality: u8 },
+    /// Always lossless; the pure-Rust encoder has no lossy mode.
+    Webp,
+}
+
+impl Encoding {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Encoding::Png => "image/png",
+            Encoding::Jpeg { .. } => "image/jpeg",
+            Encoding::Webp => "image/webp",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodeOptions {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub encoding: Encoding,
+}
+
+/// Downscales (never upscales) and encodes a raw frame.
+pub fn encode_frame(frame: &CapturedFrame, options: &EncodeOptions) -> ImageResult<Vec<u8>> {
+    let image = scaled_rgba(frame, options.max_width, options.max_height);
+    encode(&image, options.encoding)
+}
+
+/// Converts a raw frame to RGBA that fits within the given bounds.
+/// Large reductions are done with an integer box filter straight from the raw buffer,
+/// which is much cheaper than converting at full size and resampling afterwards.
+pub fn scaled_rgba(frame: &CapturedFrame, max_width: Option<u32>, max_height: Option<u32>) -> RgbaImage {
+    let max_width = max_width.unwrap_or(frame.width).max(1);
+    let max_height = max_height.unwrap_or(frame.height).max(1);
+    let factor = u32::max(frame.width / max_width, frame.height / max_height);
+    let image = if factor >= 2 {
+        box_downsample(frame, factor)
+    } else {
+        frame.to_rgba()
+    };
+    downscale(image, Some(max_width), Some(max_height))
+}
+
+/// Averages each `factor`×`factor` block of the frame into one opaque RGBA pixel.
+fn box_downsample(frame: &CapturedFrame, factor: u32) -> RgbaImage {
+    let out_width = (frame.width / factor).max(1) as usize;
+    let out_height = (frame.height / factor).max(1) as usize;
+    let (r, b) = match frame.format {
+        PixelFormat::Rgba => (0, 2),
+        PixelFormat::Bgra => (2, 0),
+    };
+    let f = factor as usize;
+    let count = (f * f) as u32;
+    let mut sums = vec![0u32; out_width * 3];
+    let mut out = Vec::with_capacity(out_width * out_height * 4);
+    for block_y in 0..out_height {
+        sums.fill(0);
+        for y in block_y * f..(block_y + 1) * f {
+            let row = &frame.data[y * frame.stride..][..out_width * f * 4];
+            for (block, sum) in row.chunks_exact(f * 4).zip(sums.chunks_exact_mut(3)) {
+                for px in block.chunks_exact(4) {
+                    sum[0] += px[r] as u32;
+                    sum[1] += px[1] as u32;
+                    sum[2] += px[b] as u32;
+                }
+            }
+        }
+        for sum in sums.chunks_exact(3) {
+            out.extend_from_slice(&[
+                (sum[0] / count) as u8,
+                (sum[1] / count) as u8,
+                (sum[2] / count) as u8,
+                255,
+            ]);
+        }
+    }
+    RgbaImage::from_raw(out_width as u32, out_height as u32, out).expect("buffer sized to fit")
+}
+
+/// Shrinks `image` to fit within the given bounds, keeping its aspect ratio.
+pub fn downscale(image: RgbaImage, max_width: Option<u32>, max_height: Option<u32>) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let max_width = max_width.unwrap_or(width).max(1);
+    let max_height = max_height.unwrap_or(height).max(1);
+    if width <= max_width && height <= max_height {
+        return image;
+    }
+    let scale = f64::min(
+        max_width as f64 / width as f64,
+        max_height as f64 / height as f64,
+    );
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    imageops::thumbnail(&image, new_width, new_height)
+}
+
+pub fn encode(image: &RgbaImage, encoding: Encoding) -> ImageResult<Vec<u8>> {
+    let mut out = Cursor::new(Vec::new());
+    let (width, height) = image.dimensions();
+    match encoding {
+        Encoding::Png => {
+            PngEncoder::new(&mut out).write_image(image, width, height, ColorType::Rgba8)?
+        }
+        Encoding::Jpeg { quality } => {
+            // JPEG has no alpha channel.
+            let rgb: RgbImage = image::buffer::ConvertBuffer::convert(image);
+            JpegEncoder::new_with_quality(&mut out, quality.clamp(1, 100))
+                .write_image(&rgb, width, height, ColorType::Rgb8)?
+        }
+        Encoding::Webp => {
+            WebPEncoder::new_lossless(&mut out).write_image(image, width, height, ColorType::Rgba8)?
+        }
+    }
+    Ok(out.into_inner())
+}
@@ -1,5 +1,56 @@
-use swift_rs::swift;
-use swift_rs::SRData;
+use swift_rs::{swift, Double, Int, SRData, SRObject, SRString};
+
+/// Layout must match the `RawFrame` class in `FFI.swift`.
+#[repr(C)]
+pub struct RawFrame {
+    pub width: Int,
+    pub height: Int,
+    pub bytes_per_row: Int,
+    /// Increases by one for every frame delivered by the stream.
+    pub sequence: Int,
+    /// Seconds since 1970.
+    pub timestamp: Double,
+    pub display_id: Int,
+    /// The area of the screen the frame covers, in global screen points.
+    pub screen_x: Double,
+    pub screen_y: Double,
+    pub screen_width: Double,
+    pub screen_height: Double,
+    /// BGRA pixels, `bytes_per_row * height` bytes.
+    pub data: SRData,
+}
+
+// Values of `get_status`, matching `RecorderStatus` in `FFI.swift`.
+pub const STATUS_IDLE: Int = 0;
+pub const STATUS_STARTING: Int = 1;
+pub const STATUS_RUNNING: Int = 2;
+pub const STATUS_PERMISSION_DENIED: Int = 3;
+pub const STATUS_FAILED: Int = 4;
 
 swift!(pub fn start());
-swift!(pub fn get_last_frame() -> Option<SRData>);
\ No newline at end of file
+swift!(pub fn stop());
+swift!(pub fn get_status() -> Int);
+swift!(pub fn get_last_frame() -> Option<SRObject<RawFrame>>);
+
+/// Layout must match the `RawForeground` class in `FFI.swift`. Missing values are empty.
+#[repr(C)]
+pub struct RawForeground {
+    pub pid: Int,
+    pub bundle_id: SRString,
+    pub name: SRString,
+    pub title: SRString,
+}
+
+swift!(pub fn get_foreground() -> Option<SRObject<RawForeground>>);
+swift!(pub fn get_browser_url(bundle_id: &SRString) -> Option<SRString>);
+/// JSON shaped like `redaction::ScreenLayout`.
+swift!(pub fn get_screen_layout() -> SRString);
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    /// Whether the app already has screen recording permission. Never prompts.
+    pub fn CGPreflightScreenCaptureAccess() -> bool;
+    /// Shows the system prompt the first time it is called; afterwards the user
+    /// has to grant access in System Settings.
+    pub fn CGRequestScreenCaptureAccess() -> bool;
+}
\ No newline at end of file
@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::verdict::{Category, Verdict};
+
+/// Weight of a verdict whose model did not report a confidence.
+const UNRATED_WEIGHT: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusState {
+    /// No verdicts yet, or none that agree enough.
+    Unknown,
+    Focused,
+    Distracted,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FocusConfig {
+    /// Verdicts older than this stop counting, except the latest one: an unchanged
+    /// screen still shows what it was classified as.
+    pub window_ms: u64,
+    /// Share of the confidence-weighted verdicts in the window a category needs to lead.
+    pub threshold: f32,
+    /// How long a category has to keep the lead before the state flips to it.
+    pub min_dwell_ms: u64,
+    /// How long after a context switch the state cannot flip to `Distracted`.
+    pub switch_grace_ms: u64,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        FocusConfig {
+            window_ms: 60_000,
+            threshold: 0.6,
+            min_dwell_ms: 10_000,
+            switch_grace_ms: 15_000,
+        }
+    }
+}
+
+/// Payload of the `focus-state-changed` event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FocusChange {
+    pub state: FocusState,
+    pub previous: FocusState,
+    /// Ms since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    timestamp: u64,
+    category: Category,
+    weight: f32,
+}
+
+/// Turns a stream of per-frame verdicts into a focus state that only changes once the
+/// verdicts have agreed for a while. Times are ms since the Unix epoch.
+pub struct FocusTracker {
+    pub config: FocusConfig,
+    samples: VecDeque<Sample>,
+    state: FocusState,
+    /// The state the window favours, and since when, while it differs from `state`.
+    leading: Option<(FocusState, u64)>,
+    grace_until: u64,
+}
+
+impl FocusTracker {
+    pub fn new(config: FocusConfig) -> Self {
+        FocusTracker {
+            config,
+            samples: VecDeque::new(),
+            state: FocusState::Unknown,
+            leading: None,
+            grace_until: 0,
+        }
+    }
+
+    pub fn state(&self) -> FocusState {
+        self.state
+    }
+
+    pub fn observe(&mut self, now: u64, verdict: &Verdict) -> Option<FocusChange> {
+        self.samples.push_back(Sample {
+            timestamp: now,
+            category: verdict.category,
+            weight: verdict.confidence.unwrap_or(UNRATED_WEIGHT),
+        });
+        self.tick(now)
+    }
+
+    /// Forgets verdicts from before the switch and holds off `Distracted` for a while,
+    /// since the screen is in flux right after switching tasks or windows.
+    pub fn context_switch(&mut self, now: u64) {
+        self.samples.clear();
+        self.leading = None;
+        self.grace_until = now + self.config.switch_grace_ms;
+    }
+
+    /// Re-evaluates the window. Call periodically so a state can settle while the
+    /// screen, and so the verdicts, stay the same.
+    pub fn tick(&mut self, now: u64) -> Option<FocusChange> {
+        while self.samples.len() > 1 && self.samples[0].timestamp + self.config.window_ms <= now {
+            self.samples.pop_front();
+        }
+        let leading = match self.leading_state() {
+            Some(leading) if leading != self.state => leading,
+            _ => {
+                self.leading = None;
+                return None;
+            }
+        };
+        if leading == FocusState::Distracted && now < self.grace_until {
+            self.leading = None;
+            return None;
+        }
+        let since = match self.leading {
+            Some((state, since)) if state == leading => since,
+            _ => {
+                self.leading = Some((leading, now));
+                now
+            }
+        };
+        if now.saturating_sub(since) < self.config.min_dwell_ms {
+            return None;
+        }
+        let change = FocusChange {
+            state: leading,
+            previous: self.state,
+            timestamp: now,
+        };
+        self.state = leading;
+        self.leading = None;
+        Some(change)
+    }
+
+    /// The state whose category has at least `threshold` of the weight in the window.
+    /// `Unknown` verdicts count towards the total, so they dilute both sides.
+    fn leading_state(&self) -> Option<FocusState> {
+        let weight = |category: Category| -> f32 {
+            self.samples
+                .iter()
+                .filter(|sample| sample.category == category)
+                .map(|sample| sample.weight)
+                .sum()
+        };
+        let total: f32 = self.samples.iter().map(|sample| sample.weight).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        if weight(Category::Work) / total >= self.config.threshold {
+            Some(FocusState::Focused)
+        } else if weight(Category::Distraction) / total >= self.config.threshold {
+            Some(FocusState::Distracted)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// What the user has in front of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForegroundInfo {
+    /// Bundle id on macOS, `WM_CLASS` class on X11.
+    pub app_id: String,
+    pub app_name: String,
+    pub pid: Option<u32>,
+    pub title: Option<String>,
+    /// The active tab's URL, for browsers that expose it.
+    pub url: Option<String>,
+}
+
+pub trait ForegroundProvider: Send + Sync {
+    /// `None` when nothing is focused or the platform will not say.
+    fn foreground(&self) -> Option<ForegroundInfo>;
+}
+
+/// Payload of the `foreground-changed` event.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ForegroundChanged {
+    pub foreground: Option<ForegroundInfo>,
+    pub previous: Option<ForegroundInfo>,
+    /// Ms since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl ForegroundChanged {
+    /// Whether another application came to the front, rather than the same one
+    /// changing its window or tab.
+    pub fn switched_app(&self) -> bool {
+        let app_id = |info: &Option<ForegroundInfo>| info.as_ref().map(|info| info.app_id.clone());
+        app_id(&self.foreground) != app_id(&self.previous)
+    }
+}
+
+/// Remembers the last answer of a provider so callers only hear about changes.
+pub struct ForegroundWatcher {
+    provider: Box<dyn ForegroundProvider>,
+    current: Mutex<Option<ForegroundInfo>>,
+}
+
+impl ForegroundWatcher {
+    pub fn new(provider: Box<dyn ForegroundProvider>) -> Self {
+        ForegroundWatcher {
+            provider,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// As of the last `poll`.
+    pub fn current(&self) -> Option<ForegroundInfo> {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub fn poll(&self, now: u64) -> Option<ForegroundChanged> {
+        let foreground = self.provider.foreground();
+        let mut current = self.current.lock().unwrap();
+        if *current == foreground {
+            return None;
+        }
+        let previous = std::mem::replace(&mut *current, foreground.clone());
+        Some(ForegroundChanged {
+            foreground,
+            previous,
+            timestamp: now,
+        })
+    }
+}
+
+/// Picks a provider from `INTERO_FOREGROUND`:
+///   - unset / `native`: the platform's window manager
+///   - `fake`: nothing in front
+///   - `fake:<path>`: a JSON array of foreground states (`null` for nothing), one per
+///     poll, then the last one
+pub fn from_env() -> Box<dyn ForegroundProvider> {
+    let spec = std::env::var("INTERO_FOREGROUND").unwrap_or_default();
+    from_spec(&spec).unwrap_or_else(|err| {
+        println!("[error]: {}, using the native foreground provider", err);
+        native()
+    })
+}
+
+/// Parses an `INTERO_FOREGROUND` value, see `from_env`.
+pub fn from_spec(spec: &str) -> Result<Box<dyn ForegroundProvider>, String> {
+    Ok(match spec.split_once(':') {
+        Some(("fake", path)) => {
+            let read = |path| -> Result<Vec<Option<ForegroundInfo>>, String> {
+                let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+                serde_json::from_str(&json).map_err(|e| e.to_string())
+            };
+            let steps = read(path).map_err(|err| format!("Could not read the foreground script {:?}: {}", path, err))?;
+            Box::new(ScriptedForeground::new(steps))
+        }
+        _ if spec == "fake" => Box::new(ScriptedForeground::default()),
+        _ if spec.is_empty() || spec == "native" => native(),
+        _ => return Err(format!("Unknown foreground provider {:?}", spec)),
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn native() -> Box<dyn ForegroundProvider> {
+    Box::new(MacForeground::default())
+}
+
+#[cfg(target_os = "linux")]
+fn native() -> Box<dyn ForegroundProvider> {
+    Box::new(X11Foreground::default())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn native() -> Box<dyn ForegroundProvider> {
+    println!("[error]: no foreground provider on this platform");
+    Box::new(ScriptedForeground::default())
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+// macOS
+
+/// NSWorkspace for the app, the window list for its title and AppleScript for browser URLs.
+#[cfg(target_os = "macos")]
+#[derive(Default)]
+pub struct MacForeground {
+    /// The URL last read for an app and title. AppleScript is slow, and tabs rarely
+    /// change without the title changing too.
+    url: Mutex<Option<((String, Option<String>), Option<String>)>>,
+}
+
+#[cfg(target_os = "macos")]
+impl ForegroundProvider for MacForeground {
+    fn foreground(&self) -> Option<ForegroundInfo> {
+        let raw = unsafe { crate::ffi::get_foreground() }?;
+        let app_id = raw.bundle_id.to_string();
+        let title = non_empty(raw.title.to_string());
+        let key = (app_id.clone(), title.clone());
+        let mut cached = self.url.lock().unwrap();
+        let url = match cached.as_ref() {
+            Some((cached_key, url)) if *cached_key == key => url.clone(),
+            _ => {
+                let url = unsafe { crate::ffi::get_browser_url(&raw.bundle_id) }.map(|url| url.to_string());
+                *cached = Some((key, url.clone()));
+                url
+            }
+        };
+        Some(ForegroundInfo {
+            app_id,
+            app_name: raw.name.to_string(),
+            pid: u32::try_from(raw.pid).ok(),
+            title,
+            url,
+        })
+    }
+}
+
+// X11
+
+/// The EWMH `_NET_ACTIVE_WINDOW` of the default screen. Browsers do not publish
+/// their URL, so `url` is always `None`.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+pub struct X11Foreground {
+    conn: Mutex<Option<X11Connection>>,
+}
+
+/// Shared with the window list in `redaction`.
+#[cfg(target_os = "linux")]
+pub(crate) struct X11Connection {
+    pub(crate) conn: x11rb::rust_connection::RustConnection,
+    pub(crate) root: u32,
+    active_window: u32,
+    wm_name: u32,
+    wm_pid: u32,
+    utf8_string: u32,
+    pub(crate) wm_window_type: u32,
+    pub(crate) window_type_notification: u32,
+    pub(crate) client_list_stacking: u32,
+}
+
+#[cfg(target_os = "linux")]
+impl X11Connection {
+    pub(crate) fn connect() -> Result<Self, Box<dyn std::error::Error>> {
+        use x11rb::protocol::xproto::ConnectionExt as _;
+
+        let (conn, screen) = x11rb::connect(None)?;
+        let root = x11rb::connection::Connection::setup(&conn).roots[screen].root;
+        let atom = |name: &[u8]| -> Result<u32, Box<dyn std::error::Error>> {
+            Ok(conn.intern_atom(false, name)?.reply()?.atom)
+        };
+        Ok(X11Connection {
+            root,
+            active_window: atom(b"_NET_ACTIVE_WINDOW")?,
+            wm_name: atom(b"_NET_WM_NAME")?,
+            wm_pid: atom(b"_NET_WM_PID")?,
+            utf8_string: atom(b"UTF8_STRING")?,
+            wm_window_type: atom(b"_NET_WM_WINDOW_TYPE")?,
+            window_type_notification: atom(b"_NET_WM_WINDOW_TYPE_NOTIFICATION")?,
+            client_list_stacking: atom(b"_NET_CLIENT_LIST_STACKING")?,
+            conn,
+        })
+    }
+
+    pub(crate) fn property(&self, window: u32, property: u32, kind: u32) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        use x11rb::protocol::xproto::ConnectionExt as _;
+
+        Ok(self.conn.get_property(false, window, property, kind, 0, u32::MAX / 4)?.reply()?.value)
+    }
+
+    /// The `WM_CLASS` class, or the instance name if there is no class.
+    pub(crate) fn app_id(&self, window: u32) -> Result<String, Box<dyn std::error::Error>> {
+        use x11rb::protocol::xproto::AtomEnum;
+
+        // WM_CLASS is "instance\0class\0".
+        let class = self.property(window, AtomEnum::WM_CLASS.into(), AtomEnum::STRING.into())?;
+        let class = String::from_utf8_lossy(&class);
+        let mut parts = class.split('\0');
+        let instance = parts.next().unwrap_or_default().to_string();
+        Ok(parts.next().filter(|class| !class.is_empty()).map_or(instance, str::to_string))
+    }
+
+    fn foreground(&self) -> Result<Option<ForegroundInfo>, Box<dyn std::error::Error>> {
+        use x11rb::protocol::xproto::AtomEnum;
+
+        let active = self.property(self.root, self.active_window, AtomEnum::WINDOW.into())?;
+        let window = match active.get(..4) {
+            Some(bytes) => u32::from_ne_bytes(bytes.try_into()?),
+            None => return Ok(None),
+        };
+        if window == 0 {
+            return Ok(None);
+        }
+        let app_id = self.app_id(window)?;
+        let mut title = self.property(window, self.wm_name, self.utf8_string)?;
+        if title.is_empty() {
+            title = self.property(window, AtomEnum::WM_NAME.into(), AtomEnum::STRING.into())?;
+        }
+        let pid = self.property(window, self.wm_pid, AtomEnum::CARDINAL.into())?;
+        Ok(Some(ForegroundInfo {
+            app_name: app_id.clone(),
+            app_id,
+            pid: pid.get(..4).and_then(|bytes| bytes.try_into().ok()).map(u32::from_ne_bytes),
+            title: non_empty(String::from_utf8_lossy(&title).into_owned()),
+            url: None,
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl ForegroundProvider for X11Foreground {
+    fn foreground(&self) -> Option<ForegroundInfo> {
+        let mut conn = self.conn.lock().unwrap();
+        if conn.is_none() {
+            *conn = X11Connection::connect()
+                .map_err(|err| println!("[error]: could not connect to X11 for the active window: {}", err))
+                .ok();
+        }
+        match conn.as_ref()?.foreground() {
+            Ok(foreground) => foreground,
+            Err(err) => {
+                // Reconnect on the next call.
+                println!("[error]: could not read the active window: {}", err);
+                conn.take();
+                None
+            }
+        }
+    }
+}
+
+// Fake
+
+/// Plays back a script of foreground states, one per call, then keeps returning the last.
+#[derive(Default)]
+pub struct ScriptedForeground {
+    steps: Mutex<VecDeque<Option<ForegroundInfo>>>,
+    last: Mutex<Option<ForegroundInfo>>,
+}
+
+impl ScriptedForeground {
+    pub fn new(steps: impl IntoIterator<Item = Option<ForegroundInfo>>) -> Self {
+        ScriptedForeground {
+            steps: Mutex::new(steps.into_iter().collect()),
+            last: Mutex::new(None),
+        }
+    }
+
+    pub fn push(&self, step: Option<ForegroundInfo>) {
+        self.steps.lock().unwrap().push_back(step);
+    }
+}
+
+impl ForegroundProvider for ScriptedForeground {
+    fn foreground(&self) -> Option<ForegroundInfo> {
+        let mut last = self.last.lock().unwrap();
+        if let Some(step) = self.steps.lock().unwrap().pop_front() {
+            *last = step;
+        }
+        last.clone()
+    }
+}
@@ -0,0 +1,290 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::encoding::{self, Encoding};
+use crate::foreground::ForegroundInfo;
+use crate::frame_source::{CapturedFrame, ScreenRect};
+use crate::screenshot::{millis_since_epoch, serialize_base64};
+
+const INDEX_FILE: &str = "index.jsonl";
+/// The next frame id, kept apart from the index so ids are never reused once the
+/// frames that had them are pruned.
+const NEXT_ID_FILE: &str = "next_id";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Oldest frames are deleted once the stored images exceed this many bytes.
+    pub max_bytes: u64,
+    /// Frames older than this are deleted.
+    pub max_age_secs: u64,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_bytes: 1024 * 1024 * 1024,
+            max_age_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FrameHistoryConfig {
+    pub retention: RetentionPolicy,
+    /// Frames are downscaled to fit this width before they are stored.
+    pub max_width: Option<u32>,
+    pub encoding: Encoding,
+}
+
+impl Default for FrameHistoryConfig {
+    fn default() -> Self {
+        FrameHistoryConfig {
+            retention: RetentionPolicy::default(),
+            max_width: Some(1920),
+            encoding: Encoding::Jpeg { quality: 80 },
+        }
+    }
+}
+
+/// One line of the index file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub id: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub display_id: u32,
+    pub content_rect: ScreenRect,
+    pub width: u32,
+    pub height: u32,
+    pub encoding: Encoding,
+    /// How much this frame differed from the previous stored one, 0..1.
+    pub score: f32,
+    /// File name inside the history directory.
+    pub file: String,
+    pub size: u64,
+    /// What was in front when the frame was captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<ForegroundInfo>,
+}
+
+/// A stored frame as sent to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredFrame {
+    #[serde(flatten)]
+    pub record: FrameRecord,
+    /// The encoded image, base64 in JSON.
+    #[serde(serialize_with = "serialize_base64")]
+    pub bytes: Vec<u8>,
+}
+
+/// Changed frames on disk: one image file per frame plus an append-only index,
+/// pruned by `RetentionPolicy` on every insert.
+pub struct FrameHistory {
+    dir: PathBuf,
+    config: FrameHistoryConfig,
+    /// Sorted by timestamp.
+    records: Vec<FrameRecord>,
+    total_size: u64,
+    /// Never decreases, see `NEXT_ID_FILE`.
+    next_id: u64,
+}
+
+impl FrameHistory {
+    /// Opens (or creates) the history in `dir`, dropping index entries whose image is gone.
+    pub fn open(dir: impl Into<PathBuf>, config: FrameHistoryConfig) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let mut records = read_index(&dir.join(INDEX_FILE))?;
+        let before = records.len();
+        records.retain(|r| dir.join(&r.file).exists());
+        records.sort_by_key(|r| (r.timestamp, r.id));
+        let history = FrameHistory {
+            total_size: records.iter().map(|r| r.size).sum(),
+            next_id: records.iter().map(|r| r.id + 1).max().unwrap_or(0).max(read_next_id(&dir)?),
+            dir,
+            config,
+            records,
+        };
+        if history.records.len() != before {
+            history.write_index()?;
+        }
+        Ok(history)
+    }
+
+    pub fn config(&self) -> FrameHistoryConfig {
+        self.config
+    }
+
+    /// Makes new frames get ids of at least `id`, e.g. to stay clear of ids the
+    /// database still has from before `NEXT_ID_FILE` existed.
+    pub fn skip_ids_below(&mut self, id: u64) -> io::Result<()> {
+        if id > self.next_id {
+            self.write_next_id(id)?;
+            self.next_id = id;
+        }
+        Ok(())
+    }
+
+    /// Encodes and stores `frame`, then applies the retention policy.
+    pub fn insert(
+        &mut self,
+        frame: &CapturedFrame,
+        score: f32,
+        foreground: Option<ForegroundInfo>,
+    ) -> io::Result<FrameRecord> {
+        let image = encoding::scaled_rgba(frame, self.config.max_width, None);
+        let bytes = encoding::encode(&image, self.config.encoding).map_err(io::Error::other)?;
+        let timestamp = millis_since_epoch(frame.timestamp);
+        let id = self.next_id;
+        self.write_next_id(id + 1)?;
+        self.next_id += 1;
+        let file = format!("{}-{}.{}", timestamp, id, extension(self.config.encoding));
+        let path = self.dir.join(&file);
+        fs::write(&path, &bytes)?;
+
+        let record = FrameRecord {
+            id,
+            timestamp,
+            display_id: frame.display_id,
+            content_rect: frame.screen_rect,
+            width: image.width(),
+            height: image.height(),
+            encoding: self.config.encoding,
+            score,
+            file,
+            size: bytes.len() as u64,
+            foreground,
+        };
+        // Without an index line the image would never be listed or pruned.
+        if let Err(err) = self.append_index(&record) {
+            if let Err(err) = fs::remove_file(&path) {
+                println!("[error]: could not delete {}: {}", record.file, err);
+            }
+            return Err(err);
+        }
+
+        self.total_size += record.size;
+        let at = self.records.partition_point(|r| r.timestamp <= timestamp);
+        self.records.insert(at, record.clone());
+        self.prune(timestamp)?;
+        Ok(record)
+    }
+
+    /// Records with `from <= timestamp < to`, oldest first, at most `limit` of them.
+    pub fn list(&self, from: Option<u64>, to: Option<u64>, limit: Option<usize>) -> &[FrameRecord] {
+        let start = from.map_or(0, |from| self.records.partition_point(|r| r.timestamp < from));
+        let end = to.map_or(self.records.len(), |to| self.records.partition_point(|r| r.timestamp < to));
+        let records = &self.records[start..end.max(start)];
+        &records[..limit.unwrap_or(records.len()).min(records.len())]
+    }
+
+    pub fn get(&self, id: u64) -> io::Result<Option<StoredFrame>> {
+        let Some(record) = self.records.iter().find(|r| r.id == id) else {
+            return Ok(None);
+        };
+        let bytes = fs::read(self.dir.join(&record.file))?;
+        Ok(Some(StoredFrame {
+            record: record.clone(),
+            bytes,
+        }))
+    }
+
+    /// The newest frame taken at or before `timestamp`.
+    pub fn at(&self, timestamp: u64) -> io::Result<Option<StoredFrame>> {
+        let end = self.records.partition_point(|r| r.timestamp <= timestamp);
+        match end.checked_sub(1) {
+            Some(i) => self.get(self.records[i].id),
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes frames that are too old, then the oldest ones until under the size limit.
+    pub fn prune(&mut self, now: u64) -> io::Result<()> {
+        let retention = self.config.retention;
+        let min_timestamp = now.saturating_sub(Duration::from_secs(retention.max_age_secs).as_millis() as u64);
+        let mut remove = self.records.partition_point(|r| r.timestamp < min_timestamp);
+        let mut size = self.total_size - self.records[..remove].iter().map(|r| r.size).sum::<u64>();
+        while size > retention.max_bytes && remove < self.records.len() {
+            size -= self.records[remove].size;
+            remove += 1;
+        }
+        if remove == 0 {
+            return Ok(());
+        }
+        for record in self.records.drain(..remove) {
+            if let Err(err) = fs::remove_file(self.dir.join(&record.file)) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    println!("[error]: could not delete {}: {}", record.file, err);
+                }
+            }
+        }
+        self.total_size = size;
+        self.write_index()
+    }
+
+    fn append_index(&self, record: &FrameRecord) -> io::Result<()> {
+        let mut index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(INDEX_FILE))?;
+        writeln!(index, "{}", serde_json::to_string(record)?)
+    }
+
+    fn write_next_id(&self, next_id: u64) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{}.tmp", NEXT_ID_FILE));
+        fs::write(&tmp, next_id.to_string())?;
+        fs::rename(tmp, self.dir.join(NEXT_ID_FILE))
+    }
+
+    /// Rewrites the index from `records`, replacing the old one atomically.
+    fn write_index(&self) -> io::Result<()> {
+        let tmp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        let mut file = File::create(&tmp)?;
+        for record in &self.records {
+            writeln!(file, "{}", serde_json::to_string(record)?)?;
+        }
+        file.sync_all()?;
+        fs::rename(tmp, self.dir.join(INDEX_FILE))
+    }
+}
+
+fn read_next_id(dir: &Path) -> io::Result<u64> {
+    match fs::read_to_string(dir.join(NEXT_ID_FILE)) {
+        Ok(text) => text.trim().parse().map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_index(path: &Path) -> io::Result<Vec<FrameRecord>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+    let mut records = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A crash mid-append can leave a torn last line; skip it rather than lose the history.
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => println!("[error]: skipping bad frame index line: {}", err),
+        }
+    }
+    Ok(records)
+}
+
+fn extension(encoding: Encoding) -> &'static str {
+    match encoding {
+        Encoding::Png => "png",
+        Encoding::Jpeg { .. } => "jpg",
+        Encoding::Webp => "webp",
+    }
+}
@@ -0,0 +1,506 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// A rectangle in frame pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A rectangle in global screen coordinates (points on macOS, pixels on X11).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScreenRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Why a source could not produce a frame. Serialized as `{ "kind": "...", ... }`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureError {
+    NotStarted,
+    Paused,
+    PermissionDenied,
+    NoFrameYet,
+    StaleFrame { age_ms: u64 },
+    Failed { message: String },
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::NotStarted => write!(f, "capture has not been started"),
+            CaptureError::Paused => write!(f, "capture is paused"),
+            CaptureError::PermissionDenied => write!(f, "screen recording permission denied"),
+            CaptureError::NoFrameYet => write!(f, "no frame captured yet"),
+            CaptureError::StaleFrame { age_ms } => write!(f, "latest frame is {} ms old", age_ms),
+            CaptureError::Failed { message } => write!(f, "capture failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl CaptureError {
+    pub fn failed(message: impl ToString) -> Self {
+        CaptureError::Failed {
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Blue, green, red, then an alpha byte that is ignored (screens are opaque).
+    Bgra,
+    Rgba,
+}
+
+/// A single frame handed out by a `FrameSource`, as raw pixels.
+/// Scaling and encoding happen on demand, see `encoding`.
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub timestamp: SystemTime,
+    /// Increases with every new frame from the same source.
+    pub sequence: u64,
+    pub width: u32,
+    pub height: u32,
+    pub display_id: u32,
+    /// The part of the screen this frame shows.
+    pub screen_rect: ScreenRect,
+    /// Bytes per row; may be larger than `width * 4`.
+    pub stride: usize,
+    pub format: PixelFormat,
+    pub data: Vec<u8>,
+}
+
+impl CapturedFrame {
+    pub fn from_rgba(image: RgbaImage, timestamp: SystemTime, sequence: u64, display_id: u32) -> Self {
+        CapturedFrame {
+            timestamp,
+            sequence,
+            width: image.width(),
+            height: image.height(),
+            display_id,
+            screen_rect: ScreenRect {
+                x: 0.0,
+                y: 0.0,
+                width: image.width() as f64,
+                height: image.height() as f64,
+            },
+            stride: image.width() as usize * 4,
+            format: PixelFormat::Rgba,
+            data: image.into_raw(),
+        }
+    }
+
+    /// Maps a rectangle in screen coordinates to the frame pixels it covers,
+    /// clipped to the frame. `None` if they don't overlap.
+    pub fn screen_to_pixels(&self, rect: ScreenRect) -> Option<Rect> {
+        let sx = self.width as f64 / self.screen_rect.width;
+        let sy = self.height as f64 / self.screen_rect.height;
+        let left = ((rect.x - self.screen_rect.x) * sx).floor().max(0.0);
+        let top = ((rect.y - self.screen_rect.y) * sy).floor().max(0.0);
+        let right = ((rect.x + rect.width - self.screen_rect.x) * sx).ceil().min(self.width as f64);
+        let bottom = ((rect.y + rect.height - self.screen_rect.y) * sy).ceil().min(self.height as f64);
+        if !(right > left && bottom > top) {
+            return None;
+        }
+        Some(Rect {
+            x: left as u32,
+            y: top as u32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+
+    /// Copies out the given pixel rectangle, which must lie within the frame.
+    pub fn cropped(&self, rect: Rect) -> CapturedFrame {
+        let row_len = rect.width as usize * 4;
+        let mut data = Vec::with_capacity(row_len * rect.height as usize);
+        for y in rect.y..rect.y + rect.height {
+            let start = y as usize * self.stride + rect.x as usize * 4;
+            data.extend_from_slice(&self.data[start..start + row_len]);
+        }
+        let sx = self.screen_rect.width / self.width as f64;
+        let sy = self.screen_rect.height / self.height as f64;
+        CapturedFrame {
+            timestamp: self.timestamp,
+            sequence: self.sequence,
+            width: rect.width,
+            height: rect.height,
+            display_id: self.display_id,
+            screen_rect: ScreenRect {
+                x: self.screen_rect.x + rect.x as f64 * sx,
+                y: self.screen_rect.y + rect.y as f64 * sy,
+                width: rect.width as f64 * sx,
+                height: rect.height as f64 * sy,
+            },
+            stride: row_len,
+            format: self.format,
+            data,
+        }
+    }
+
+    /// Tightly packed, opaque RGBA copy of the frame.
+    pub fn to_rgba(&self) -> RgbaImage {
+        let row_len = self.width as usize * 4;
+        let mut out = Vec::with_capacity(row_len * self.height as usize);
+        for row in self.data.chunks(self.stride).take(self.height as usize) {
+            let row = &row[..row_len.min(row.len())];
+            match self.format {
+                PixelFormat::Rgba => out.extend_from_slice(row),
+                PixelFormat::Bgra => {
+                    for px in row.chunks_exact(4) {
+                        out.extend_from_slice(&[px[2], px[1], px[0], 255]);
+                    }
+                }
+            }
+        }
+        out.resize(row_len * self.height as usize, 0);
+        RgbaImage::from_raw(self.width, self.height, out).expect("buffer sized to fit")
+    }
+}
+
+/// Something that produces screen frames: the ScreenCaptureKit bridge on macOS,
+/// or a replay / synthetic source for tests and recorded sessions.
+pub trait FrameSource: Send + Sync {
+    fn start(&self) -> Result<(), CaptureError>;
+    fn stop(&self);
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError>;
+
+    /// Whether the OS lets us capture the screen. Only macOS asks.
+    fn permission(&self) -> Permission {
+        Permission::Granted
+    }
+
+    /// Asks the OS for permission, prompting the user if it can.
+    fn request_permission(&self) -> Permission {
+        self.permission()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    Granted,
+    Denied,
+}
+
+/// Picks a source from `INTERO_FRAME_SOURCE`:
+///   - unset / `native`: the platform capture backend
+///   - `pattern`: synthetic moving bars
+///   - `x11` / `x11:<monitor>` (Linux): the root window, or one RandR monitor
+///   - `replay:<path>`: a single image or a directory of images, played back in name order
+///   - `fake` / `fake:denied`: synthetic frames with scriptable permission and failures
+pub fn from_env() -> Box<dyn FrameSource> {
+    let spec = std::env::var("INTERO_FRAME_SOURCE").unwrap_or_default();
+    from_spec(&spec).unwrap_or_else(|err| {
+        println!("[error]: {}, using the native capture backend", err);
+        native()
+    })
+}
+
+/// Parses an `INTERO_FRAME_SOURCE` value, see `from_env`.
+pub fn from_spec(spec: &str) -> Result<Box<dyn FrameSource>, String> {
+    Ok(match spec.split_once(':') {
+        Some(("replay", "")) => return Err("replay needs a path, as in replay:<path>".to_string()),
+        Some(("replay", path)) => Box::new(ReplaySource::new(path, DEFAULT_INTERVAL)),
+        #[cfg(target_os = "linux")]
+        Some(("x11", monitor)) => {
+            let monitor = monitor.parse().map_err(|_| format!("Bad X11 monitor index {:?}", monitor))?;
+            Box::new(crate::x11_source::X11Source::new(Some(monitor)))
+        }
+        #[cfg(target_os = "linux")]
+        _ if spec == "x11" => Box::new(crate::x11_source::X11Source::new(None)),
+        _ if spec == "pattern" => Box::new(PatternSource::new(640, 400, DEFAULT_INTERVAL)),
+        _ if spec == "fake" => Box::new(FakeSource::new(Permission::Granted)),
+        _ if spec == "fake:denied" => Box::new(FakeSource::new(Permission::Denied)),
+        _ if spec.is_empty() || spec == "native" => native(),
+        _ => return Err(format!("Unknown frame source {:?}", spec)),
+    })
+}
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+#[cfg(target_os = "macos")]
+fn native() -> Box<dyn FrameSource> {
+    Box::new(ScreenCaptureKitSource)
+}
+
+#[cfg(target_os = "linux")]
+fn native() -> Box<dyn FrameSource> {
+    Box::new(crate::x11_source::X11Source::new(None))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn native() -> Box<dyn FrameSource> {
+    println!("[error]: no native capture backend on this platform, using synthetic frames");
+    Box::new(PatternSource::new(640, 400, DEFAULT_INTERVAL))
+}
+
+// ScreenCaptureKit
+
+#[cfg(target_os = "macos")]
+pub struct ScreenCaptureKitSource;
+
+#[cfg(target_os = "macos")]
+impl FrameSource for ScreenCaptureKitSource {
+    fn start(&self) -> Result<(), CaptureError> {
+        unsafe { crate::ffi::start() };
+        Ok(())
+    }
+
+    fn stop(&self) {
+        unsafe { crate::ffi::stop() };
+    }
+
+    fn permission(&self) -> Permission {
+        if unsafe { crate::ffi::CGPreflightScreenCaptureAccess() } {
+            Permission::Granted
+        } else {
+            Permission::Denied
+        }
+    }
+
+    fn request_permission(&self) -> Permission {
+        if unsafe { crate::ffi::CGRequestScreenCaptureAccess() } {
+            Permission::Granted
+        } else {
+            Permission::Denied
+        }
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        match unsafe { crate::ffi::get_status() } {
+            crate::ffi::STATUS_IDLE => return Err(CaptureError::NotStarted),
+            crate::ffi::STATUS_PERMISSION_DENIED => return Err(CaptureError::PermissionDenied),
+            crate::ffi::STATUS_FAILED => return Err(CaptureError::failed("ScreenCaptureKit stream stopped")),
+            _ => {}
+        }
+        let frame = unsafe { crate::ffi::get_last_frame() }.ok_or(CaptureError::NoFrameYet)?;
+        Ok(CapturedFrame {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs_f64(frame.timestamp),
+            sequence: frame.sequence as u64,
+            width: frame.width as u32,
+            height: frame.height as u32,
+            display_id: frame.display_id as u32,
+            screen_rect: ScreenRect {
+                x: frame.screen_x,
+                y: frame.screen_y,
+                width: frame.screen_width,
+                height: frame.screen_height,
+            },
+            stride: frame.bytes_per_row as usize,
+            format: PixelFormat::Bgra,
+            data: frame.data.as_slice().to_vec(),
+        })
+    }
+}
+
+// Replay
+
+/// Plays back image files, advancing one file per `interval` and looping at the end.
+pub struct ReplaySource {
+    files: Vec<PathBuf>,
+    interval: Duration,
+    started_at: Mutex<Option<Instant>>,
+}
+
+impl ReplaySource {
+    pub fn new(path: impl AsRef<Path>, interval: Duration) -> Self {
+        let path = path.as_ref();
+        let files = if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(path)
+                .map(|entries| {
+                    entries
+                        .filter_map(|entry| entry.ok().map(|e| e.path()))
+                        .filter(|p| image::ImageFormat::from_path(p).is_ok())
+                        .collect()
+                })
+                .unwrap_or_default();
+            files.sort();
+            files
+        } else {
+            vec![path.to_path_buf()]
+        };
+        ReplaySource {
+            files,
+            interval,
+            started_at: Mutex::new(None),
+        }
+    }
+
+    fn load(path: &Path) -> Option<RgbaImage> {
+        Some(image::open(path).ok()?.to_rgba8())
+    }
+}
+
+impl FrameSource for ReplaySource {
+    fn start(&self) -> Result<(), CaptureError> {
+        if self.files.is_empty() {
+            return Err(CaptureError::failed("No images to replay"));
+        }
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+
+    fn stop(&self) {
+        *self.started_at.lock().unwrap() = None;
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        let started_at = (*self.started_at.lock().unwrap()).ok_or(CaptureError::NotStarted)?;
+        let step = (started_at.elapsed().as_millis() / self.interval.as_millis().max(1)) as usize;
+        let path = &self.files[step % self.files.len()];
+        match Self::load(path) {
+            Some(image) => Ok(CapturedFrame::from_rgba(image, SystemTime::now(), step as u64, 0)),
+            None => Err(CaptureError::failed(format!("Could not load replay frame {:?}", path))),
+        }
+    }
+}
+
+// Synthetic pattern
+
+/// Generates vertical colour bars that shift one bar every `interval`.
+pub struct PatternSource {
+    width: u32,
+    height: u32,
+    interval: Duration,
+    started_at: Mutex<Option<Instant>>,
+}
+
+const PATTERN_COLORS: [[u8; 4]; 8] = [
+    [255, 255, 255, 255],
+    [255, 255, 0, 255],
+    [0, 255, 255, 255],
+    [0, 255, 0, 255],
+    [255, 0, 255, 255],
+    [255, 0, 0, 255],
+    [0, 0, 255, 255],
+    [0, 0, 0, 255],
+];
+
+impl PatternSource {
+    pub fn new(width: u32, height: u32, interval: Duration) -> Self {
+        PatternSource {
+            width,
+            height,
+            interval,
+            started_at: Mutex::new(None),
+        }
+    }
+
+    /// Renders the pattern for a given step. Exposed so tests can compare against it.
+    pub fn render(&self, step: usize) -> RgbaImage {
+        let bar_width = (self.width / PATTERN_COLORS.len() as u32).max(1);
+        RgbaImage::from_fn(self.width, self.height, |x, _| {
+            let bar = (x / bar_width) as usize + step;
+            Rgba(PATTERN_COLORS[bar % PATTERN_COLORS.len()])
+        })
+    }
+}
+
+impl FrameSource for PatternSource {
+    fn start(&self) -> Result<(), CaptureError> {
+        *self.started_at.lock().unwrap() = Some(Instant::now());
+        Ok(())
+    }
+
+    fn stop(&self) {
+        *self.started_at.lock().unwrap() = None;
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        let started_at = (*self.started_at.lock().unwrap()).ok_or(CaptureError::NotStarted)?;
+        let step = (started_at.elapsed().as_millis() / self.interval.as_millis().max(1)) as usize;
+        Ok(CapturedFrame::from_rgba(self.render(step), SystemTime::now(), step as u64, 0))
+    }
+}
+
+// Fake
+
+/// Pattern frames plus knobs for permission and failures, for exercising the
+/// capture status flow without a real backend.
+pub struct FakeSource {
+    pattern: PatternSource,
+    permission: Mutex<Permission>,
+    /// Permission `request_permission` switches to, if any.
+    grant_on_request: Mutex<Option<Permission>>,
+    start_error: Mutex<Option<CaptureError>>,
+    stream_error: Mutex<Option<CaptureError>>,
+}
+
+impl FakeSource {
+    pub fn new(permission: Permission) -> Self {
+        FakeSource {
+            pattern: PatternSource::new(320, 200, DEFAULT_INTERVAL),
+            permission: Mutex::new(permission),
+            grant_on_request: Mutex::new(None),
+            start_error: Mutex::new(None),
+            stream_error: Mutex::new(None),
+        }
+    }
+
+    pub fn set_permission(&self, permission: Permission) {
+        *self.permission.lock().unwrap() = permission;
+    }
+
+    pub fn answer_request_with(&self, permission: Permission) {
+        *self.grant_on_request.lock().unwrap() = Some(permission);
+    }
+
+    pub fn fail_start(&self, error: Option<CaptureError>) {
+        *self.start_error.lock().unwrap() = error;
+    }
+
+    /// Makes `latest_frame` fail until cleared, as if the stream died.
+    pub fn fail_stream(&self, error: Option<CaptureError>) {
+        *self.stream_error.lock().unwrap() = error;
+    }
+}
+
+impl FrameSource for FakeSource {
+    fn start(&self) -> Result<(), CaptureError> {
+        if self.permission() == Permission::Denied {
+            return Err(CaptureError::PermissionDenied);
+        }
+        if let Some(err) = self.start_error.lock().unwrap().clone() {
+            return Err(err);
+        }
+        self.pattern.start()
+    }
+
+    fn stop(&self) {
+        self.pattern.stop();
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        if let Some(err) = self.stream_error.lock().unwrap().clone() {
+            return Err(err);
+        }
+        self.pattern.latest_frame()
+    }
+
+    fn permission(&self) -> Permission {
+        *self.permission.lock().unwrap()
+    }
+
+    fn request_permission(&self) -> Permission {
+        if let Some(answer) = self.grant_on_request.lock().unwrap().take() {
+            self.set_permission(answer);
+        }
+        self.permission()
+    }
+}
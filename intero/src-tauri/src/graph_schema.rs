@@ -0,0 +1,139 @@
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::{Graph, Relation};
+
+/// Version 1 is the frontend's `ToposorterStateData`, as saved in `localStorage` and
+/// by the `backup` command: edges live in each node's `children` and there is no
+/// `version` field. Version 2 keeps edges only in `relations.depends`.
+pub const GRAPH_SCHEMA_VERSION: u64 = 2;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SchemaError {
+    /// Written by a newer version of the app.
+    FutureVersion { version: u64, supported: u64 },
+    Invalid { message: String },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::FutureVersion { version, supported } => write!(
+                f,
+                "the graph has schema version {}, but this app only reads up to {}",
+                version, supported
+            ),
+            SchemaError::Invalid { message } => write!(f, "not a graph: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+fn invalid(message: impl ToString) -> SchemaError {
+    SchemaError::Invalid {
+        message: message.to_string(),
+    }
+}
+
+/// An edge that points at a node that does not exist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DanglingEdge {
+    pub kind: String,
+    pub parent: String,
+    pub child: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MigrationReport {
+    pub from_version: u64,
+    /// `children` entries that became `depends` relations.
+    pub converted: usize,
+    /// Dropped, since relations must point at existing nodes.
+    pub dangling: Vec<DanglingEdge>,
+}
+
+/// Reads a graph of any version up to `GRAPH_SCHEMA_VERSION`, such as a backup file,
+/// and brings it up to date.
+pub fn load(json: &str) -> Result<(Graph, MigrationReport), SchemaError> {
+    let value: Value = serde_json::from_str(json).map_err(invalid)?;
+    let version = match value.get("version") {
+        None => 1,
+        Some(version) => version.as_u64().ok_or_else(|| invalid("the version is not a number"))?,
+    };
+    if version > GRAPH_SCHEMA_VERSION {
+        return Err(SchemaError::FutureVersion {
+            version,
+            supported: GRAPH_SCHEMA_VERSION,
+        });
+    }
+    let mut graph: Graph = serde_json::from_value(value).map_err(invalid)?;
+    let report = migrate(&mut graph);
+    Ok((
+        graph,
+        MigrationReport {
+            from_version: version,
+            ..report
+        },
+    ))
+}
+
+/// `graph` with a `version` field, for writing to a file.
+pub fn to_json(graph: &Graph) -> Value {
+    let mut value = serde_json::to_value(graph).expect("graphs serialize");
+    value["version"] = GRAPH_SCHEMA_VERSION.into();
+    value
+}
+
+/// Moves every `children` list into `depends`, drops edges to missing nodes and
+/// the `__` fields the frontend derives. Does nothing to a graph that is already current.
+pub fn migrate(graph: &mut Graph) -> MigrationReport {
+    let mut converted = 0;
+    let mut depends = graph.relations.remove("depends").unwrap_or_default();
+    for (parent, data) in graph.nodes.iter_mut() {
+        let Some(data) = data.as_object_mut() else {
+            continue;
+        };
+        data.retain(|key, _| !key.starts_with("__"));
+        let Some(children) = data.remove("children") else {
+            continue;
+        };
+        for child in children.as_array().into_iter().flatten().filter_map(Value::as_str) {
+            let relation = Relation {
+                parent: parent.clone(),
+                child: child.to_string(),
+            };
+            if !depends.contains(&relation) {
+                depends.push(relation);
+                converted += 1;
+            }
+        }
+    }
+    if !depends.is_empty() {
+        graph.relations.insert("depends".to_string(), depends);
+    }
+
+    let mut dangling = vec![];
+    let nodes = &graph.nodes;
+    for (kind, relations) in graph.relations.iter_mut() {
+        relations.retain(|relation| {
+            let known = nodes.contains_key(&relation.parent) && nodes.contains_key(&relation.child);
+            if !known {
+                dangling.push(DanglingEdge {
+                    kind: kind.clone(),
+                    parent: relation.parent.clone(),
+                    child: relation.child.clone(),
+                });
+            }
+            known
+        });
+    }
+    MigrationReport {
+        from_version: GRAPH_SCHEMA_VERSION,
+        converted,
+        dangling,
+    }
+}
@@ -1,3 +1,33 @@
+#[cfg(target_os = "macos")]
 pub mod panel_ext;
+#[cfg(target_os = "macos")]
 pub mod widget;
-pub mod main_window;
\ No newline at end of file
+#[cfg(target_os = "macos")]
+pub mod main_window;
+#[cfg(target_os = "macos")]
+pub mod ffi;
+pub mod capture_status;
+pub mod change_detector;
+pub mod classifier;
+pub mod db;
+pub mod encoding;
+pub mod focus;
+pub mod foreground;
+pub mod frame_history;
+pub mod frame_source;
+pub mod graph_schema;
+pub mod prompt;
+pub mod redaction;
+pub mod reports;
+pub mod rules;
+pub mod screenshot;
+pub mod secrets;
+pub mod speech;
+pub mod toposort;
+pub mod undo;
+pub mod url;
+pub mod verdict;
+pub mod verdict_cache;
+pub mod watch_loop;
+#[cfg(target_os = "linux")]
+pub mod x11_source;
@@ -1,34 +1,92 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+#[cfg(target_os = "macos")]
 mod main_window;
+#[cfg(target_os = "macos")]
 mod window_ext;
+#[cfg(target_os = "macos")]
 mod panel_ext;
 mod widget;
-mod screenshot;
-mod ffi;
 
 
-use clippy_app::main_window::position_window_fullscreen;
-use tauri::{AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, Wry};
+use clippy_app::capture_status::{CaptureController, CaptureStatus};
+use clippy_app::change_detector::ChangeDetectorConfig;
+use clippy_app::classifier::{self, Classifier, ClassifierConfig, ClassifierError, Image, OpenAiClassifier};
+use clippy_app::db::{ActivityKind, ActivityRow, Db, Graph, PromptTemplateRow, Relation, VerdictRow};
+use clippy_app::focus::{FocusChange, FocusConfig, FocusState, FocusTracker};
+use clippy_app::foreground::{self, ForegroundInfo, ForegroundWatcher};
+use clippy_app::frame_history::{FrameHistory, FrameHistoryConfig, FrameRecord, StoredFrame};
+use clippy_app::frame_source::{self, CaptureError};
+use clippy_app::graph_schema::{self, MigrationReport};
+use clippy_app::prompt::{self, Prompt, TaskContext};
+use clippy_app::redaction::{self, RedactionConfig, Redactor};
+use clippy_app::reports::{self, ExportFormat, Report};
+use clippy_app::secrets::{SecretName, Secrets, SecretsStatus};
+use clippy_app::toposort::{self, Connection, Cycle, GraphError, SortedGraph};
+use clippy_app::speech::{self, Speech, SpeechConfig, SpeechError};
+use clippy_app::rules::{RuleContext, RulesStatus, RulesWatcher};
+use clippy_app::screenshot::{self, Frame, ScreenshotOptions};
+use clippy_app::undo::{self, Op, UndoLog, UndoStatus};
+use clippy_app::verdict::{self, Verdict};
+use clippy_app::verdict_cache::{self, CacheConfig, CacheStats, PerceptualHash, VerdictCache};
+use clippy_app::watch_loop::{self, WatchConfig};
+use tauri::{AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem, Window, Wry};
 use tauri_plugin_autostart::MacosLauncher;
+#[cfg(target_os = "macos")]
+use clippy_app::main_window::position_window_fullscreen;
+#[cfg(target_os = "macos")]
 use tauri_nspanel::ManagerExt;
+#[cfg(target_os = "macos")]
 use block::ConcreteBlock;
+#[cfg(target_os = "macos")]
 use cocoa::appkit::NSEventMask;
+#[cfg(target_os = "macos")]
 use objc::{class, msg_send, sel, sel_impl};
+#[cfg(target_os = "macos")]
 use cocoa::base::{id, nil};
+#[cfg(target_os = "macos")]
 use cocoa::foundation::{NSPoint, NSRect};
+#[cfg(target_os = "macos")]
 use window_vibrancy::NSVisualEffectMaterial;
 
-use std::{process, thread};
+use std::io;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
 
 
 fn make_tray() -> SystemTray {
     // <- a function that creates the system tray
-    let menu = SystemTrayMenu::new()
+    return SystemTray::new().with_menu(tray_menu(CaptureStatus::Unknown));
+}
+
+fn tray_menu(status: CaptureStatus) -> SystemTrayMenu {
+    let mut menu = SystemTrayMenu::new();
+    match status {
+        CaptureStatus::Denied => {
+            menu = menu.add_item(CustomMenuItem::new("grant_screen_recording".to_string(), "Grant screen recording…"));
+        }
+        CaptureStatus::Starting | CaptureStatus::Running => {
+            menu = menu
+                .add_item(CustomMenuItem::new("pause_capture".to_string(), "Pause capture"))
+                .add_item(CustomMenuItem::new("stop_capture".to_string(), "Stop capture"));
+        }
+        CaptureStatus::Paused => {
+            menu = menu
+                .add_item(CustomMenuItem::new("start_capture".to_string(), "Resume capture"))
+                .add_item(CustomMenuItem::new("stop_capture".to_string(), "Stop capture"));
+        }
+        CaptureStatus::Requesting => {}
+        _ => {
+            menu = menu.add_item(CustomMenuItem::new("start_capture".to_string(), "Start capture"));
+        }
+    }
+    menu.add_native_item(SystemTrayMenuItem::Separator)
         .add_item(CustomMenuItem::new("open".to_string(), "Open Intero"))
-        .add_item(CustomMenuItem::new("quit".to_string(), "Quit"));
-    return SystemTray::new().with_menu(menu);
+        .add_item(CustomMenuItem::new("quit".to_string(), "Quit"))
 }
 
 fn handle_tray_event(app: &AppHandle, event: SystemTrayEvent) {
@@ -39,16 +97,92 @@ fn handle_tray_event(app: &AppHandle, event: SystemTrayEvent) {
         if id.as_str() == "open" {
             open_panel(app)
         }
+        if id.as_str() == "grant_screen_recording" {
+            grant_screen_recording(app)
+        }
+        if id.as_str() == "start_capture" {
+            let capture = app.state::<CaptureController>();
+            let resuming = capture.status() == CaptureStatus::Paused;
+            if let Err(err) = capture.start() {
+                println!("[error]: could not start capture: {}", err);
+            }
+            if !resuming {
+                set_watch(app, true);
+            }
+        }
+        if id.as_str() == "pause_capture" {
+            app.state::<CaptureController>().pause(None);
+        }
+        if id.as_str() == "stop_capture" {
+            app.state::<CaptureController>().stop();
+            set_watch(app, false);
+        }
     }
 }
 
+/// Keeps the frontend's `watch` preference in line with capture started or stopped from the tray.
+fn set_watch(app: &AppHandle, watch: bool) {
+    app.emit_all("set-watch", watch).ok();
+}
+
+fn grant_screen_recording(app: &AppHandle) {
+    // macOS only prompts once; after that the user has to flip the switch themselves.
+    if app.state::<CaptureController>().request_permission() == CaptureStatus::Denied {
+        open_screen_recording_settings();
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn open_screen_recording_settings() {
+    let url = "x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture";
+    if let Err(err) = process::Command::new("open").arg(url).spawn() {
+        println!("[error]: could not open System Settings: {}", err);
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn open_screen_recording_settings() {}
+
+struct ChangeDetection(Mutex<ChangeDetectorConfig>);
+
+/// `None` if the history directory could not be opened.
+struct History(Mutex<Option<FrameHistory>>);
+
+struct Database(Mutex<Db>);
+
+struct ActiveClassifier(Mutex<(ClassifierConfig, Arc<dyn Classifier>)>);
+
+struct Cache(Mutex<VerdictCache>);
+
+/// `None` if there is no app data directory to keep rules in.
+struct RuleBook(Mutex<Option<RulesWatcher>>);
+
+struct Scheduling(Mutex<WatchConfig>);
+
+struct Focus(Mutex<FocusTracker>);
+
+struct Undo(Mutex<UndoLog>);
+
+/// Payload of the `db-changed` event, so every window can reload what it shows.
+#[derive(Clone, serde::Serialize)]
+struct DbChanged {
+    table: &'static str,
+}
+
 fn main() {
-    thread::spawn(|| {
-        let _output = unsafe { ffi::start() };
-    });
+    let builder = tauri::Builder::default();
+    #[cfg(target_os = "macos")]
+    let builder = builder.plugin(tauri_nspanel::init());
 
-    tauri::Builder::default()
-        .plugin(tauri_nspanel::init())
+    builder
+        .manage(CaptureController::new(frame_source::from_env()))
+        .manage(ForegroundWatcher::new(foreground::from_env()))
+        .manage(ChangeDetection(Mutex::new(ChangeDetectorConfig::default())))
+        .manage(Cache(Mutex::new(VerdictCache::new(CacheConfig::default()))))
+        .manage(Scheduling(Mutex::new(WatchConfig::default())))
+        .manage(Focus(Mutex::new(FocusTracker::new(FocusConfig::default()))))
+        // Entries beyond this are forgotten.
+        .manage(Undo(Mutex::new(UndoLog::new(200))))
         .plugin(tauri_plugin_store::Builder::default().build())
         .plugin(tauri_plugin_autostart::init(
             MacosLauncher::LaunchAgent,
@@ -62,18 +196,87 @@ fn main() {
             close_panel,
             toggle_panel,
             screenshot,
+            capture_status,
+            start_capture,
+            stop_capture,
+            pause_capture,
+            get_change_detection,
+            set_change_detection,
+            list_frames,
+            get_frame,
+            frame_at,
+            db_graph,
+            db_put_node,
+            db_delete_node,
+            db_link,
+            db_unlink,
+            db_replace_graph,
+            db_log_activity,
+            db_activity,
+            db_verdicts,
+            db_prompt_templates,
+            db_import,
+            db_backup,
+            db_restore,
+            graph_add,
+            graph_link,
+            graph_unlink,
+            graph_cycles,
+            graph_set_status,
+            graph_sorted,
+            history_undo,
+            history_redo,
+            history_status,
+            history_begin_group,
+            history_end_group,
+            report_time,
+            report_export,
+            get_classifier,
+            set_classifier,
+            classify_frame,
+            classifier_diagnostics,
+            secrets_status,
+            set_secret,
+            clear_secret,
+            test_secret,
+            speak,
+            current_foreground,
+            rules_status,
+            get_redaction_config,
+            set_redaction_config,
+            get_watch_config,
+            set_watch_config,
+            focus_state,
+            get_focus_config,
+            set_focus_config,
             widget::show_widget_window,
         ])
         .setup(move |app| {
-            app.set_activation_policy(tauri::ActivationPolicy::Accessory);
-            let window = app.get_window("main").unwrap();
             #[cfg(target_os = "macos")]
-            window_vibrancy::apply_vibrancy(&window, NSVisualEffectMaterial::Popover, None, None)
-                .expect("Unsupported platform! 'apply_vibrancy' is only supported on macOS");
-            // window.set_transparent_titlebar(true, true);
-            panel_ext::init_as_panel(window);
+            {
+                app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+                let window = app.get_window("main").unwrap();
+                window_vibrancy::apply_vibrancy(&window, NSVisualEffectMaterial::Popover, None, None)
+                    .expect("Unsupported platform! 'apply_vibrancy' is only supported on macOS");
+                // window.set_transparent_titlebar(true, true);
+                panel_ext::init_as_panel(window);
+            }
             widget::show_widget_window(app.app_handle());
+            #[cfg(target_os = "macos")]
             track_mouse(&app.app_handle());
+            watch_capture_status(app.app_handle());
+            app.manage(Database(Mutex::new(open_database(&app.app_handle())?)));
+            app.manage(History(Mutex::new(open_frame_history(&app.app_handle()))));
+            app.manage(RuleBook(Mutex::new(open_rules(&app.app_handle()))));
+            app.manage(open_redactor(&app.app_handle()));
+            app.manage(open_secrets(&app.app_handle())?);
+            let (secrets, redactor) = (app.state::<Secrets>(), app.state::<Redactor>());
+            let classifier = with_classifier(classifier::config_from_env(), &secrets, &redactor);
+            app.manage(ActiveClassifier(Mutex::new(classifier)));
+            watch_rules(app.app_handle());
+            watch_foreground(app.app_handle());
+            watch_frame_changes(app.app_handle());
+            track_focus(app.app_handle());
 
             Ok(())
         })
@@ -83,8 +286,870 @@ fn main() {
 }
 
 #[tauri::command]
-async fn screenshot(app: AppHandle<Wry>) -> Vec<String> {
-    return screenshot::capture().await;
+async fn screenshot(
+    capture: tauri::State<'_, CaptureController>,
+    redactor: tauri::State<'_, Redactor>,
+    options: Option<ScreenshotOptions>,
+) -> Result<Frame, CaptureError> {
+    screenshot::capture(&redactor.wrap(capture.inner()), &options.unwrap_or_default()).await
+}
+
+#[tauri::command]
+fn capture_status(capture: tauri::State<CaptureController>) -> CaptureStatus {
+    capture.status()
+}
+
+#[tauri::command]
+fn start_capture(capture: tauri::State<CaptureController>) -> Result<(), CaptureError> {
+    capture.start()
+}
+
+#[tauri::command]
+fn stop_capture(capture: tauri::State<CaptureController>) {
+    capture.stop();
+}
+
+/// Pauses until `start_capture`, or for `minutes` if given.
+#[tauri::command]
+fn pause_capture(capture: tauri::State<CaptureController>, minutes: Option<u64>) {
+    capture.pause(minutes.map(|m| Duration::from_secs(m * 60)));
+}
+
+/// Keeps the tray and frontend in sync with the capture status. Capture itself is
+/// started by the frontend once it knows the `watch` preference.
+fn watch_capture_status(app: AppHandle) {
+    let capture = app.state::<CaptureController>();
+    let handle = app.clone();
+    capture.set_listener(Box::new(move |change| {
+        println!("[info]: capture status {:?} -> {:?}", change.previous, change.status);
+        if let Err(err) = handle.tray_handle().set_menu(tray_menu(change.status)) {
+            println!("[error]: could not update tray menu: {}", err);
+        }
+        handle.emit_all("capture-status-changed", change).ok();
+    }));
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        app.state::<CaptureController>().refresh();
+    });
+}
+
+#[tauri::command]
+fn get_change_detection(detection: tauri::State<ChangeDetection>) -> ChangeDetectorConfig {
+    *detection.0.lock().unwrap()
+}
+
+#[tauri::command]
+fn set_change_detection(detection: tauri::State<ChangeDetection>, config: ChangeDetectorConfig) {
+    *detection.0.lock().unwrap() = config;
+}
+
+fn open_frame_history(app: &AppHandle) -> Option<FrameHistory> {
+    let dir = app.path_resolver().app_data_dir()?.join("frames");
+    let next_id = app.state::<Database>().0.lock().unwrap().next_frame_id();
+    let opened = FrameHistory::open(&dir, FrameHistoryConfig::default()).and_then(|mut history| {
+        // The database may remember frames the history has pruned.
+        history.skip_ids_below(next_id.map_err(io::Error::other)? as u64)?;
+        Ok(history)
+    });
+    match opened {
+        Ok(history) => Some(history),
+        Err(err) => {
+            println!("[error]: could not open frame history in {}: {}", dir.display(), err);
+            None
+        }
+    }
+}
+
+#[tauri::command]
+fn list_frames(
+    history: tauri::State<History>,
+    from: Option<u64>,
+    to: Option<u64>,
+    limit: Option<usize>,
+) -> Result<Vec<FrameRecord>, String> {
+    let history = history.0.lock().unwrap();
+    let history = history.as_ref().ok_or("Frame history is unavailable")?;
+    Ok(history.list(from, to, limit).to_vec())
+}
+
+#[tauri::command]
+fn get_frame(history: tauri::State<History>, id: u64) -> Result<Option<StoredFrame>, String> {
+    let history = history.0.lock().unwrap();
+    let history = history.as_ref().ok_or("Frame history is unavailable")?;
+    history.get(id).map_err(|e| e.to_string())
+}
+
+/// The last stored frame taken at or before `timestamp` (ms since the Unix epoch).
+#[tauri::command]
+fn frame_at(history: tauri::State<History>, timestamp: u64) -> Result<Option<StoredFrame>, String> {
+    let history = history.0.lock().unwrap();
+    let history = history.as_ref().ok_or("Frame history is unavailable")?;
+    history.at(timestamp).map_err(|e| e.to_string())
+}
+
+/// Fails rather than running on an in-memory database, where nothing would be saved,
+/// e.g. when the file was written by a newer version of the app.
+fn open_database(app: &AppHandle) -> Result<Db, String> {
+    let dir = app.path_resolver().app_data_dir().ok_or("could not open the database: no app data directory")?;
+    let database = std::fs::create_dir_all(&dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| Db::open(dir.join("intero.db")).map_err(|e| e.to_string()))
+        .map_err(|err| format!("could not open the database: {}", err))?;
+    if let Err(err) = database.save_prompt_templates(prompt::TEMPLATES) {
+        println!("[error]: could not save prompt templates: {}", err);
+    }
+    Ok(database)
+}
+
+fn db_changed(app: &AppHandle, table: &'static str) {
+    app.emit_all("db-changed", DbChanged { table }).ok();
+}
+
+#[tauri::command]
+fn db_graph(database: tauri::State<Database>) -> Result<Graph, String> {
+    database.0.lock().unwrap().graph().map_err(|e| e.to_string())
+}
+
+/// `label` names the change in the undo history, e.g. "rename".
+#[tauri::command]
+fn db_put_node(
+    window: Window,
+    database: tauri::State<Database>,
+    id: String,
+    data: serde_json::Value,
+    label: String,
+) -> Result<(), String> {
+    update_graph(&window, &database, &label, |graph| {
+        graph.nodes.insert(id, data);
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn db_delete_node(window: Window, database: tauri::State<Database>, id: String) -> Result<bool, String> {
+    update_graph(&window, &database, "delete node", |graph| Ok(graph.nodes.remove(&id).is_some()))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn db_link(
+    window: Window,
+    database: tauri::State<Database>,
+    kind: String,
+    parent: String,
+    child: String,
+) -> Result<(), String> {
+    update_graph(&window, &database, "link", |graph| {
+        if kind == "depends" {
+            return toposort::link(graph, &parent, &child).map(drop);
+        }
+        for id in [&parent, &child] {
+            if !graph.nodes.contains_key(id) {
+                return Err(GraphError::UnknownNode { id: id.clone() });
+            }
+        }
+        let relation = Relation { parent, child };
+        let relations = graph.relations.entry(kind).or_default();
+        if !relations.contains(&relation) {
+            relations.push(relation);
+        }
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn db_unlink(
+    window: Window,
+    database: tauri::State<Database>,
+    kind: String,
+    parent: String,
+    child: String,
+) -> Result<bool, String> {
+    update_graph(&window, &database, "unlink", |graph| {
+        let Some(relations) = graph.relations.get_mut(&kind) else {
+            return Ok(false);
+        };
+        let before = relations.len();
+        relations.retain(|r| !(r.parent == parent && r.child == child));
+        Ok(relations.len() != before)
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// `label` names the change in the undo history. Fails if the new graph has a cycle
+/// through an edge the stored graph does not have.
+#[tauri::command]
+fn db_replace_graph(
+    window: Window,
+    database: tauri::State<Database>,
+    graph: Graph,
+    label: String,
+) -> Result<(), String> {
+    update_graph(&window, &database, &label, |stored| {
+        *stored = graph;
+        Ok(())
+    })
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn db_log_activity(
+    window: Window,
+    database: tauri::State<Database>,
+    focus: tauri::State<Focus>,
+    row: ActivityRow,
+) -> Result<Option<i64>, String> {
+    let db = database.0.lock().unwrap();
+    let id = db.log_activity(&row).map_err(|e| e.to_string())?;
+    if id.is_some() {
+        let op = Op::InsertActivity {
+            row: ActivityRow { id, ..row.clone() },
+        };
+        record_undo(&window, &db, "log activity", vec![op]);
+        db_changed(&window.app_handle(), "activity");
+        if row.kind == ActivityKind::Start {
+            focus.0.lock().unwrap().context_switch(now());
+        }
+    }
+    Ok(id)
+}
+
+#[tauri::command]
+fn db_activity(
+    database: tauri::State<Database>,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<Vec<ActivityRow>, String> {
+    database.0.lock().unwrap().activity(from, to).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn db_verdicts(
+    database: tauri::State<Database>,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<Vec<VerdictRow>, String> {
+    database.0.lock().unwrap().verdicts(from, to).map_err(|e| e.to_string())
+}
+
+/// Every prompt template verdicts were made with, for auditing them.
+#[tauri::command]
+fn db_prompt_templates(database: tauri::State<Database>) -> Result<Vec<PromptTemplateRow>, String> {
+    database.0.lock().unwrap().prompt_templates().map_err(|e| e.to_string())
+}
+
+/// Imports what the webview kept in `localStorage`, unless the database already has data.
+#[tauri::command]
+fn db_import(
+    app: AppHandle,
+    database: tauri::State<Database>,
+    graph: Graph,
+    activity: Vec<ActivityRow>,
+) -> Result<bool, String> {
+    let imported = database.0.lock().unwrap().import(&graph, &activity).map_err(|e| e.to_string())?;
+    if imported {
+        db_changed(&app, "nodes");
+        db_changed(&app, "activity");
+    }
+    Ok(imported)
+}
+
+/// Writes the graph, with its schema version, to a file in the app data directory.
+/// Returns the path.
+#[tauri::command]
+fn db_backup(app: AppHandle, database: tauri::State<Database>) -> Result<String, String> {
+    let dir = app.path_resolver().app_data_dir().ok_or("no app data directory")?;
+    let graph = database.0.lock().unwrap().graph().map_err(|e| e.to_string())?;
+    let time = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let path = dir.join(format!("toposorter-backup-{}.json", time));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    std::fs::write(&path, graph_schema::to_json(&graph).to_string()).map_err(|e| e.to_string())?;
+    Ok(path.display().to_string())
+}
+
+/// Replaces the graph with a backup file, either from `db_backup` or the frontend's
+/// `backup` command. Refuses files from a newer schema.
+#[tauri::command]
+fn db_restore(window: Window, database: tauri::State<Database>, path: String) -> Result<MigrationReport, String> {
+    let json = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let (graph, report) = graph_schema::load(&json).map_err(|e| e.to_string())?;
+    update_graph(&window, &database, "restore backup", |stored| {
+        *stored = graph;
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+/// Applies `change` to the stored graph, saves what it changed and records it as
+/// one undo entry named `label`. Refuses changes that add a cycle, whichever command
+/// made them, see `undo::change_graph`.
+fn update_graph<T>(
+    window: &Window,
+    database: &Database,
+    label: &str,
+    change: impl FnOnce(&mut Graph) -> Result<T, GraphError>,
+) -> Result<T, GraphError> {
+    let db = database.0.lock().unwrap();
+    let (result, ops) = undo::change_graph(&db, change)?;
+    notify_changed(&window.app_handle(), &ops);
+    record_undo(window, &db, label, ops);
+    Ok(result)
+}
+
+/// The change has already been saved, so failing to record it only costs the undo step.
+/// Grouped with whatever `window` has in `history_begin_group`.
+fn record_undo(window: &Window, db: &Db, label: &str, ops: Vec<Op>) {
+    let recorded = window.state::<Undo>().0.lock().unwrap().record(db, window.label(), label, ops, now() as i64);
+    if let Err(err) = recorded {
+        println!("[error]: could not record \"{}\" for undo: {}", label, err);
+    }
+}
+
+fn notify_changed(app: &AppHandle, ops: &[Op]) {
+    let tables: std::collections::BTreeSet<_> = ops.iter().map(Op::table).collect();
+    for table in tables {
+        db_changed(app, table);
+    }
+}
+
+/// Adds an empty node, optionally as a parent or child of `from`. Returns its id.
+#[tauri::command]
+fn graph_add(
+    window: Window,
+    database: tauri::State<Database>,
+    from: Option<String>,
+    connection: Option<Connection>,
+) -> Result<String, GraphError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let label = match from.as_ref().and(connection) {
+        None => "add node",
+        Some(Connection::Child) => "add child",
+        Some(Connection::Parent) => "add parent",
+    };
+    update_graph(&window, &database, label, |graph| {
+        toposort::add(graph, &id, chrono::Utc::now(), from.as_deref().zip(connection))
+    })?;
+    Ok(id)
+}
+
+#[tauri::command]
+fn graph_link(window: Window, database: tauri::State<Database>, parent: String, child: String) -> Result<bool, GraphError> {
+    update_graph(&window, &database, "link", |graph| toposort::link(graph, &parent, &child))
+}
+
+#[tauri::command]
+fn graph_unlink(window: Window, database: tauri::State<Database>, parent: String, child: String) -> Result<bool, GraphError> {
+    update_graph(&window, &database, "unlink", |graph| toposort::unlink(graph, &parent, &child))
+}
+
+/// Cycles left over from before links were checked, so they can be unlinked.
+#[tauri::command]
+fn graph_cycles(database: tauri::State<Database>) -> Result<Vec<Cycle>, GraphError> {
+    Ok(toposort::cycles(&database.0.lock().unwrap().graph()?))
+}
+
+/// `status` is "active", "done" or "unset".
+#[tauri::command]
+fn graph_set_status(window: Window, database: tauri::State<Database>, id: String, status: String) -> Result<(), GraphError> {
+    update_graph(&window, &database, "set status", |graph| toposort::set_status(graph, &id, &status))
+}
+
+#[tauri::command]
+fn graph_sorted(database: tauri::State<Database>) -> Result<SortedGraph, GraphError> {
+    Ok(toposort::sorted(&database.0.lock().unwrap().graph()?))
+}
+
+/// Reverts the newest change to the graph or activity log.
+#[tauri::command]
+fn history_undo(app: AppHandle, database: tauri::State<Database>, undo: tauri::State<Undo>) -> Result<UndoStatus, String> {
+    let db = database.0.lock().unwrap();
+    let mut undo = undo.0.lock().unwrap();
+    if let Some(entry) = undo.undo(&db).map_err(|e| e.to_string())? {
+        notify_changed(&app, &entry.ops);
+    }
+    let status = undo.status(&db).map_err(|e| e.to_string())?;
+    app.emit_all("history-changed", &status).ok();
+    Ok(status)
+}
+
+#[tauri::command]
+fn history_redo(app: AppHandle, database: tauri::State<Database>, undo: tauri::State<Undo>) -> Result<UndoStatus, String> {
+    let db = database.0.lock().unwrap();
+    let mut undo = undo.0.lock().unwrap();
+    if let Some(entry) = undo.redo(&db).map_err(|e| e.to_string())? {
+        notify_changed(&app, &entry.ops);
+    }
+    let status = undo.status(&db).map_err(|e| e.to_string())?;
+    app.emit_all("history-changed", &status).ok();
+    Ok(status)
+}
+
+#[tauri::command]
+fn history_status(database: tauri::State<Database>, undo: tauri::State<Undo>) -> Result<UndoStatus, String> {
+    let db = database.0.lock().unwrap();
+    let status = undo.0.lock().unwrap().status(&db);
+    status.map_err(|e| e.to_string())
+}
+
+/// Until `history_end_group`, changes from this window are undone together, e.g.
+/// deleting several nodes at once. Closes by itself after `undo::GROUP_TIMEOUT_MS`.
+#[tauri::command]
+fn history_begin_group(window: Window, undo: tauri::State<Undo>, label: String) {
+    undo.0.lock().unwrap().begin_group(window.label(), &label, now() as i64);
+}
+
+#[tauri::command]
+fn history_end_group(window: Window, undo: tauri::State<Undo>) {
+    undo.0.lock().unwrap().end_group(window.label());
+}
+
+/// Defaults to everything logged so far. Days and weeks are in local time.
+fn time_report(database: &Database, from: Option<i64>, to: Option<i64>) -> Result<Report, String> {
+    let db = database.0.lock().unwrap();
+    let now = now() as i64;
+    let to = to.unwrap_or(now);
+    let activity = db.activity(None, Some(to)).map_err(|e| e.to_string())?;
+    let from = from.or(activity.first().map(|row| row.created_at)).unwrap_or(to);
+    let verdicts = db
+        .verdicts(Some(from - reports::VERDICT_SPAN_MS), Some(to))
+        .map_err(|e| e.to_string())?;
+    let graph = db.graph().map_err(|e| e.to_string())?;
+    Ok(reports::report(&graph, &activity, &verdicts, from, to, now, &chrono::Local))
+}
+
+/// Time on task per node, focused vs distracted time and daily and weekly totals.
+#[tauri::command]
+fn report_time(database: tauri::State<Database>, from: Option<i64>, to: Option<i64>) -> Result<Report, String> {
+    time_report(&database, from, to)
+}
+
+/// The same report as CSV or Markdown text.
+#[tauri::command]
+fn report_export(
+    database: tauri::State<Database>,
+    from: Option<i64>,
+    to: Option<i64>,
+    format: ExportFormat,
+) -> Result<String, String> {
+    Ok(reports::export(&time_report(&database, from, to)?, format))
+}
+
+fn with_classifier(
+    config: ClassifierConfig,
+    secrets: &Secrets,
+    redactor: &Redactor,
+) -> (ClassifierConfig, Arc<dyn Classifier>) {
+    let classifier = Arc::from(config.build(secrets, redactor.local_only()));
+    (config, classifier)
+}
+
+#[tauri::command]
+fn get_classifier(active: tauri::State<ActiveClassifier>) -> ClassifierConfig {
+    active.0.lock().unwrap().0.clone()
+}
+
+#[tauri::command]
+fn set_classifier(
+    active: tauri::State<ActiveClassifier>,
+    secrets: tauri::State<Secrets>,
+    redactor: tauri::State<Redactor>,
+    config: ClassifierConfig,
+) {
+    *active.0.lock().unwrap() = with_classifier(config, &secrets, &redactor);
+}
+
+/// Fails rather than keeping API keys in a shared directory such as /tmp.
+fn open_secrets(app: &AppHandle) -> Result<Secrets, String> {
+    let dir = app.path_resolver().app_data_dir().ok_or("could not open secrets: no app data directory")?;
+    Ok(Secrets::open(dir))
+}
+
+/// Which API keys are stored. Their values never leave the backend.
+#[tauri::command]
+fn secrets_status(secrets: tauri::State<Secrets>) -> Result<SecretsStatus, String> {
+    secrets.status().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_secret(
+    secrets: tauri::State<Secrets>,
+    active: tauri::State<ActiveClassifier>,
+    redactor: tauri::State<Redactor>,
+    name: SecretName,
+    value: String,
+) -> Result<(), String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("The key is empty; use clear_secret to remove it".to_string());
+    }
+    secrets.set(name, value).map_err(|e| e.to_string())?;
+    rebuild_classifier(&active, &secrets, &redactor);
+    Ok(())
+}
+
+#[tauri::command]
+fn clear_secret(
+    secrets: tauri::State<Secrets>,
+    active: tauri::State<ActiveClassifier>,
+    redactor: tauri::State<Redactor>,
+    name: SecretName,
+) -> Result<bool, String> {
+    let cleared = secrets.clear(name).map_err(|e| e.to_string())?;
+    rebuild_classifier(&active, &secrets, &redactor);
+    Ok(cleared)
+}
+
+/// Picks up a changed key or `local_only` setting.
+fn rebuild_classifier(active: &ActiveClassifier, secrets: &Secrets, redactor: &Redactor) {
+    let mut active = active.0.lock().unwrap();
+    *active = with_classifier(active.0.clone(), secrets, redactor);
+}
+
+/// Checks a stored key against its service.
+#[tauri::command]
+async fn test_secret(
+    secrets: tauri::State<'_, Secrets>,
+    active: tauri::State<'_, ActiveClassifier>,
+    name: SecretName,
+) -> Result<(), String> {
+    let key = secrets.get(name).ok_or("No key is stored")?;
+    match name {
+        SecretName::OpenAi => {
+            let base_url = match &active.0.lock().unwrap().0 {
+                ClassifierConfig::OpenAi { base_url, .. } => base_url.clone(),
+                _ => SpeechConfig::default().base_url,
+            };
+            OpenAiClassifier::new(base_url, "", Some(key))
+                .check_key()
+                .await
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Text to speech, so the OpenAI key stays in the backend.
+#[tauri::command]
+async fn speak(secrets: tauri::State<'_, Secrets>, text: String, voice: Option<String>) -> Result<Speech, SpeechError> {
+    let mut config = SpeechConfig::default();
+    if let Some(voice) = voice {
+        config.voice = voice;
+    }
+    speech::speak(&config, secrets.get(SecretName::OpenAi).as_deref(), &text).await
+}
+
+/// Runs the active classifier on a frame from the frame history and records the verdict.
+#[tauri::command]
+async fn classify_frame(
+    history: tauri::State<'_, History>,
+    active: tauri::State<'_, ActiveClassifier>,
+    cache: tauri::State<'_, Cache>,
+    rules: tauri::State<'_, RuleBook>,
+    database: tauri::State<'_, Database>,
+    id: u64,
+) -> Result<Verdict, ClassifierError> {
+    classify_stored_frame(&history, &active, &cache, &rules, &database, id).await
+}
+
+/// Hit and miss counts of the verdict cache.
+#[tauri::command]
+fn classifier_diagnostics(cache: tauri::State<Cache>) -> CacheStats {
+    cache.0.lock().unwrap().stats()
+}
+
+/// Asks the user's rules first, then reuses a recent verdict when the frame looks like
+/// one the model has just seen, and only then runs the classifier. Stored frames are
+/// already redacted, and the classifier refuses them if they must stay on this machine.
+async fn classify_stored_frame(
+    history: &History,
+    active: &ActiveClassifier,
+    cache: &Cache,
+    rules: &RuleBook,
+    database: &Database,
+    id: u64,
+) -> Result<Verdict, ClassifierError> {
+    let frame = match history.0.lock().unwrap().as_ref() {
+        Some(history) => history
+            .get(id)
+            .map_err(|e| ClassifierError::FrameUnreadable { id, message: e.to_string() })?,
+        None => None,
+    };
+    let frame = frame.ok_or(ClassifierError::FrameNotFound { id })?;
+    let task = active_task(&database.0.lock().unwrap());
+    if let Some(verdict) = rule_verdict(rules, task.as_ref(), &frame.record) {
+        record_verdict(&database.0.lock().unwrap(), &verdict, Some(id));
+        return Ok(verdict);
+    }
+    let classifier = active.0.lock().unwrap().1.clone();
+    let prompt = Prompt::for_task(task.as_ref(), &classifier.model());
+    let key = verdict_cache::cache_key(&classifier.model(), &prompt, frame.record.foreground.as_ref());
+    let started = Instant::now();
+    let hash = PerceptualHash::of_encoded(&frame.bytes);
+    let cached = hash.and_then(|hash| cache.0.lock().unwrap().get(&hash, &key, now()));
+    let mut verdict = match cached {
+        Some(mut verdict) => {
+            // What this answer took, not what the model took the first time.
+            verdict.latency_ms = started.elapsed().as_millis() as u64;
+            verdict
+        }
+        None => {
+            let image = Image {
+                bytes: &frame.bytes,
+                mime_type: frame.record.encoding.mime_type(),
+            };
+            let verdict = verdict::classify(classifier.as_ref(), &image, &prompt).await?;
+            if let Some(hash) = hash {
+                cache.0.lock().unwrap().insert(hash, key, now(), verdict.clone());
+            }
+            verdict
+        }
+    };
+    verdict.foreground = frame.record.foreground;
+    record_verdict(&database.0.lock().unwrap(), &verdict, Some(id));
+    Ok(verdict)
+}
+
+fn open_rules(app: &AppHandle) -> Option<RulesWatcher> {
+    let dir = app.path_resolver().app_data_dir()?;
+    Some(RulesWatcher::open(dir))
+}
+
+/// Picks up edits to the rules file.
+fn watch_rules(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(2));
+        let status = match app.state::<RuleBook>().0.lock().unwrap().as_mut() {
+            Some(rules) if rules.reload_if_changed() => rules.status(),
+            _ => continue,
+        };
+        println!("[info]: reloaded {} rules from {:?}", status.rules, status.path);
+        app.emit_all("rules-reloaded", status).ok();
+    });
+}
+
+#[tauri::command]
+fn rules_status(rules: tauri::State<RuleBook>) -> Result<RulesStatus, String> {
+    let rules = rules.0.lock().unwrap();
+    Ok(rules.as_ref().ok_or("Rules are unavailable")?.status())
+}
+
+fn open_redactor(app: &AppHandle) -> Redactor {
+    match app.path_resolver().app_data_dir() {
+        Some(dir) => Redactor::open(dir.join("redaction.json"), redaction::from_env()),
+        None => Redactor::new(RedactionConfig::default(), redaction::from_env()),
+    }
+}
+
+#[tauri::command]
+fn get_redaction_config(redactor: tauri::State<Redactor>) -> RedactionConfig {
+    redactor.config()
+}
+
+#[tauri::command]
+fn set_redaction_config(
+    redactor: tauri::State<Redactor>,
+    active: tauri::State<ActiveClassifier>,
+    secrets: tauri::State<Secrets>,
+    config: RedactionConfig,
+) -> Result<(), String> {
+    redactor.set_config(config).map_err(|e| e.to_string())?;
+    rebuild_classifier(&active, &secrets, &redactor);
+    Ok(())
+}
+
+/// The verdict of the first rule that matches the frame's context, if any.
+fn rule_verdict(rules: &RuleBook, task: Option<&TaskContext>, record: &FrameRecord) -> Option<Verdict> {
+    let rules = rules.0.lock().unwrap();
+    let rules = rules.as_ref()?.rules();
+    if rules.is_empty() {
+        return None;
+    }
+    let context = RuleContext {
+        foreground: record.foreground.as_ref(),
+        minute_of_day: local_minute_of_day(record.timestamp),
+        task_type: task.and_then(|task| task.kind.as_deref()),
+    };
+    Some(rules.evaluate(&context)?.verdict(record.foreground.clone()))
+}
+
+/// The task currently being worked on, with the goals above it.
+fn active_task(database: &Db) -> Option<TaskContext> {
+    let row = database.last_activity().ok()??;
+    if row.kind != ActivityKind::Start {
+        return None;
+    }
+    TaskContext::from_graph(&database.graph().ok()?, &row.activity_id)
+}
+
+fn local_minute_of_day(timestamp: u64) -> u32 {
+    use chrono::{Local, TimeZone, Timelike};
+    let time = Local
+        .timestamp_millis_opt(timestamp as i64)
+        .single()
+        .unwrap_or_else(Local::now);
+    time.hour() * 60 + time.minute()
+}
+
+fn record_verdict(database: &Db, verdict: &Verdict, frame_id: Option<u64>) {
+    let activity_id = match database.last_activity() {
+        Ok(Some(row)) if row.kind == ActivityKind::Start => Some(row.activity_id),
+        _ => None,
+    };
+    let row = VerdictRow {
+        id: None,
+        timestamp: now() as i64,
+        frame_id: frame_id.map(|id| id as i64),
+        activity_id,
+        classifier: verdict.model.clone(),
+        prompt_version: verdict.prompt_version.clone(),
+        verdict: serde_json::to_value(verdict).unwrap_or_default(),
+    };
+    if let Err(err) = database.insert_verdict(&row) {
+        println!("[error]: could not record verdict: {}", err);
+    }
+}
+
+#[tauri::command]
+fn get_watch_config(scheduling: tauri::State<Scheduling>) -> WatchConfig {
+    *scheduling.0.lock().unwrap()
+}
+
+#[tauri::command]
+fn set_watch_config(scheduling: tauri::State<Scheduling>, config: WatchConfig) {
+    *scheduling.0.lock().unwrap() = config;
+}
+
+/// Tracks the frontmost app and window while capturing. Switching apps counts as a
+/// context switch for the focus state.
+fn watch_foreground(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        if app.state::<CaptureController>().status() != CaptureStatus::Running {
+            continue;
+        }
+        let Some(change) = app.state::<ForegroundWatcher>().poll(now()) else {
+            continue;
+        };
+        if change.switched_app() {
+            app.state::<Focus>().0.lock().unwrap().context_switch(change.timestamp);
+        }
+        app.emit_all("foreground-changed", change).ok();
+    });
+}
+
+/// The frontmost app and window as of the last poll. Only tracked while capturing.
+#[tauri::command]
+fn current_foreground(foreground: tauri::State<ForegroundWatcher>) -> Option<ForegroundInfo> {
+    foreground.current()
+}
+
+/// Diffs captured frames, stores the changed ones, tells the widget their ids and hands
+/// them to the classify loop.
+fn watch_frame_changes(app: AppHandle) {
+    let (frames, changed) = watch::channel(None);
+    classify_frame_changes(app.clone(), changed);
+    tauri::async_runtime::spawn(async move {
+        let capture = app.state::<CaptureController>();
+        let redactor = app.state::<Redactor>();
+        let detection = app.state::<ChangeDetection>();
+        let scheduling = app.state::<Scheduling>();
+        let history = app.state::<History>();
+        let database = app.state::<Database>();
+        let foreground = app.state::<ForegroundWatcher>();
+        screenshot::watch_changes(
+            &redactor.wrap(capture.inner()),
+            &detection.0,
+            || Duration::from_millis(scheduling.0.lock().unwrap().capture_interval_ms),
+            |frame, mut event| {
+                if let Some(history) = history.0.lock().unwrap().as_mut() {
+                    match history.insert(frame, event.score, foreground.current()) {
+                        Ok(record) => {
+                            record_frame(&database.0.lock().unwrap(), history, &record);
+                            event.frame_id = Some(record.id);
+                            frames.send_replace(Some(record.id));
+                        }
+                        Err(err) => println!("[error]: could not store frame: {}", err),
+                    }
+                }
+                app.emit_to("widget", "frame-changed", event).is_ok()
+            },
+        )
+        .await;
+    });
+}
+
+/// Classifies changed frames once the screen settles and emits a `verdict` event for each.
+fn classify_frame_changes(app: AppHandle, changed: watch::Receiver<Option<u64>>) {
+    tauri::async_runtime::spawn(async move {
+        let scheduling = app.state::<Scheduling>();
+        let history = app.state::<History>();
+        let active = app.state::<ActiveClassifier>();
+        let cache = app.state::<Cache>();
+        let rules = app.state::<RuleBook>();
+        let database = app.state::<Database>();
+        let focus = app.state::<Focus>();
+        watch_loop::classify_changes(
+            changed,
+            || *scheduling.0.lock().unwrap(),
+            |id| classify_stored_frame(&history, &active, &cache, &rules, &database, id),
+            |event| {
+                let change = focus.0.lock().unwrap().observe(now(), &event.verdict);
+                app.emit_all("verdict", event).ok();
+                if let Some(change) = change {
+                    focus_changed(&app, change);
+                }
+            },
+        )
+        .await;
+    });
+}
+
+/// Lets the focus state settle while the screen, and so the verdicts, stay the same.
+fn track_focus(app: AppHandle) {
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        let change = app.state::<Focus>().0.lock().unwrap().tick(now());
+        if let Some(change) = change {
+            focus_changed(&app, change);
+        }
+    });
+}
+
+fn focus_changed(app: &AppHandle, change: FocusChange) {
+    println!("[info]: focus {:?} -> {:?}", change.previous, change.state);
+    app.emit_all("focus-state-changed", change).ok();
+}
+
+#[tauri::command]
+fn focus_state(focus: tauri::State<Focus>) -> FocusState {
+    focus.0.lock().unwrap().state()
+}
+
+#[tauri::command]
+fn get_focus_config(focus: tauri::State<Focus>) -> FocusConfig {
+    focus.0.lock().unwrap().config
+}
+
+#[tauri::command]
+fn set_focus_config(focus: tauri::State<Focus>, config: FocusConfig) {
+    focus.0.lock().unwrap().config = config;
+}
+
+fn now() -> u64 {
+    screenshot::millis_since_epoch(std::time::SystemTime::now())
+}
+
+/// Mirrors a stored frame into the database and forgets the ones history has pruned.
+fn record_frame(database: &Db, history: &FrameHistory, record: &FrameRecord) {
+    let oldest = history.list(None, None, Some(1)).first().map_or(record.timestamp, |r| r.timestamp);
+    let result = database
+        .insert_frame(record.id as i64, record.timestamp as i64, &record.file, record.foreground.as_ref())
+        .and_then(|_| database.delete_frames_before(oldest as i64));
+    if let Err(err) = result {
+        println!("[error]: could not record frame: {}", err);
+    }
 }
 
 #[tauri::command]
@@ -92,6 +1157,7 @@ fn show_panel(handle: AppHandle<Wry>) {
   open_panel(&handle);
 }
 
+#[cfg(target_os = "macos")]
 fn open_panel(handle: &AppHandle<Wry>) {
   let window = handle.get_window("main").unwrap();
   position_window_fullscreen(&window, 1.0);
@@ -101,23 +1167,40 @@ fn open_panel(handle: &AppHandle<Wry>) {
   panel.make_key_window();
 }
 
+#[cfg(not(target_os = "macos"))]
+fn open_panel(handle: &AppHandle<Wry>) {
+  let window = handle.get_window("main").unwrap();
+  window.show().ok();
+  window.set_focus().ok();
+}
+
 #[tauri::command]
 fn hide_panel(handle: AppHandle<Wry>) {
-  let panel = handle.get_panel("main").unwrap();
-  panel.order_out(None);
+  #[cfg(target_os = "macos")]
+  handle.get_panel("main").unwrap().order_out(None);
+  #[cfg(not(target_os = "macos"))]
+  handle.get_window("main").unwrap().hide().ok();
 }
 
 #[tauri::command]
 fn close_panel(handle: AppHandle<Wry>) {
-  let panel = handle.get_panel("main").unwrap();
-  panel.released_when_closed(true);
-  panel.close();
+  #[cfg(target_os = "macos")]
+  {
+    let panel = handle.get_panel("main").unwrap();
+    panel.released_when_closed(true);
+    panel.close();
+  }
+  #[cfg(not(target_os = "macos"))]
+  handle.get_window("main").unwrap().close().ok();
 }
 
 #[tauri::command]
 fn toggle_panel(app_handle: AppHandle<Wry>) {
-    let panel = app_handle.get_panel("main").unwrap();
-    if panel.is_visible() {
+    #[cfg(target_os = "macos")]
+    let visible = app_handle.get_panel("main").unwrap().is_visible();
+    #[cfg(not(target_os = "macos"))]
+    let visible = app_handle.get_window("main").unwrap().is_visible().unwrap_or(false);
+    if visible {
         hide_panel(app_handle);
     } else {
         show_panel(app_handle);
@@ -135,6 +1218,7 @@ struct MouseMoved {
     window_height: f64,
 }
 
+#[cfg(target_os = "macos")]
 fn track_mouse(app: &AppHandle) {
     let widget = app.get_window("widget").unwrap();
     let widget_window = widget.ns_window().unwrap() as id;
@@ -0,0 +1,179 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::Graph;
+
+/// A prompt as written, with `{{placeholders}}`. Never edit a published template;
+/// add one with a new version so stored verdicts keep pointing at the text they used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PromptTemplate {
+    pub version: &'static str,
+    pub system: &'static str,
+    pub user: &'static str,
+}
+
+/// The original fixed prompt, for when no task is active.
+pub const GENERIC_V1: PromptTemplate = PromptTemplate {
+    version: "generic-v1",
+    system: "You are an AI assistant tasked with analyzing the user's screen. \
+You must respond in valid JSON. Use the following typescript type: \
+{ description: string; activity: string; reason: string; }. Do not use a markdown code block.",
+    user: r#"Describe the nature of the activity in the screen with one of the following categories:
+- "work" - only productive work-related activities.
+- "distraction" - includes social media, news, youtube, etc.
+- "unknown" - if you are unsure.
+"#,
+};
+
+/// `GENERIC_V1` with the closing instruction the Ollama backend always had. The other
+/// backends never leaned towards "distraction", so they keep `GENERIC_V1`.
+pub const GENERIC_OLLAMA_V1: PromptTemplate = PromptTemplate {
+    version: "generic-ollama-v1",
+    system: GENERIC_V1.system,
+    user: r#"Describe the nature of the activity in the screen with one of the following categories:
+- "work" - only productive work-related activities.
+- "distraction" - includes social media, news, youtube, etc.
+- "unknown" - if you are unsure.
+
+Be liberal with the "distraction" category. All videos should be considered distractions.
+"#,
+};
+
+/// Judges the screen against the task the user said they are working on. Unlike
+/// `GENERIC_OLLAMA_V1` it does not lean towards "distraction": a video about the task is work.
+pub const TASK_V1: PromptTemplate = PromptTemplate {
+    version: "task-v1",
+    system: "You are an AI assistant tasked with analyzing the user's screen and deciding whether \
+it serves the task they said they are working on. \
+You must respond in valid JSON. Use the following typescript type: \
+{ description: string; activity: string; reason: string; }. Do not use a markdown code block.",
+    user: r#"The user is working on this task:
+{{task}}
+
+Describe the nature of the activity in the screen with one of the following categories:
+- "work" - anything that plausibly serves this task, including tutorials, documentation, videos or research about it.
+- "distraction" - unrelated to the task, such as social media, news or entertainment.
+- "unknown" - if you are unsure.
+"#,
+};
+
+/// Every template that has been used, so verdicts can be traced to their prompt.
+pub const TEMPLATES: &[PromptTemplate] = &[GENERIC_V1, GENERIC_OLLAMA_V1, TASK_V1];
+
+/// The task from the activity log, as the model should see it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskContext {
+    pub title: String,
+    pub notes: Option<String>,
+    /// task, goal, project or problem.
+    pub kind: Option<String>,
+    /// Titles of the goals this task serves, outermost first.
+    pub ancestors: Vec<String>,
+}
+
+impl TaskContext {
+    /// Reads node `id` and its ancestors, following both `children` lists and
+    /// `depends` relations. `None` if the node does not exist.
+    pub fn from_graph(graph: &Graph, id: &str) -> Option<Self> {
+        let node = graph.nodes.get(id)?;
+        let text = |node: &Value, key: &str| {
+            node.get(key)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string)
+        };
+        let parents = |child: &str| -> Vec<String> {
+            let from_children = graph.nodes.iter().filter_map(|(parent, data)| {
+                let children = data.get("children")?.as_array()?;
+                children.iter().any(|c| c.as_str() == Some(child)).then(|| parent.clone())
+            });
+            let from_relations = graph
+                .relations
+                .get("depends")
+                .into_iter()
+                .flatten()
+                .filter(|relation| relation.child == child)
+                .map(|relation| relation.parent.clone());
+            from_children.chain(from_relations).collect()
+        };
+
+        // Breadth-first, so nearer goals come later once reversed.
+        let mut seen = BTreeSet::from([id.to_string()]);
+        let mut queue = VecDeque::from([id.to_string()]);
+        let mut ancestors = vec![];
+        while let Some(current) = queue.pop_front() {
+            for parent in parents(&current) {
+                if seen.insert(parent.clone()) {
+                    if let Some(title) = graph.nodes.get(&parent).and_then(|data| text(data, "value")) {
+                        ancestors.push(title);
+                    }
+                    queue.push_back(parent);
+                }
+            }
+        }
+        ancestors.reverse();
+
+        Some(TaskContext {
+            title: text(node, "value").unwrap_or_default(),
+            notes: text(node, "notes"),
+            kind: text(node, "type"),
+            ancestors,
+        })
+    }
+
+    fn render(&self) -> String {
+        let mut out = format!("Task: {}", self.title);
+        if let Some(kind) = &self.kind {
+            out += &format!("\nType: {}", kind);
+        }
+        if !self.ancestors.is_empty() {
+            out += &format!("\nPart of: {}", self.ancestors.join(" > "));
+        }
+        if let Some(notes) = &self.notes {
+            out += &format!("\nNotes: {}", notes);
+        }
+        out
+    }
+}
+
+/// A template filled in for one request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    pub version: &'static str,
+    pub system: String,
+    pub user: String,
+}
+
+impl Prompt {
+    /// `TASK_V1` when a task is active, otherwise the generic prompt for `model`'s
+    /// backend. `model` is `<backend>:<model>`, as `Classifier::model` returns.
+    pub fn for_task(task: Option<&TaskContext>, model: &str) -> Prompt {
+        match task {
+            Some(task) => render(&TASK_V1, &[("task", &task.render())]),
+            None if model.starts_with("ollama:") => render(&GENERIC_OLLAMA_V1, &[]),
+            None => render(&GENERIC_V1, &[]),
+        }
+    }
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Prompt::for_task(None, "")
+    }
+}
+
+pub fn render(template: &PromptTemplate, values: &[(&str, &str)]) -> Prompt {
+    let fill = |text: &str| {
+        values
+            .iter()
+            .fold(text.to_string(), |text, (key, value)| text.replace(&format!("{{{{{}}}}}", key), value))
+    };
+    Prompt {
+        version: template.version,
+        system: fill(template.system),
+        user: fill(template.user),
+    }
+}
@@ -0,0 +1,436 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::frame_source::{CaptureError, CapturedFrame, FrameSource, Permission, Rect, ScreenRect};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "style", rename_all = "lowercase")]
+pub enum RedactionStyle {
+    #[default]
+    Blackout,
+    /// Averages `block_size`-pixel squares, which keeps the layout but not the text.
+    Blur { block_size: u32 },
+}
+
+/// What to hide from every frame before it is stored, shown or classified.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub style: RedactionStyle,
+    /// Always hidden, in screen coordinates.
+    pub regions: Vec<ScreenRect>,
+    /// Bundle ids or X11 classes whose windows are hidden wherever they are visible.
+    pub apps: Vec<String>,
+    /// The focused password field. Needs Accessibility permission on macOS; X11 has no way to tell.
+    pub password_fields: bool,
+    pub notifications: bool,
+    /// Only classify with backends on this machine.
+    pub local_only: bool,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            style: RedactionStyle::Blackout,
+            regions: vec![],
+            apps: vec![],
+            password_fields: true,
+            notifications: true,
+            local_only: false,
+        }
+    }
+}
+
+/// An on-screen window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowInfo {
+    /// Bundle id on macOS, `WM_CLASS` class on X11.
+    pub app_id: String,
+    pub rect: ScreenRect,
+    /// A notification banner rather than an app window.
+    #[serde(default)]
+    pub notification: bool,
+}
+
+/// What the window manager knows about the screen, beyond its pixels.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScreenLayout {
+    /// Front to back.
+    pub windows: Vec<WindowInfo>,
+    pub password_fields: Vec<ScreenRect>,
+}
+
+pub trait LayoutProvider: Send + Sync {
+    fn layout(&self) -> ScreenLayout;
+}
+
+/// How much of a frame was hidden.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RedactionReport {
+    pub windows: usize,
+    pub notifications: usize,
+    pub password_fields: usize,
+    pub regions: usize,
+    pub pixels: u64,
+}
+
+/// Hides what `config` asks for in place. Windows are painted back to front, so an app
+/// window is only hidden where no other window covers it.
+pub fn redact(frame: &mut CapturedFrame, config: &RedactionConfig, layout: &ScreenLayout) -> RedactionReport {
+    let mut report = RedactionReport::default();
+    // (rect, hide): later entries are in front of earlier ones.
+    let mut paint = vec![];
+    for window in layout.windows.iter().rev() {
+        let hide = if window.notification {
+            config.notifications
+        } else {
+            config.apps.iter().any(|app| app.eq_ignore_ascii_case(&window.app_id))
+        };
+        let Some(rect) = frame.screen_to_pixels(window.rect) else {
+            continue;
+        };
+        if hide {
+            if window.notification {
+                report.notifications += 1;
+            } else {
+                report.windows += 1;
+            }
+        }
+        paint.push((rect, hide));
+    }
+    if config.password_fields {
+        for &field in &layout.password_fields {
+            if let Some(rect) = frame.screen_to_pixels(field) {
+                report.password_fields += 1;
+                paint.push((rect, true));
+            }
+        }
+    }
+    for &region in &config.regions {
+        if let Some(rect) = frame.screen_to_pixels(region) {
+            report.regions += 1;
+            paint.push((rect, true));
+        }
+    }
+    if !paint.iter().any(|(_, hide)| *hide) {
+        return report;
+    }
+
+    let width = frame.width as usize;
+    let mut mask = vec![false; width * frame.height as usize];
+    for (rect, hide) in paint {
+        for y in rect.y..rect.y + rect.height {
+            let row = y as usize * width;
+            mask[row + rect.x as usize..row + (rect.x + rect.width) as usize].fill(hide);
+        }
+    }
+    report.pixels = mask.iter().filter(|&&hidden| hidden).count() as u64;
+    match config.style {
+        RedactionStyle::Blackout => blackout(frame, &mask),
+        RedactionStyle::Blur { block_size } => blur(frame, &mask, block_size.max(2)),
+    }
+    report
+}
+
+fn pixel_offset(frame: &CapturedFrame, x: u32, y: u32) -> usize {
+    y as usize * frame.stride + x as usize * 4
+}
+
+fn blackout(frame: &mut CapturedFrame, mask: &[bool]) {
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            if mask[(y * frame.width + x) as usize] {
+                let at = pixel_offset(frame, x, y);
+                // Black with an opaque alpha byte in both RGBA and BGRA.
+                frame.data[at..at + 4].copy_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+    }
+}
+
+fn blur(frame: &mut CapturedFrame, mask: &[bool], block_size: u32) {
+    for top in (0..frame.height).step_by(block_size as usize) {
+        for left in (0..frame.width).step_by(block_size as usize) {
+            let block = Rect {
+                x: left,
+                y: top,
+                width: block_size.min(frame.width - left),
+                height: block_size.min(frame.height - top),
+            };
+            let pixels = || {
+                (block.y..block.y + block.height).flat_map(move |y| (block.x..block.x + block.width).map(move |x| (x, y)))
+            };
+            if !pixels().any(|(x, y)| mask[(y * frame.width + x) as usize]) {
+                continue;
+            }
+            let mut sum = [0u64; 4];
+            for (x, y) in pixels() {
+                let at = pixel_offset(frame, x, y);
+                for (total, &value) in sum.iter_mut().zip(&frame.data[at..at + 4]) {
+                    *total += value as u64;
+                }
+            }
+            let count = (block.width * block.height) as u64;
+            let average = sum.map(|total| (total / count) as u8);
+            for (x, y) in pixels() {
+                if mask[(y * frame.width + x) as usize] {
+                    let at = pixel_offset(frame, x, y);
+                    frame.data[at..at + 4].copy_from_slice(&average);
+                }
+            }
+        }
+    }
+}
+
+/// The redaction settings plus where the screen layout comes from. Settings are kept
+/// in a JSON file so they survive restarts.
+pub struct Redactor {
+    config: Mutex<RedactionConfig>,
+    layout: Box<dyn LayoutProvider>,
+    path: Option<PathBuf>,
+}
+
+impl Redactor {
+    pub fn new(config: RedactionConfig, layout: Box<dyn LayoutProvider>) -> Self {
+        Redactor {
+            config: Mutex::new(config),
+            layout,
+            path: None,
+        }
+    }
+
+    /// Loads the settings from `path`, or the defaults if there is no such file.
+    pub fn open(path: impl Into<PathBuf>, layout: Box<dyn LayoutProvider>) -> Self {
+        let path = path.into();
+        let config = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+                // Fail closed: the file may have asked to keep frames on this machine.
+                println!("[error]: could not read redaction settings, keeping frames local: {}", err);
+                RedactionConfig {
+                    local_only: true,
+                    ..RedactionConfig::default()
+                }
+            }),
+            Err(_) => RedactionConfig::default(),
+        };
+        Redactor {
+            path: Some(path),
+            ..Redactor::new(config, layout)
+        }
+    }
+
+    pub fn config(&self) -> RedactionConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: RedactionConfig) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            fs::write(path, serde_json::to_string_pretty(&config)?)?;
+        }
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    pub fn local_only(&self) -> bool {
+        self.config.lock().unwrap().local_only
+    }
+
+    pub fn apply(&self, frame: &mut CapturedFrame) -> RedactionReport {
+        let config = self.config();
+        let nothing_to_find = config.apps.is_empty() && !config.password_fields && !config.notifications;
+        let layout = if nothing_to_find {
+            ScreenLayout::default()
+        } else {
+            self.layout.layout()
+        };
+        redact(frame, &config, &layout)
+    }
+
+    /// `source`, with every frame redacted.
+    pub fn wrap<'a>(&'a self, source: &'a dyn FrameSource) -> Redacted<'a> {
+        Redacted { source, redactor: self }
+    }
+}
+
+pub struct Redacted<'a> {
+    source: &'a dyn FrameSource,
+    redactor: &'a Redactor,
+}
+
+impl FrameSource for Redacted<'_> {
+    fn start(&self) -> Result<(), CaptureError> {
+        self.source.start()
+    }
+
+    fn stop(&self) {
+        self.source.stop()
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        let mut frame = self.source.latest_frame()?;
+        self.redactor.apply(&mut frame);
+        Ok(frame)
+    }
+
+    fn permission(&self) -> Permission {
+        self.source.permission()
+    }
+
+    fn request_permission(&self) -> Permission {
+        self.source.request_permission()
+    }
+}
+
+/// Picks a provider from `INTERO_SCREEN_LAYOUT`:
+///   - unset / `native`: the platform's window manager
+///   - `fake`: an empty screen, until set
+pub fn from_env() -> Box<dyn LayoutProvider> {
+    match std::env::var("INTERO_SCREEN_LAYOUT").unwrap_or_default().as_str() {
+        "fake" => Box::new(FixedLayout::default()),
+        _ => native(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn native() -> Box<dyn LayoutProvider> {
+    Box::new(MacLayout)
+}
+
+#[cfg(target_os = "linux")]
+fn native() -> Box<dyn LayoutProvider> {
+    Box::new(X11Layout::default())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn native() -> Box<dyn LayoutProvider> {
+    println!("[error]: no screen layout provider on this platform, only fixed regions are redacted");
+    Box::new(FixedLayout::default())
+}
+
+// macOS
+
+/// The window list for windows and banners, and the Accessibility API for the focused
+/// password field.
+#[cfg(target_os = "macos")]
+pub struct MacLayout;
+
+#[cfg(target_os = "macos")]
+impl LayoutProvider for MacLayout {
+    fn layout(&self) -> ScreenLayout {
+        let json = unsafe { crate::ffi::get_screen_layout() };
+        serde_json::from_str(json.as_str()).unwrap_or_else(|err| {
+            println!("[error]: could not read the screen layout: {}", err);
+            ScreenLayout::default()
+        })
+    }
+}
+
+// X11
+
+/// `_NET_CLIENT_LIST_STACKING` for app windows, plus top-level windows of type
+/// `_NET_WM_WINDOW_TYPE_NOTIFICATION`. Password fields cannot be detected.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+pub struct X11Layout {
+    conn: Mutex<Option<crate::foreground::X11Connection>>,
+}
+
+#[cfg(target_os = "linux")]
+fn windows(conn: &crate::foreground::X11Connection) -> Result<Vec<WindowInfo>, Box<dyn std::error::Error>> {
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, MapState};
+
+    let ids = |bytes: Vec<u8>| -> Vec<u32> {
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect()
+    };
+    let rect = |window: u32| -> Result<ScreenRect, Box<dyn std::error::Error>> {
+        let geometry = conn.conn.get_geometry(window)?.reply()?;
+        let origin = conn.conn.translate_coordinates(window, conn.root, 0, 0)?.reply()?;
+        Ok(ScreenRect {
+            x: origin.dst_x as f64,
+            y: origin.dst_y as f64,
+            width: geometry.width as f64,
+            height: geometry.height as f64,
+        })
+    };
+
+    let info = |window: u32, notification: bool| -> Result<WindowInfo, Box<dyn std::error::Error>> {
+        Ok(WindowInfo {
+            app_id: conn.app_id(window)?,
+            rect: rect(window)?,
+            notification,
+        })
+    };
+
+    let mut windows = vec![];
+    // Notification daemons usually bypass the window manager, so look at every top-level window.
+    for window in conn.conn.query_tree(conn.root)?.reply()?.children.into_iter().rev() {
+        let types = ids(conn.property(window, conn.wm_window_type, AtomEnum::ATOM.into())?);
+        if !types.contains(&conn.window_type_notification) {
+            continue;
+        }
+        if conn.conn.get_window_attributes(window)?.reply()?.map_state != MapState::VIEWABLE {
+            continue;
+        }
+        // Windows can close while we look at them.
+        windows.extend(info(window, true).ok());
+    }
+    let stacking = ids(conn.property(conn.root, conn.client_list_stacking, AtomEnum::WINDOW.into())?);
+    windows.extend(stacking.into_iter().rev().filter_map(|window| info(window, false).ok()));
+    Ok(windows)
+}
+
+#[cfg(target_os = "linux")]
+impl LayoutProvider for X11Layout {
+    fn layout(&self) -> ScreenLayout {
+        let mut conn = self.conn.lock().unwrap();
+        if conn.is_none() {
+            *conn = crate::foreground::X11Connection::connect()
+                .map_err(|err| println!("[error]: could not connect to X11 for the window list: {}", err))
+                .ok();
+        }
+        let Some(current) = conn.as_ref() else {
+            return ScreenLayout::default();
+        };
+        match windows(current) {
+            Ok(windows) => ScreenLayout {
+                windows,
+                password_fields: vec![],
+            },
+            Err(err) => {
+                // Reconnect on the next call.
+                println!("[error]: could not read the window list: {}", err);
+                conn.take();
+                ScreenLayout::default()
+            }
+        }
+    }
+}
+
+// Fake
+
+/// Returns whatever layout it was last given.
+#[derive(Default)]
+pub struct FixedLayout(Mutex<ScreenLayout>);
+
+impl FixedLayout {
+    pub fn new(layout: ScreenLayout) -> Self {
+        FixedLayout(Mutex::new(layout))
+    }
+
+    pub fn set(&self, layout: ScreenLayout) {
+        *self.0.lock().unwrap() = layout;
+    }
+}
+
+impl LayoutProvider for FixedLayout {
+    fn layout(&self) -> ScreenLayout {
+        self.0.lock().unwrap().clone()
+    }
+}
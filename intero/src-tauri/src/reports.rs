@@ -0,0 +1,417 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+
+use chrono::{Datelike, Days, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::{ActivityKind, ActivityRow, Graph, VerdictRow};
+use crate::toposort;
+use crate::verdict::Category;
+
+// Time accounting over the activity log. Times are ms since the Unix epoch.
+
+/// How long a verdict counts for when no newer one comes sooner. With change detection
+/// an unchanged screen produces no new verdicts, but capture may also have been paused.
+pub const VERDICT_SPAN_MS: i64 = 5 * 60_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub tracked_ms: i64,
+    /// Covered by a `work` verdict.
+    pub focused_ms: i64,
+    /// Covered by a `distraction` verdict. The rest of `tracked_ms` is unclassified.
+    pub distracted_ms: i64,
+}
+
+impl Totals {
+    fn add(&mut self, ms: i64, category: Option<Category>) {
+        self.tracked_ms += ms;
+        match category {
+            Some(Category::Work) => self.focused_ms += ms,
+            Some(Category::Distraction) => self.distracted_ms += ms,
+            Some(Category::Unknown) | None => {}
+        }
+    }
+
+    fn merge(&mut self, other: &Totals) {
+        self.tracked_ms += other.tracked_ms;
+        self.focused_ms += other.focused_ms;
+        self.distracted_ms += other.distracted_ms;
+    }
+
+    pub fn unclassified_ms(&self) -> i64 {
+        self.tracked_ms - self.focused_ms - self.distracted_ms
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeTime {
+    pub id: String,
+    /// The node's `value`, or `None` if it has been deleted since.
+    pub title: Option<String>,
+    /// While the node itself was the activity.
+    pub own: Totals,
+    /// `own` plus that of every descendant, each counted once.
+    pub total: Totals,
+    /// `estimatedTime`, to compare against `total.tracked_ms`.
+    pub estimate_ms: Option<i64>,
+}
+
+impl NodeTime {
+    /// Time spent beyond the estimate; negative if under it.
+    pub fn over_estimate_ms(&self) -> Option<i64> {
+        self.estimate_ms.map(|estimate| self.total.tracked_ms - estimate)
+    }
+}
+
+/// A local calendar day or ISO week.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Period {
+    /// "2026-10-18" for days, "2026-W42" for weeks.
+    pub label: String,
+    pub from: i64,
+    pub to: i64,
+    pub totals: Totals,
+}
+
+/// Returned by the `report_time` command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub from: i64,
+    pub to: i64,
+    pub totals: Totals,
+    /// Nodes with tracked time, themselves or below them, most first.
+    pub nodes: Vec<NodeTime>,
+    /// Only days and weeks with tracked time, oldest first.
+    pub days: Vec<Period>,
+    pub weeks: Vec<Period>,
+}
+
+/// A stretch of time spent on one activity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Session {
+    pub activity_id: String,
+    pub from: i64,
+    pub to: i64,
+}
+
+/// A start row lasts until the next row of any kind, and the last one, if nothing
+/// stopped it, until `now`. `end_time` is when the timer runs out rather than when
+/// work stopped, so it is not used. `activity` is oldest first, as `Db::activity` returns it.
+pub fn sessions(activity: &[ActivityRow], now: i64) -> Vec<Session> {
+    let mut out = vec![];
+    for (i, row) in activity.iter().enumerate() {
+        if row.kind != ActivityKind::Start {
+            continue;
+        }
+        let to = activity.get(i + 1).map_or(now, |next| next.created_at);
+        if to > row.created_at {
+            out.push(Session {
+                activity_id: row.activity_id.clone(),
+                from: row.created_at,
+                to,
+            });
+        }
+    }
+    out
+}
+
+/// What a verdict says about the time until the next one, up to `VERDICT_SPAN_MS`.
+struct Coverage {
+    from: i64,
+    to: i64,
+    category: Option<Category>,
+}
+
+fn coverage(verdicts: &[VerdictRow]) -> Vec<Coverage> {
+    let mut out = vec![];
+    for (i, row) in verdicts.iter().enumerate() {
+        let until = row.timestamp + VERDICT_SPAN_MS;
+        let to = verdicts.get(i + 1).map_or(until, |next| next.timestamp.min(until));
+        let category = row
+            .verdict
+            .get("category")
+            .and_then(|category| serde_json::from_value(category.clone()).ok());
+        if to > row.timestamp {
+            out.push(Coverage {
+                from: row.timestamp,
+                to,
+                category,
+            });
+        }
+    }
+    out
+}
+
+/// The start of `date` in `tz`. When midnight is skipped by a DST change, the first
+/// time that exists that day.
+fn day_start<Tz: TimeZone>(tz: &Tz, date: NaiveDate) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight exists");
+    (0..24)
+        .find_map(|hour| tz.from_local_datetime(&(midnight + chrono::Duration::hours(hour))).earliest())
+        .map_or_else(|| midnight.and_utc().timestamp_millis(), |time| time.timestamp_millis())
+}
+
+fn local_date<Tz: TimeZone>(tz: &Tz, timestamp: i64) -> NaiveDate {
+    tz.timestamp_millis_opt(timestamp)
+        .single()
+        .expect("timestamps map to one local time")
+        .date_naive()
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(date.weekday().num_days_from_monday().into())
+}
+
+/// Time on task between `from` and `to`, with days and weeks in `tz`. `activity` has
+/// to include the row that was running at `from`; verdicts are only needed from
+/// `VERDICT_SPAN_MS` before it.
+pub fn report<Tz: TimeZone>(
+    graph: &Graph,
+    activity: &[ActivityRow],
+    verdicts: &[VerdictRow],
+    from: i64,
+    to: i64,
+    now: i64,
+    tz: &Tz,
+) -> Report {
+    let coverage = coverage(verdicts);
+    let mut totals = Totals::default();
+    let mut own: HashMap<String, Totals> = HashMap::new();
+    let mut days: BTreeMap<NaiveDate, Totals> = BTreeMap::new();
+    let mut weeks: BTreeMap<NaiveDate, Totals> = BTreeMap::new();
+
+    let mut v = 0;
+    for session in sessions(activity, now) {
+        let end = session.to.min(to);
+        let mut at = session.from.max(from);
+        while at < end {
+            while coverage.get(v).is_some_and(|c| c.to <= at) {
+                v += 1;
+            }
+            let (until, category) = match coverage.get(v) {
+                Some(c) if c.from <= at => (c.to.min(end), c.category),
+                Some(c) => (c.from.min(end), None),
+                None => (end, None),
+            };
+            totals.add(until - at, category);
+            own.entry(session.activity_id.clone()).or_default().add(until - at, category);
+
+            // Split at local midnights.
+            let mut piece = at;
+            while piece < until {
+                let date = local_date(tz, piece);
+                let next = day_start(tz, date + Days::new(1)).clamp(piece + 1, until);
+                days.entry(date).or_default().add(next - piece, category);
+                weeks.entry(week_start(date)).or_default().add(next - piece, category);
+                piece = next;
+            }
+            at = until;
+        }
+    }
+
+    Report {
+        from,
+        to,
+        totals,
+        nodes: roll_up(graph, &own),
+        days: days
+            .into_iter()
+            .map(|(date, totals)| Period {
+                label: date.format("%Y-%m-%d").to_string(),
+                from: day_start(tz, date),
+                to: day_start(tz, date + Days::new(1)),
+                totals,
+            })
+            .collect(),
+        weeks: weeks
+            .into_iter()
+            .map(|(date, totals)| Period {
+                label: format!("{}-W{:02}", date.iso_week().year(), date.iso_week().week()),
+                from: day_start(tz, date),
+                to: day_start(tz, date + Days::new(7)),
+                totals,
+            })
+            .collect(),
+    }
+}
+
+/// Adds each node's own time to all of its ancestors through `depends`.
+fn roll_up(graph: &Graph, own: &HashMap<String, Totals>) -> Vec<NodeTime> {
+    let mut parents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (parent, children) in toposort::children(graph) {
+        for child in children {
+            parents.entry(child).or_default().push(parent);
+        }
+    }
+
+    let mut total: HashMap<&str, Totals> = HashMap::new();
+    for (id, times) in own {
+        // Older data can have cycles, and a node can be reached along several paths.
+        let mut seen = HashSet::from([id.as_str()]);
+        let mut queue = VecDeque::from([id.as_str()]);
+        while let Some(node) = queue.pop_front() {
+            total.entry(node).or_default().merge(times);
+            for &parent in parents.get(node).into_iter().flatten() {
+                if seen.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+    }
+
+    let mut nodes: Vec<NodeTime> = total
+        .into_iter()
+        .map(|(id, total)| {
+            let data = graph.nodes.get(id);
+            NodeTime {
+                id: id.to_string(),
+                title: data.and_then(|data| data.get("value")).and_then(Value::as_str).map(str::to_string),
+                own: own.get(id).copied().unwrap_or_default(),
+                total,
+                estimate_ms: data
+                    .and_then(|data| data.get("estimatedTime"))
+                    .and_then(Value::as_f64)
+                    .map(|minutes| (minutes * 60_000.0) as i64),
+            }
+        })
+        .collect();
+    nodes.sort_by(|a, b| b.total.tracked_ms.cmp(&a.total.tracked_ms).then_with(|| a.id.cmp(&b.id)));
+    nodes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    NodesCsv,
+    DaysCsv,
+    WeeksCsv,
+    Markdown,
+}
+
+pub fn export(report: &Report, format: ExportFormat) -> String {
+    match format {
+        ExportFormat::NodesCsv => nodes_csv(report),
+        ExportFormat::DaysCsv => periods_csv(&report.days),
+        ExportFormat::WeeksCsv => periods_csv(&report.weeks),
+        ExportFormat::Markdown => markdown(report),
+    }
+}
+
+fn minutes(ms: i64) -> String {
+    format!("{:.1}", ms as f64 / 60_000.0)
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// One row per node, in minutes. Estimate columns are empty for nodes without one.
+pub fn nodes_csv(report: &Report) -> String {
+    let mut out = String::from("id,title,total_min,own_min,focused_min,distracted_min,estimate_min,over_estimate_min\n");
+    for node in &report.nodes {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            csv_field(&node.id),
+            csv_field(node.title.as_deref().unwrap_or_default()),
+            minutes(node.total.tracked_ms),
+            minutes(node.own.tracked_ms),
+            minutes(node.total.focused_ms),
+            minutes(node.total.distracted_ms),
+            node.estimate_ms.map(minutes).unwrap_or_default(),
+            node.over_estimate_ms().map(minutes).unwrap_or_default(),
+        );
+    }
+    out
+}
+
+/// One row per day or week, in minutes.
+pub fn periods_csv(periods: &[Period]) -> String {
+    let mut out = String::from("period,tracked_min,focused_min,distracted_min,unclassified_min\n");
+    for period in periods {
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{}",
+            period.label,
+            minutes(period.totals.tracked_ms),
+            minutes(period.totals.focused_ms),
+            minutes(period.totals.distracted_ms),
+            minutes(period.totals.unclassified_ms()),
+        );
+    }
+    out
+}
+
+/// "1h 05m", rounded to the minute.
+pub fn duration(ms: i64) -> String {
+    let sign = if ms < 0 { "-" } else { "" };
+    let minutes = (ms.abs() + 30_000) / 60_000;
+    if minutes < 60 {
+        format!("{}{}m", sign, minutes)
+    } else {
+        format!("{}{}h {:02}m", sign, minutes / 60, minutes % 60)
+    }
+}
+
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn periods_table(out: &mut String, heading: &str, periods: &[Period]) {
+    let _ = writeln!(out, "\n## {}\n", heading);
+    out.push_str("| | Tracked | Focused | Distracted | Unclassified |\n|---|---:|---:|---:|---:|\n");
+    for period in periods {
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} | {} |",
+            period.label,
+            duration(period.totals.tracked_ms),
+            duration(period.totals.focused_ms),
+            duration(period.totals.distracted_ms),
+            duration(period.totals.unclassified_ms()),
+        );
+    }
+}
+
+pub fn markdown(report: &Report) -> String {
+    let mut out = String::from("# Time report");
+    if let (Some(first), Some(last)) = (report.days.first(), report.days.last()) {
+        let _ = write!(out, ", {} to {}", first.label, last.label);
+    }
+    let totals = &report.totals;
+    let _ = writeln!(
+        out,
+        "\n\nTracked {}: {} focused, {} distracted, {} unclassified.",
+        duration(totals.tracked_ms),
+        duration(totals.focused_ms),
+        duration(totals.distracted_ms),
+        duration(totals.unclassified_ms()),
+    );
+
+    out.push_str("\n## Tasks\n\n");
+    out.push_str("| Task | Total | Own | Focused | Distracted | Estimate | Over |\n");
+    out.push_str("|---|---:|---:|---:|---:|---:|---:|\n");
+    for node in &report.nodes {
+        let title = node.title.clone().unwrap_or_else(|| format!("{} (deleted)", node.id));
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} | {} | {} | {} |",
+            cell(&title),
+            duration(node.total.tracked_ms),
+            duration(node.own.tracked_ms),
+            duration(node.total.focused_ms),
+            duration(node.total.distracted_ms),
+            node.estimate_ms.map(duration).unwrap_or_default(),
+            node.over_estimate_ms().map(duration).unwrap_or_default(),
+        );
+    }
+    periods_table(&mut out, "Days", &report.days);
+    periods_table(&mut out, "Weeks", &report.weeks);
+    out
+}
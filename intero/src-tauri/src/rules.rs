@@ -0,0 +1,360 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::foreground::ForegroundInfo;
+use crate::url::url_host;
+use crate::verdict::{Category, ParseKind, Verdict};
+
+/// Looked for in this order in the rules directory.
+const FILE_NAMES: &[&str] = &["rules.toml", "rules.json"];
+
+const EXAMPLE: &str = r#"# Rules decide a verdict from context, without sending the screen to the model.
+# The first rule whose conditions all hold wins; within a list, any entry matches.
+# This file is reloaded whenever it changes.
+#
+# [[rule]]
+# name = "Editors"
+# category = "work"
+# app_id = ["com.microsoft.VSCode", "Code"]
+#
+# [[rule]]
+# name = "Video sites"
+# category = "distraction"
+# url_host = ["youtube.com", "twitch.tv"]
+#
+# [[rule]]
+# name = "Pull requests"
+# category = "work"
+# title = "(?i)pull request"
+#
+# [[rule]]
+# name = "Reading for research tasks"
+# category = "work"
+# app_id = ["com.apple.Safari"]
+# task_type = ["problem"]
+# hours = "09:00-18:00"
+"#;
+
+/// One user rule. Conditions that are left out always hold, but a rule needs at least one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub category: Category,
+    /// Bundle ids or X11 classes, compared case-insensitively.
+    #[serde(default)]
+    pub app_id: Vec<String>,
+    /// A regular expression searched for in the window title.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Hosts of the active browser tab. Subdomains match too.
+    #[serde(default)]
+    pub url_host: Vec<String>,
+    /// Local time of day, e.g. "09:00-17:30". May wrap past midnight.
+    #[serde(default)]
+    pub hours: Option<Hours>,
+    /// The `type` of the task being worked on: task, goal, project or problem.
+    #[serde(default)]
+    pub task_type: Vec<String>,
+}
+
+impl Rule {
+    pub fn verdict(&self, foreground: Option<ForegroundInfo>) -> Verdict {
+        Verdict {
+            category: self.category,
+            confidence: Some(1.0),
+            description: String::new(),
+            reason: format!("Matched rule \"{}\"", self.name),
+            parse: ParseKind::Rule,
+            raw: String::new(),
+            model: "rules".to_string(),
+            latency_ms: 0,
+            foreground,
+            prompt_version: None,
+        }
+    }
+
+    fn has_conditions(&self) -> bool {
+        !self.app_id.is_empty()
+            || self.title.is_some()
+            || !self.url_host.is_empty()
+            || self.hours.is_some()
+            || !self.task_type.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Hours {
+    /// Minutes since midnight.
+    pub start: u32,
+    pub end: u32,
+}
+
+impl Hours {
+    pub fn contains(&self, minute_of_day: u32) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start || minute_of_day < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for Hours {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let minutes = |time: &str| -> Option<u32> {
+            let (hours, minutes) = time.trim().split_once(':')?;
+            let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+            // "24:00" ends a range at midnight; nothing later that day exists.
+            (hours < 24 && minutes < 60 || hours == 24 && minutes == 0).then_some(hours * 60 + minutes)
+        };
+        let (start, end) = value
+            .split_once('-')
+            .and_then(|(start, end)| Some((minutes(start)?, minutes(end)?)))
+            .ok_or_else(|| format!("expected hours like \"09:00-17:30\", got {:?}", value))?;
+        Ok(Hours { start, end })
+    }
+}
+
+impl From<Hours> for String {
+    fn from(hours: Hours) -> String {
+        format!(
+            "{:02}:{:02}-{:02}:{:02}",
+            hours.start / 60,
+            hours.start % 60,
+            hours.end / 60,
+            hours.end % 60
+        )
+    }
+}
+
+/// What rules are matched against.
+#[derive(Debug, Clone, Default)]
+pub struct RuleContext<'a> {
+    pub foreground: Option<&'a ForegroundInfo>,
+    /// Local time, in minutes since midnight.
+    pub minute_of_day: u32,
+    pub task_type: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulesError {
+    pub message: String,
+}
+
+impl fmt::Display for RulesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for RulesError {}
+
+fn rules_error(message: impl ToString) -> RulesError {
+    RulesError {
+        message: message.to_string(),
+    }
+}
+
+#[derive(Deserialize)]
+struct RulesFile {
+    #[serde(default, alias = "rules")]
+    rule: Vec<Rule>,
+}
+
+/// A checked, ordered list of rules.
+#[derive(Debug, Default)]
+pub struct Rules {
+    rules: Vec<(Rule, Option<Regex>)>,
+}
+
+impl Rules {
+    pub fn from_toml(text: &str) -> Result<Self, RulesError> {
+        let file: RulesFile = toml::from_str(text).map_err(rules_error)?;
+        Rules::new(file.rule)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, RulesError> {
+        let file: RulesFile = serde_json::from_str(text).map_err(rules_error)?;
+        Rules::new(file.rule)
+    }
+
+    pub fn new(rules: Vec<Rule>) -> Result<Self, RulesError> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| {
+                if !rule.has_conditions() {
+                    return Err(rules_error(format!("rule \"{}\" has no conditions", rule.name)));
+                }
+                let title = match &rule.title {
+                    Some(title) => Some(
+                        Regex::new(title)
+                            .map_err(|err| rules_error(format!("rule \"{}\": {}", rule.name, err)))?,
+                    ),
+                    None => None,
+                };
+                Ok((rule, title))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Rules { rules })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The first rule that matches, if any.
+    pub fn evaluate(&self, context: &RuleContext) -> Option<&Rule> {
+        self.rules
+            .iter()
+            .find(|(rule, title)| matches(rule, title.as_ref(), context))
+            .map(|(rule, _)| rule)
+    }
+}
+
+fn matches(rule: &Rule, title: Option<&Regex>, context: &RuleContext) -> bool {
+    let foreground = context.foreground;
+    if !rule.app_id.is_empty() {
+        let Some(app_id) = foreground.map(|f| &f.app_id) else {
+            return false;
+        };
+        if !rule.app_id.iter().any(|id| id.eq_ignore_ascii_case(app_id)) {
+            return false;
+        }
+    }
+    if let Some(title) = title {
+        match foreground.and_then(|f| f.title.as_deref()) {
+            Some(window_title) if title.is_match(window_title) => {}
+            _ => return false,
+        }
+    }
+    if !rule.url_host.is_empty() {
+        let Some(host) = foreground.and_then(|f| f.url.as_deref()).and_then(url_host) else {
+            return false;
+        };
+        if !rule.url_host.iter().any(|rule_host| host_matches(host, rule_host)) {
+            return false;
+        }
+    }
+    if let Some(hours) = rule.hours {
+        if !hours.contains(context.minute_of_day) {
+            return false;
+        }
+    }
+    if !rule.task_type.is_empty() {
+        let Some(task_type) = context.task_type else {
+            return false;
+        };
+        if !rule.task_type.iter().any(|t| t.eq_ignore_ascii_case(task_type)) {
+            return false;
+        }
+    }
+    true
+}
+
+fn host_matches(host: &str, rule_host: &str) -> bool {
+    let host = host.trim_start_matches("www.");
+    let rule_host = rule_host.trim_start_matches("www.");
+    host.eq_ignore_ascii_case(rule_host)
+        || host.len() > rule_host.len()
+            && host.as_bytes()[host.len() - rule_host.len() - 1] == b'.'
+            && host[host.len() - rule_host.len()..].eq_ignore_ascii_case(rule_host)
+}
+
+/// Returned by the `rules_status` command and emitted as `rules-reloaded`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RulesStatus {
+    pub path: Option<PathBuf>,
+    pub rules: usize,
+    /// Why the file could not be loaded. The previous rules stay in effect.
+    pub error: Option<String>,
+}
+
+/// The rules file in a directory, reloaded when it changes.
+pub struct RulesWatcher {
+    dir: PathBuf,
+    loaded: Option<(PathBuf, Option<SystemTime>)>,
+    rules: Rules,
+    error: Option<String>,
+}
+
+impl RulesWatcher {
+    /// Writes a commented example file if there is no rules file yet.
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        if FILE_NAMES.iter().all(|name| !dir.join(name).exists()) {
+            let result = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(FILE_NAMES[0]), EXAMPLE));
+            if let Err(err) = result {
+                println!("[error]: could not write an example rules file: {}", err);
+            }
+        }
+        let mut watcher = RulesWatcher {
+            dir,
+            loaded: None,
+            rules: Rules::default(),
+            error: None,
+        };
+        watcher.reload_if_changed();
+        watcher
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn status(&self) -> RulesStatus {
+        RulesStatus {
+            path: self.loaded.as_ref().map(|(path, _)| path.clone()),
+            rules: self.rules.len(),
+            error: self.error.clone(),
+        }
+    }
+
+    /// Returns whether the file changed since the last call.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let path = FILE_NAMES.iter().map(|name| self.dir.join(name)).find(|path| path.exists());
+        let current = path.map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        });
+        if current == self.loaded {
+            return false;
+        }
+        match current.as_ref().map(|(path, _)| load(path)) {
+            Some(Ok(rules)) => {
+                self.rules = rules;
+                self.error = None;
+            }
+            Some(Err(err)) => {
+                println!("[error]: could not load rules: {}", err);
+                self.error = Some(err.to_string());
+            }
+            None => {
+                self.rules = Rules::default();
+                self.error = None;
+            }
+        }
+        self.loaded = current;
+        true
+    }
+}
+
+fn load(path: &Path) -> Result<Rules, RulesError> {
+    let text = fs::read_to_string(path).map_err(rules_error)?;
+    if path.extension().is_some_and(|ext| ext == "json") {
+        Rules::from_json(&text)
+    } else {
+        Rules::from_toml(&text)
+    }
+}
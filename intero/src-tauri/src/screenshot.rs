@@ -1,15 +1,142 @@
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use base64::prelude::*;
-use crate::ffi; // Add this import
+use serde::{Deserialize, Serialize};
+
+use crate::change_detector::{ChangeDetector, ChangeDetectorConfig};
+use crate::encoding::{self, EncodeOptions, Encoding};
+use crate::frame_source::{CaptureError, CapturedFrame, FrameSource, Rect, ScreenRect};
+
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScreenshotOptions {
+    #[serde(flatten)]
+    pub encode: EncodeOptions,
+    /// Only keep this part of the screen, in screen coordinates.
+    pub crop: Option<ScreenRect>,
+    /// Fail unless the frame comes from this display.
+    pub display_id: Option<u32>,
+    /// Fail with `StaleFrame` if the latest frame is older than this.
+    /// ScreenCaptureKit only delivers frames when the screen changes, so leave unset
+    /// unless the caller really needs a fresh frame.
+    pub max_age_ms: Option<u64>,
+}
+
+/// An encoded frame as sent to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct Frame {
+    /// When the frame was captured, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Increases with every new frame from the capture source.
+    pub sequence: u64,
+    pub display_id: u32,
+    /// Image pixels per screen point.
+    pub scale_factor: f64,
+    /// The part of the screen the image shows, in screen coordinates.
+    pub content_rect: ScreenRect,
+    /// Size of the encoded image.
+    pub width: u32,
+    pub height: u32,
+    pub encoding: Encoding,
+    /// The encoded image, base64 in JSON.
+    #[serde(serialize_with = "serialize_base64")]
+    pub bytes: Vec<u8>,
+}
+
+pub(crate) fn serialize_base64<S: serde::Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64_STANDARD.encode(bytes))
+}
+
+pub async fn capture(source: &dyn FrameSource, options: &ScreenshotOptions) -> Result<Frame, CaptureError> {
+    let mut frame = source.latest_frame()?;
+    if let Some(max_age_ms) = options.max_age_ms {
+        let age_ms = frame.timestamp.elapsed().unwrap_or_default().as_millis() as u64;
+        if age_ms > max_age_ms {
+            return Err(CaptureError::StaleFrame { age_ms });
+        }
+    }
+    if let Some(display_id) = options.display_id {
+        if display_id != frame.display_id {
+            return Err(CaptureError::failed(format!(
+                "Display {} is not being captured (capturing {})",
+                display_id, frame.display_id
+            )));
+        }
+    }
+    if let Some(crop) = options.crop {
+        let rect = frame
+            .screen_to_pixels(crop)
+            .ok_or_else(|| CaptureError::failed("Crop rect is outside the captured screen"))?;
+        frame = frame.cropped(rect);
+    }
+    let image = encoding::scaled_rgba(&frame, options.encode.max_width, options.encode.max_height);
+    let bytes = encoding::encode(&image, options.encode.encoding).map_err(CaptureError::failed)?;
+    Ok(Frame {
+        timestamp: millis_since_epoch(frame.timestamp),
+        sequence: frame.sequence,
+        display_id: frame.display_id,
+        scale_factor: image.width() as f64 / frame.screen_rect.width,
+        content_rect: frame.screen_rect,
+        width: image.width(),
+        height: image.height(),
+        encoding: options.encode.encoding,
+        bytes,
+    })
+}
+
+pub fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
 
+/// Payload of the `frame-changed` event. Carries no image: fetch the frame by `frame_id`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameChanged {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub width: u32,
+    pub height: u32,
+    pub score: f32,
+    pub changed_pixels: u32,
+    pub regions: Vec<Rect>,
+    /// Id in the frame history, once stored there.
+    pub frame_id: Option<u64>,
+}
 
-pub async fn capture() -> Vec<String> {
-    let data = unsafe { ffi::get_last_frame() };
-    if let Some(data) = data {
-        let base64_string = BASE64_STANDARD.encode(data);
-        return vec![base64_string];
-    } else {
-        println!("No data");
-        // Handle the None case or return an empty Vec or error
-        return vec![];
+/// Polls `source` every `interval()` and calls `on_change` with the raw frame and the
+/// event for each frame that differs enough from the last reported one. `config` and
+/// `interval` are re-read on every poll so they can be tuned while running. Runs until `on_change`
+/// returns false.
+pub async fn watch_changes(
+    source: &dyn FrameSource,
+    config: &Mutex<ChangeDetectorConfig>,
+    interval: impl Fn() -> Duration,
+    mut on_change: impl FnMut(&CapturedFrame, FrameChanged) -> bool,
+) {
+    let mut detector = ChangeDetector::new(*config.lock().unwrap());
+    loop {
+        tokio::time::sleep(interval()).await;
+        detector.config = *config.lock().unwrap();
+        let Ok(frame) = source.latest_frame() else {
+            continue;
+        };
+        let diff = detector.observe(frame.to_rgba());
+        if !diff.changed {
+            continue;
+        }
+        let event = FrameChanged {
+            timestamp: millis_since_epoch(frame.timestamp),
+            width: frame.width,
+            height: frame.height,
+            score: diff.score,
+            changed_pixels: diff.changed_pixels,
+            regions: diff.regions,
+            frame_id: None,
+        };
+        if !on_change(&frame, event) {
+            return;
+        }
     }
-}
\ No newline at end of file
+}
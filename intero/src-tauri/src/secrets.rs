@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+
+/// The credentials the backend knows how to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretName {
+    /// Used for classification and text to speech.
+    OpenAi,
+}
+
+impl SecretName {
+    pub const ALL: [SecretName; 1] = [SecretName::OpenAi];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SecretName::OpenAi => "openai",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SecretsError {
+    Io { message: String },
+    /// The store exists but cannot be decrypted or parsed.
+    Corrupt { message: String },
+    Keychain { message: String },
+}
+
+impl fmt::Display for SecretsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretsError::Io { message } => write!(f, "could not access the secrets file: {}", message),
+            SecretsError::Corrupt { message } => write!(f, "could not read the secrets file: {}", message),
+            SecretsError::Keychain { message } => write!(f, "keychain error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SecretsError {}
+
+impl From<std::io::Error> for SecretsError {
+    fn from(err: std::io::Error) -> Self {
+        SecretsError::Io {
+            message: err.to_string(),
+        }
+    }
+}
+
+fn corrupt(message: impl ToString) -> SecretsError {
+    SecretsError::Corrupt {
+        message: message.to_string(),
+    }
+}
+
+pub trait SecretStore: Send + Sync {
+    fn get(&self, name: &str) -> Result<Option<String>, SecretsError>;
+    fn set(&self, name: &str, value: &str) -> Result<(), SecretsError>;
+    /// Returns whether there was a value to remove.
+    fn remove(&self, name: &str) -> Result<bool, SecretsError>;
+    /// Where secrets are kept, for the settings screen.
+    fn backend(&self) -> &'static str;
+}
+
+/// Whether each secret is set. Values never leave the backend.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SecretsStatus {
+    pub backend: &'static str,
+    pub set: Vec<SecretName>,
+}
+
+/// The credential store used by the app.
+pub struct Secrets {
+    store: Box<dyn SecretStore>,
+}
+
+impl Secrets {
+    pub fn new(store: Box<dyn SecretStore>) -> Self {
+        Secrets { store }
+    }
+
+    /// The keychain on macOS, otherwise an encrypted file in `dir`.
+    /// `INTERO_SECRETS=file` forces the file.
+    pub fn open(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        if std::env::var("INTERO_SECRETS").is_ok_and(|backend| backend == "file") {
+            return Secrets::new(Box::new(FileStore::new(dir)));
+        }
+        #[cfg(target_os = "macos")]
+        let store: Box<dyn SecretStore> = Box::new(KeychainStore::new("industries.strange.clippy"));
+        #[cfg(not(target_os = "macos"))]
+        let store: Box<dyn SecretStore> = Box::new(FileStore::new(dir));
+        Secrets::new(store)
+    }
+
+    /// Logs and returns `None` if the store cannot be read.
+    pub fn get(&self, name: SecretName) -> Option<String> {
+        self.store.get(name.as_str()).unwrap_or_else(|err| {
+            println!("[error]: could not read the {} secret: {}", name.as_str(), err);
+            None
+        })
+    }
+
+    pub fn set(&self, name: SecretName, value: &str) -> Result<(), SecretsError> {
+        self.store.set(name.as_str(), value)
+    }
+
+    pub fn clear(&self, name: SecretName) -> Result<bool, SecretsError> {
+        self.store.remove(name.as_str())
+    }
+
+    pub fn status(&self) -> Result<SecretsStatus, SecretsError> {
+        let mut set = vec![];
+        for name in SecretName::ALL {
+            if self.store.get(name.as_str())?.is_some() {
+                set.push(name);
+            }
+        }
+        Ok(SecretsStatus {
+            backend: self.store.backend(),
+            set,
+        })
+    }
+}
+
+// Encrypted file
+
+const NONCE_LEN: usize = 12;
+
+/// Secrets as ChaCha20-Poly1305 encrypted JSON in `secrets.bin`, with the key in
+/// `secrets.key`. Both are only readable by the user. This keeps keys out of backups
+/// and sync tools that pick up single files, not from someone who can read the whole directory.
+pub struct FileStore {
+    dir: PathBuf,
+    /// Serializes read-modify-write cycles.
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileStore {
+            dir: dir.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn data_path(&self) -> PathBuf {
+        self.dir.join("secrets.bin")
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join("secrets.key")
+    }
+
+    fn cipher(&self, create: bool) -> Result<Option<ChaCha20Poly1305>, SecretsError> {
+        let path = self.key_path();
+        let key = match fs::read(&path) {
+            Ok(bytes) if bytes.len() == 32 => *Key::from_slice(&bytes),
+            Ok(_) => return Err(corrupt("the key file has the wrong length")),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && create => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                write_private(&path, &key)?;
+                key
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(ChaCha20Poly1305::new(&key)))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>, SecretsError> {
+        let data = match fs::read(self.data_path()) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let cipher = self.cipher(false)?.ok_or_else(|| corrupt("the key file is missing"))?;
+        if data.len() < NONCE_LEN {
+            return Err(corrupt("the file is truncated"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| corrupt("the file does not match its key"))?;
+        serde_json::from_slice(&plaintext).map_err(corrupt)
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), SecretsError> {
+        fs::create_dir_all(&self.dir)?;
+        let cipher = self.cipher(true)?.expect("created above");
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let plaintext = serde_json::to_vec(secrets).map_err(corrupt)?;
+        let ciphertext = cipher.encrypt(&nonce, plaintext.as_slice()).map_err(|_| corrupt("encryption failed"))?;
+        let temporary = self.dir.join("secrets.bin.tmp");
+        write_private(&temporary, &[nonce.as_slice(), &ciphertext].concat())?;
+        fs::rename(temporary, self.data_path())?;
+        Ok(())
+    }
+}
+
+fn write_private(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(bytes)
+}
+
+impl SecretStore for FileStore {
+    fn get(&self, name: &str) -> Result<Option<String>, SecretsError> {
+        let _lock = self.lock.lock().unwrap();
+        Ok(self.load()?.remove(name))
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<(), SecretsError> {
+        let _lock = self.lock.lock().unwrap();
+        let mut secrets = self.load()?;
+        secrets.insert(name.to_string(), value.to_string());
+        self.save(&secrets)
+    }
+
+    fn remove(&self, name: &str) -> Result<bool, SecretsError> {
+        let _lock = self.lock.lock().unwrap();
+        let mut secrets = self.load()?;
+        if secrets.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&secrets)?;
+        Ok(true)
+    }
+
+    fn backend(&self) -> &'static str {
+        "file"
+    }
+}
+
+// Keychain
+
+/// Generic passwords in the login keychain, one per secret.
+#[cfg(target_os = "macos")]
+pub struct KeychainStore {
+    service: String,
+}
+
+#[cfg(target_os = "macos")]
+impl KeychainStore {
+    pub fn new(service: impl Into<String>) -> Self {
+        KeychainStore {
+            service: service.into(),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+
+#[cfg(target_os = "macos")]
+fn keychain_error(err: security_framework::base::Error) -> SecretsError {
+    SecretsError::Keychain {
+        message: err.to_string(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl SecretStore for KeychainStore {
+    fn get(&self, name: &str) -> Result<Option<String>, SecretsError> {
+        match security_framework::passwords::get_generic_password(&self.service, name) {
+            Ok(bytes) => String::from_utf8(bytes).map(Some).map_err(corrupt),
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => Ok(None),
+            Err(err) => Err(keychain_error(err)),
+        }
+    }
+
+    fn set(&self, name: &str, value: &str) -> Result<(), SecretsError> {
+        security_framework::passwords::set_generic_password(&self.service, name, value.as_bytes())
+            .map_err(keychain_error)
+    }
+
+    fn remove(&self, name: &str) -> Result<bool, SecretsError> {
+        match security_framework::passwords::delete_generic_password(&self.service, name) {
+            Ok(()) => Ok(true),
+            Err(err) if err.code() == ERR_SEC_ITEM_NOT_FOUND => Ok(false),
+            Err(err) => Err(keychain_error(err)),
+        }
+    }
+
+    fn backend(&self) -> &'static str {
+        "keychain"
+    }
+}
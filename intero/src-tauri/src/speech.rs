@@ -0,0 +1,91 @@
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::screenshot::serialize_base64;
+
+/// Text to speech with OpenAI's `audio/speech` endpoint.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeechConfig {
+    pub base_url: String,
+    pub model: String,
+    pub voice: String,
+}
+
+impl Default for SpeechConfig {
+    fn default() -> Self {
+        SpeechConfig {
+            base_url: "https://api.openai.com/v1".to_string(),
+            model: "tts-1".to_string(),
+            voice: "nova".to_string(),
+        }
+    }
+}
+
+/// Returned by the `speak` command.
+#[derive(Debug, Clone, Serialize)]
+pub struct Speech {
+    pub mime_type: &'static str,
+    /// Base64 in JSON.
+    #[serde(serialize_with = "serialize_base64")]
+    pub audio: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SpeechError {
+    /// No OpenAI key has been stored.
+    MissingKey,
+    Http { message: String },
+    Status { status: u16, body: String },
+}
+
+impl fmt::Display for SpeechError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpeechError::MissingKey => write!(f, "no OpenAI API key is set"),
+            SpeechError::Http { message } => write!(f, "request failed: {}", message),
+            SpeechError::Status { status, body } => write!(f, "backend returned {}: {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for SpeechError {}
+
+impl From<reqwest::Error> for SpeechError {
+    fn from(err: reqwest::Error) -> Self {
+        SpeechError::Http {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Reads `text` aloud as MP3.
+pub async fn speak(config: &SpeechConfig, api_key: Option<&str>, text: &str) -> Result<Speech, SpeechError> {
+    let api_key = api_key.ok_or(SpeechError::MissingKey)?;
+    let response = reqwest::Client::new()
+        .post(format!("{}/audio/speech", config.base_url.trim_end_matches('/')))
+        .timeout(Duration::from_secs(60))
+        .bearer_auth(api_key)
+        .json(&json!({
+            "model": config.model,
+            "voice": config.voice,
+            "input": text,
+            "response_format": "mp3",
+        }))
+        .send()
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(SpeechError::Status {
+            status: status.as_u16(),
+            body: response.text().await.unwrap_or_default(),
+        });
+    }
+    Ok(Speech {
+        mime_type: "audio/mpeg",
+        audio: response.bytes().await?.to_vec(),
+    })
+}
@@ -0,0 +1,471 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::db::{Graph, Relation};
+
+// Task graph ordering, ported from `Toposorter` in `ToposorterState.tsx`.
+
+/// `[pinned, status points, priority points, createdAt ms]`. Higher is better.
+pub type ScoreVector = [f64; 4];
+
+pub fn status_points(status: Option<&str>) -> f64 {
+    match status {
+        Some("done") => -1.0,
+        Some("active") => 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Priority 1 is the most urgent; unset counts as 3.
+pub fn priority_points(priority: Option<f64>) -> f64 {
+    -priority.unwrap_or(3.0)
+}
+
+/// `createdAt` is an ISO string as written by `JSON.stringify(new Date())`, or ms.
+pub fn created_at_millis(data: &Value) -> Option<i64> {
+    match data.get("createdAt")? {
+        Value::String(text) => chrono::DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.timestamp_millis()),
+        Value::Number(number) => number.as_f64().map(|ms| ms as i64),
+        _ => None,
+    }
+}
+
+pub fn score_vector(data: &Value) -> ScoreVector {
+    [
+        if data.get("pinned") == Some(&Value::Bool(true)) { 1.0 } else { 0.0 },
+        status_points(data.get("status").and_then(Value::as_str)),
+        priority_points(data.get("priority").and_then(Value::as_f64)),
+        created_at_millis(data).unwrap_or(0) as f64,
+    ]
+}
+
+/// Sort order for score vectors: the larger vector first. `Less` means `a` ranks higher.
+pub fn compare_vecs(a: &ScoreVector, b: &ScoreVector) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        if a < b {
+            return Ordering::Greater;
+        }
+        if a > b {
+            return Ordering::Less;
+        }
+    }
+    Ordering::Equal
+}
+
+/// The larger of two vectors, preferring `a` on ties.
+pub fn choose_max_vec(a: ScoreVector, b: ScoreVector) -> ScoreVector {
+    if compare_vecs(&a, &b) == Ordering::Greater {
+        b
+    } else {
+        a
+    }
+}
+
+/// Children of every node: the legacy `children` list, then `depends` relations.
+pub fn children(graph: &Graph) -> HashMap<&str, Vec<&str>> {
+    let mut out: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (id, data) in &graph.nodes {
+        let list = out.entry(id.as_str()).or_default();
+        for child in data.get("children").and_then(Value::as_array).into_iter().flatten() {
+            if let Some(child) = child.as_str() {
+                if !list.contains(&child) {
+                    list.push(child);
+                }
+            }
+        }
+    }
+    for relation in graph.relations.get("depends").into_iter().flatten() {
+        let list = out.entry(relation.parent.as_str()).or_default();
+        if !list.contains(&relation.child.as_str()) {
+            list.push(relation.child.as_str());
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SortedNode {
+    pub id: String,
+    pub data: Value,
+    /// The best score among the node and its descendants.
+    pub max_vec: ScoreVector,
+    pub parents: Vec<String>,
+    pub children: Vec<String>,
+}
+
+/// Returned by the `graph_sorted` command.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SortedGraph {
+    pub nodes: Vec<SortedNode>,
+    /// `(parent, child)`, see `order_edges`.
+    pub edges: Vec<(String, String)>,
+}
+
+/// Nodes with children before parents, in the order the DFS finishes them.
+///
+/// Children that do not exist are ignored. `link` refuses cycles, but older data can
+/// still have them (see `cycles`): an edge back to a node that is still being visited
+/// is skipped rather than followed forever.
+pub fn toposort(graph: &Graph) -> Vec<SortedNode> {
+    struct Visit<'a> {
+        graph: &'a Graph,
+        children: HashMap<&'a str, Vec<&'a str>>,
+        on_stack: HashSet<&'a str>,
+        index: HashMap<&'a str, usize>,
+        out: Vec<SortedNode>,
+    }
+
+    impl<'a> Visit<'a> {
+        fn visit(&mut self, id: &'a str) {
+            if self.index.contains_key(id) || self.on_stack.contains(id) {
+                return;
+            }
+            let Some(data) = self.graph.nodes.get(id) else {
+                return;
+            };
+            self.on_stack.insert(id);
+            let mut max_vec = score_vector(data);
+            let mut children = vec![];
+            for child in self.children.get(id).cloned().unwrap_or_default() {
+                self.visit(child);
+                if let Some(&i) = self.index.get(child) {
+                    max_vec = choose_max_vec(max_vec, self.out[i].max_vec);
+                    self.out[i].parents.push(id.to_string());
+                    children.push(child.to_string());
+                }
+            }
+            self.on_stack.remove(id);
+            self.index.insert(id, self.out.len());
+            self.out.push(SortedNode {
+                id: id.to_string(),
+                data: data.clone(),
+                max_vec,
+                parents: vec![],
+                children,
+            });
+        }
+    }
+
+    let mut visit = Visit {
+        graph,
+        children: children(graph),
+        on_stack: HashSet::new(),
+        index: HashMap::new(),
+        out: vec![],
+    };
+    for id in graph.nodes.keys() {
+        visit.visit(id);
+    }
+    visit.out
+}
+
+/// Ranks nodes best first by `max_vec`. The sort is stable, so ties keep DFS order.
+pub fn order_nodes(mut nodes: Vec<SortedNode>) -> Vec<SortedNode> {
+    nodes.sort_by(|a, b| compare_vecs(&a.max_vec, &b.max_vec));
+    nodes
+}
+
+/// Edges grouped by parent in node order, each parent's children in node order.
+pub fn order_edges(nodes: &[SortedNode]) -> Vec<(String, String)> {
+    let index: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, node)| (node.id.as_str(), i)).collect();
+    let mut edges = vec![];
+    for node in nodes {
+        let mut children: Vec<&String> = node.children.iter().filter(|c| index.contains_key(c.as_str())).collect();
+        children.sort_by_key(|c| index[c.as_str()]);
+        edges.extend(children.into_iter().map(|child| (node.id.clone(), child.clone())));
+    }
+    edges
+}
+
+pub fn sorted(graph: &Graph) -> SortedGraph {
+    let nodes = order_nodes(toposort(graph));
+    let edges = order_edges(&nodes);
+    SortedGraph { nodes, edges }
+}
+
+// Cycles
+
+/// The shortest path from `from` to `to`, both included. If they are the same
+/// node, the shortest cycle through it.
+pub fn find_path(graph: &Graph, from: &str, to: &str) -> Option<Vec<String>> {
+    let children = children(graph);
+    let mut previous: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(id) = queue.pop_front() {
+        for &child in children.get(id).into_iter().flatten() {
+            if child == to {
+                let mut path = vec![to.to_string(), id.to_string()];
+                let mut current = id;
+                while let Some(&parent) = previous.get(current) {
+                    path.push(parent.to_string());
+                    current = parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if child != from && graph.nodes.contains_key(child) && !previous.contains_key(child) {
+                previous.insert(child, id);
+                queue.push_back(child);
+            }
+        }
+    }
+    None
+}
+
+/// A strongly connected group of nodes, found in data saved before links were checked.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cycle {
+    /// Every node in the group, sorted.
+    pub nodes: Vec<String>,
+    /// One cycle through the group, starting and ending at the same node. Removing
+    /// one of its edges is a step towards breaking the group up.
+    pub path: Vec<String>,
+}
+
+/// Every group of nodes that can reach each other, using Tarjan's algorithm.
+pub fn cycles(graph: &Graph) -> Vec<Cycle> {
+    struct Tarjan<'a> {
+        graph: &'a Graph,
+        children: HashMap<&'a str, Vec<&'a str>>,
+        index: HashMap<&'a str, usize>,
+        low: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: HashSet<&'a str>,
+        components: Vec<Vec<&'a str>>,
+    }
+
+    impl<'a> Tarjan<'a> {
+        fn connect(&mut self, id: &'a str) {
+            let index = self.index.len();
+            self.index.insert(id, index);
+            self.low.insert(id, index);
+            self.stack.push(id);
+            self.on_stack.insert(id);
+            for child in self.children.get(id).cloned().unwrap_or_default() {
+                if !self.graph.nodes.contains_key(child) {
+                    continue;
+                }
+                let reachable = if !self.index.contains_key(child) {
+                    self.connect(child);
+                    self.low[child]
+                } else if self.on_stack.contains(child) {
+                    self.index[child]
+                } else {
+                    continue;
+                };
+                let low = self.low.get_mut(id).expect("inserted above");
+                *low = (*low).min(reachable);
+            }
+            if self.low[id] == index {
+                let mut component = vec![];
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(member);
+                    component.push(member);
+                    if member == id {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        graph,
+        children: children(graph),
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: vec![],
+        on_stack: HashSet::new(),
+        components: vec![],
+    };
+    for id in graph.nodes.keys() {
+        if !tarjan.index.contains_key(id.as_str()) {
+            tarjan.connect(id);
+        }
+    }
+    let looped = |id: &str| tarjan.children.get(id).is_some_and(|c| c.contains(&id));
+    let mut cycles: Vec<Cycle> = tarjan
+        .components
+        .iter()
+        .filter(|component| component.len() > 1 || looped(component[0]))
+        .map(|component| {
+            let mut nodes: Vec<String> = component.iter().map(|id| id.to_string()).collect();
+            nodes.sort();
+            let path = find_path(graph, &nodes[0], &nodes[0]).expect("strongly connected");
+            Cycle { nodes, path }
+        })
+        .collect();
+    cycles.sort_by(|a, b| a.nodes.cmp(&b.nodes));
+    cycles
+}
+
+// Mutations, mirroring `ToposorterStateManager`
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Connection {
+    /// The new node becomes a parent of `from`.
+    Parent,
+    /// The new node becomes a child of `from`.
+    Child,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GraphError {
+    UnknownNode { id: String },
+    /// Not "active", "done" or "unset".
+    InvalidStatus { status: String },
+    /// The edge would close `path`, which starts and ends with the new parent.
+    Cycle { path: Vec<String> },
+    Storage { message: String },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::UnknownNode { id } => write!(f, "no node with id {}", id),
+            GraphError::InvalidStatus { status } => {
+                write!(f, "invalid status {}, expected \"active\", \"done\" or \"unset\"", status)
+            }
+            GraphError::Cycle { path } => write!(f, "this link would create a cycle: {}", path.join(" → ")),
+            GraphError::Storage { message } => write!(f, "could not save the graph: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl From<rusqlite::Error> for GraphError {
+    fn from(err: rusqlite::Error) -> Self {
+        GraphError::Storage {
+            message: err.to_string(),
+        }
+    }
+}
+
+fn node_mut<'a>(graph: &'a mut Graph, id: &str) -> Result<&'a mut serde_json::Map<String, Value>, GraphError> {
+    graph
+        .nodes
+        .get_mut(id)
+        .and_then(Value::as_object_mut)
+        .ok_or_else(|| GraphError::UnknownNode { id: id.to_string() })
+}
+
+/// Adds an empty node `id`, optionally connected to `from`.
+pub fn add(
+    graph: &mut Graph,
+    id: &str,
+    created_at: chrono::DateTime<chrono::Utc>,
+    from: Option<(&str, Connection)>,
+) -> Result<(), GraphError> {
+    if let Some((from, _)) = from {
+        node_mut(graph, from)?;
+    }
+    let created_at = created_at.to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    graph
+        .nodes
+        .insert(id.to_string(), json!({ "value": "", "createdAt": created_at }));
+    match from {
+        None => {}
+        Some((from, Connection::Child)) => {
+            link(graph, from, id)?;
+        }
+        Some((from, Connection::Parent)) => {
+            link(graph, id, from)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that an edge from `parent` to `child` would keep the graph acyclic.
+pub fn check_link(graph: &Graph, parent: &str, child: &str) -> Result<(), GraphError> {
+    for id in [parent, child] {
+        if !graph.nodes.contains_key(id) {
+            return Err(GraphError::UnknownNode { id: id.to_string() });
+        }
+    }
+    let back = if parent == child {
+        Some(vec![child.to_string()])
+    } else {
+        find_path(graph, child, parent)
+    };
+    match back {
+        Some(back) => Err(GraphError::Cycle {
+            path: std::iter::once(parent.to_string()).chain(back).collect(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every edge `after` has and `before` lacks keeps `after` acyclic, so a
+/// whole-graph replace cannot add what `link` would refuse. Cycles already in `before`
+/// are left for `cycles` to report.
+pub fn check_new_edges(before: &Graph, after: &Graph) -> Result<(), GraphError> {
+    let old = children(before);
+    let new = children(after);
+    for parent in after.nodes.keys() {
+        for &child in new.get(parent.as_str()).into_iter().flatten() {
+            let existed = old.get(parent.as_str()).is_some_and(|children| children.contains(&child));
+            if !existed && after.nodes.contains_key(child) {
+                check_link(after, parent, child)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds a `depends` relation. Returns false if the edge already existed, and fails
+/// with `GraphError::Cycle` if `child` already leads to `parent`.
+pub fn link(graph: &mut Graph, parent: &str, child: &str) -> Result<bool, GraphError> {
+    if children(graph).get(parent).is_some_and(|children| children.contains(&child)) {
+        return Ok(false);
+    }
+    check_link(graph, parent, child)?;
+    graph.relations.entry("depends".to_string()).or_default().push(Relation {
+        parent: parent.to_string(),
+        child: child.to_string(),
+    });
+    Ok(true)
+}
+
+/// Removes the edge, whether it is a `depends` relation or in a legacy `children` list.
+/// Returns whether there was one.
+pub fn unlink(graph: &mut Graph, parent: &str, child: &str) -> Result<bool, GraphError> {
+    let mut removed = false;
+    if let Some(relations) = graph.relations.get_mut("depends") {
+        let before = relations.len();
+        relations.retain(|r| !(r.parent == parent && r.child == child));
+        removed = relations.len() != before;
+    }
+    let node = node_mut(graph, parent)?;
+    if let Some(children) = node.get_mut("children").and_then(Value::as_array_mut) {
+        let before = children.len();
+        children.retain(|c| c.as_str() != Some(child));
+        removed |= children.len() != before;
+    }
+    Ok(removed)
+}
+
+/// `status` is "active", "done", or "unset" to clear it.
+pub fn set_status(graph: &mut Graph, id: &str, status: &str) -> Result<(), GraphError> {
+    if !matches!(status, "active" | "done" | "unset") {
+        return Err(GraphError::InvalidStatus {
+            status: status.to_string(),
+        });
+    }
+    let node = node_mut(graph, id)?;
+    if status == "unset" {
+        node.remove("status");
+    } else {
+        node.insert("status".to_string(), json!(status));
+    }
+    Ok(())
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::{self, ActivityRow, Db, Graph, Relation, UndoRow, UndoStack};
+use crate::graph_schema;
+use crate::toposort::{self, GraphError};
+
+/// One reversible change to the database.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Op {
+    /// `None` means the node does not exist.
+    Node {
+        id: String,
+        before: Option<Value>,
+        after: Option<Value>,
+    },
+    Link {
+        kind: String,
+        parent: String,
+        child: String,
+    },
+    Unlink {
+        kind: String,
+        parent: String,
+        child: String,
+    },
+    /// `row.id` is set, so the same row comes back on redo.
+    InsertActivity { row: ActivityRow },
+    DeleteActivity { row: ActivityRow },
+}
+
+impl Op {
+    pub fn inverse(&self) -> Op {
+        match self.clone() {
+            Op::Node { id, before, after } => Op::Node {
+                id,
+                before: after,
+                after: before,
+            },
+            Op::Link { kind, parent, child } => Op::Unlink { kind, parent, child },
+            Op::Unlink { kind, parent, child } => Op::Link { kind, parent, child },
+            Op::InsertActivity { row } => Op::DeleteActivity { row },
+            Op::DeleteActivity { row } => Op::InsertActivity { row },
+        }
+    }
+
+    /// For the `db-changed` event.
+    pub fn table(&self) -> &'static str {
+        match self {
+            Op::Node { .. } => "nodes",
+            Op::Link { .. } | Op::Unlink { .. } => "relations",
+            Op::InsertActivity { .. } | Op::DeleteActivity { .. } => "activity",
+        }
+    }
+}
+
+pub fn apply(db: &Db, op: &Op) -> db::Result<()> {
+    match op {
+        Op::Node { id, after: Some(data), .. } => db.put_node(id, data),
+        Op::Node { id, after: None, .. } => db.delete_node(id).map(drop),
+        Op::Link { kind, parent, child } => db.link(kind, parent, child),
+        Op::Unlink { kind, parent, child } => db.unlink(kind, parent, child).map(drop),
+        Op::InsertActivity { row } => db.restore_activity(row),
+        Op::DeleteActivity { row } => match row.id {
+            Some(id) => db.delete_activity(id).map(drop),
+            None => Ok(()),
+        },
+    }
+}
+
+/// Applies `change` to the stored graph and saves what it changed. Fails without
+/// saving if the result, once legacy `children` lists are migrated, has a cycle
+/// through an edge the stored graph does not have. Returns the ops to record.
+pub fn change_graph<T>(
+    db: &Db,
+    change: impl FnOnce(&mut Graph) -> Result<T, GraphError>,
+) -> Result<(T, Vec<Op>), GraphError> {
+    let before = db.graph()?;
+    let mut graph = before.clone();
+    let result = change(&mut graph)?;
+    graph_schema::migrate(&mut graph);
+    toposort::check_new_edges(&before, &graph)?;
+    let ops = diff(&before, &graph);
+    db.atomically(|db| ops.iter().try_for_each(|op| apply(db, op)))?;
+    Ok((result, ops))
+}
+
+/// The ops that turn `before` into `after`. New nodes come before the relations that
+/// need them and deleted nodes after, so the foreign keys hold in either direction.
+pub fn diff(before: &Graph, after: &Graph) -> Vec<Op> {
+    let mut ops = vec![];
+    for (id, data) in &after.nodes {
+        let old = before.nodes.get(id);
+        if old != Some(data) {
+            ops.push(Op::Node {
+                id: id.clone(),
+                before: old.cloned(),
+                after: Some(data.clone()),
+            });
+        }
+    }
+    let missing = |graph: &Graph, kind: &str, relation: &Relation| {
+        !graph.relations.get(kind).is_some_and(|relations| relations.contains(relation))
+    };
+    for (kind, relations) in &after.relations {
+        for relation in relations.iter().filter(|r| missing(before, kind, r)) {
+            ops.push(Op::Link {
+                kind: kind.clone(),
+                parent: relation.parent.clone(),
+                child: relation.child.clone(),
+            });
+        }
+    }
+    for (kind, relations) in &before.relations {
+        for relation in relations.iter().filter(|r| missing(after, kind, r)) {
+            ops.push(Op::Unlink {
+                kind: kind.clone(),
+                parent: relation.parent.clone(),
+                child: relation.child.clone(),
+            });
+        }
+    }
+    for (id, data) in &before.nodes {
+        if !after.nodes.contains_key(id) {
+            ops.push(Op::Node {
+                id: id.clone(),
+                before: Some(data.clone()),
+                after: None,
+            });
+        }
+    }
+    ops
+}
+
+/// Returned by the undo commands, so menus can say what would be undone.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UndoStatus {
+    pub undo: Option<String>,
+    pub redo: Option<String>,
+}
+
+/// How long a group stays open without `end_group`, e.g. after the window that began
+/// it reloaded.
+pub const GROUP_TIMEOUT_MS: i64 = 30_000;
+
+struct OpenGroup {
+    label: String,
+    started_at: i64,
+    /// `begin_group` calls not yet ended.
+    depth: usize,
+    /// The stored entry, once something has been recorded.
+    entry: Option<UndoRow>,
+}
+
+/// Undo and redo stacks kept in the database, so they survive restarts. Only the
+/// newest `limit` entries can be undone.
+pub struct UndoLog {
+    limit: usize,
+    /// Open groups by caller, usually a window label.
+    groups: HashMap<String, OpenGroup>,
+}
+
+impl UndoLog {
+    pub fn new(limit: usize) -> Self {
+        UndoLog {
+            limit,
+            groups: HashMap::new(),
+        }
+    }
+
+    /// Makes what `caller` records until the matching `end_group` one entry, undone in
+    /// one step. Groups nest; the outermost label is kept. Other callers are not affected.
+    pub fn begin_group(&mut self, caller: &str, label: &str, now: i64) {
+        self.expire(now);
+        let group = self.groups.entry(caller.to_string()).or_insert_with(|| OpenGroup {
+            label: label.to_string(),
+            started_at: now,
+            depth: 0,
+            entry: None,
+        });
+        group.depth += 1;
+    }
+
+    pub fn end_group(&mut self, caller: &str) {
+        if let Some(group) = self.groups.get_mut(caller) {
+            group.depth -= 1;
+            if group.depth == 0 {
+                self.groups.remove(caller);
+            }
+        }
+    }
+
+    /// Adds ops that have already been applied. Clears the redo stack.
+    ///
+    /// Inside a group the entry is stored with the first ops and extended after, so a
+    /// group that is never ended loses nothing. If other changes were recorded in
+    /// between, the group carries on in a new entry, keeping the stack in order.
+    pub fn record(&mut self, db: &Db, caller: &str, label: &str, ops: Vec<Op>, now: i64) -> db::Result<()> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        self.expire(now);
+        let Some(group) = self.groups.get_mut(caller) else {
+            let entry = UndoRow {
+                id: None,
+                label: label.to_string(),
+                ops,
+                created_at: now,
+            };
+            return db.push_undo(&entry, self.limit).map(drop);
+        };
+        let newest = db.undo_entry(UndoStack::Undo)?.and_then(|entry| entry.id);
+        match &mut group.entry {
+            Some(entry) if entry.id == newest => {
+                entry.ops.extend(ops);
+                db.update_undo_ops(entry.id.expect("stored"), &entry.ops)
+            }
+            _ => {
+                let mut entry = UndoRow {
+                    id: None,
+                    label: group.label.clone(),
+                    ops,
+                    created_at: now,
+                };
+                entry.id = Some(db.push_undo(&entry, self.limit)?);
+                group.entry = Some(entry);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reverts the newest entry, closing all open groups first. Returns the entry.
+    pub fn undo(&mut self, db: &Db) -> db::Result<Option<UndoRow>> {
+        self.groups.clear();
+        let Some(entry) = db.undo_entry(UndoStack::Undo)? else {
+            return Ok(None);
+        };
+        db.atomically(|db| {
+            for op in entry.ops.iter().rev() {
+                apply(db, &op.inverse())?;
+            }
+            db.move_undo_entry(entry.id.expect("stored"), UndoStack::Redo)
+        })?;
+        Ok(Some(entry))
+    }
+
+    /// Applies the most recently undone entry again.
+    pub fn redo(&mut self, db: &Db) -> db::Result<Option<UndoRow>> {
+        self.groups.clear();
+        let Some(entry) = db.undo_entry(UndoStack::Redo)? else {
+            return Ok(None);
+        };
+        db.atomically(|db| {
+            for op in &entry.ops {
+                apply(db, op)?;
+            }
+            db.move_undo_entry(entry.id.expect("stored"), UndoStack::Undo)
+        })?;
+        Ok(Some(entry))
+    }
+
+    pub fn status(&self, db: &Db) -> db::Result<UndoStatus> {
+        Ok(UndoStatus {
+            undo: db.undo_entry(UndoStack::Undo)?.map(|entry| entry.label),
+            redo: db.undo_entry(UndoStack::Redo)?.map(|entry| entry.label),
+        })
+    }
+
+    fn expire(&mut self, now: i64) {
+        self.groups.retain(|_, group| now - group.started_at < GROUP_TIMEOUT_MS);
+    }
+}
@@ -0,0 +1,275 @@
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::classifier::{Classification, Classifier, ClassifierError, Image};
+use crate::foreground::ForegroundInfo;
+use crate::prompt::Prompt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Work,
+    Distraction,
+    Unknown,
+}
+
+impl Category {
+    /// Matches a label as models tend to write it: any case, maybe with stray punctuation.
+    pub fn from_label(label: &str) -> Option<Category> {
+        let label = label.trim().trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        match label.as_str() {
+            "work" | "working" | "productive" | "productivity" => Some(Category::Work),
+            "distraction" | "distracted" | "distracting" => Some(Category::Distraction),
+            "unknown" | "unsure" | "uncertain" => Some(Category::Unknown),
+            _ => None,
+        }
+    }
+}
+
+/// How the answer was read. Only `Json`, `FencedJson` and `PartialJson` carry
+/// description and reason; `Refusal` and `Unrecognized` are always `Unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseKind {
+    Json,
+    /// JSON inside a markdown code block, or surrounded by prose.
+    FencedJson,
+    /// Truncated or malformed JSON that still had recognisable fields.
+    PartialJson,
+    /// Plain text containing one of the category labels.
+    Label,
+    /// The model declined to answer.
+    Refusal,
+    Unrecognized,
+    /// Decided by a user rule without asking the model; `reason` names the rule.
+    Rule,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verdict {
+    pub category: Category,
+    /// 0..1, only when the model reported one.
+    pub confidence: Option<f32>,
+    pub description: String,
+    pub reason: String,
+    pub parse: ParseKind,
+    /// The model's answer, unmodified.
+    pub raw: String,
+    pub model: String,
+    pub latency_ms: u64,
+    /// What was in front when the frame was captured.
+    #[serde(default)]
+    pub foreground: Option<ForegroundInfo>,
+    /// Version of the prompt template the model was asked with.
+    #[serde(default)]
+    pub prompt_version: Option<String>,
+}
+
+impl Verdict {
+    pub fn from_classification(classification: &Classification) -> Verdict {
+        let parsed = parse(&classification.text);
+        Verdict {
+            category: parsed.category,
+            confidence: parsed.confidence,
+            description: parsed.description,
+            reason: parsed.reason,
+            parse: parsed.kind,
+            raw: classification.text.clone(),
+            model: classification.model.clone(),
+            latency_ms: classification.latency_ms,
+            foreground: None,
+            prompt_version: None,
+        }
+    }
+}
+
+/// Classifies `image` and reads the answer. A content policy rejection from the
+/// backend becomes a `Refusal` verdict rather than an error.
+pub async fn classify(classifier: &dyn Classifier, image: &Image<'_>, prompt: &Prompt) -> Result<Verdict, ClassifierError> {
+    let started = Instant::now();
+    match classifier.classify(image, prompt).await {
+        Ok(classification) => Ok(Verdict {
+            prompt_version: Some(prompt.version.to_string()),
+            ..Verdict::from_classification(&classification)
+        }),
+        Err(ClassifierError::Status { status: 400, body }) if body.contains("content_policy") => {
+            let refusal = unknown(ParseKind::Refusal);
+            Ok(Verdict {
+                category: refusal.category,
+                confidence: None,
+                description: refusal.description,
+                reason: refusal.reason,
+                parse: refusal.kind,
+                raw: body,
+                model: classifier.model(),
+                latency_ms: started.elapsed().as_millis() as u64,
+                foreground: None,
+                prompt_version: Some(prompt.version.to_string()),
+            })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parsed {
+    pub category: Category,
+    pub confidence: Option<f32>,
+    pub description: String,
+    pub reason: String,
+    pub kind: ParseKind,
+}
+
+const REFUSALS: &[&str] = &[
+    "i'm sorry",
+    "i am sorry",
+    "i cannot",
+    "i can't",
+    "i can not",
+    "i'm unable",
+    "i am unable",
+    "i won't",
+    "cannot assist",
+    "can't assist",
+    "can't help with",
+];
+
+/// Reads a classifier answer, trying strict JSON, then JSON in a code block or
+/// among prose, then partial JSON, then a refusal, then a bare label.
+pub fn parse(text: &str) -> Parsed {
+    let text = text.trim();
+    if let Ok(Value::Object(object)) = serde_json::from_str(text) {
+        return from_object(&object, ParseKind::Json);
+    }
+    let body = strip_fence(text).unwrap_or(text);
+    if let Some(Value::Object(object)) = outermost_braces(body).and_then(|json| serde_json::from_str(json).ok()) {
+        return from_object(&object, ParseKind::FencedJson);
+    }
+    if let Some(start) = body.find('{') {
+        if let Some(parsed) = from_partial(&body[start..]) {
+            return parsed;
+        }
+    }
+    let lower = text.to_lowercase();
+    if REFUSALS.iter().any(|refusal| lower.contains(refusal)) {
+        return unknown(ParseKind::Refusal);
+    }
+    match first_label(&lower) {
+        Some(category) => Parsed {
+            category,
+            confidence: None,
+            description: String::new(),
+            reason: String::new(),
+            kind: ParseKind::Label,
+        },
+        None => unknown(ParseKind::Unrecognized),
+    }
+}
+
+fn unknown(kind: ParseKind) -> Parsed {
+    Parsed {
+        category: Category::Unknown,
+        confidence: None,
+        description: String::new(),
+        reason: String::new(),
+        kind,
+    }
+}
+
+fn from_object(object: &Map<String, Value>, kind: ParseKind) -> Parsed {
+    let text = |key: &str| object.get(key).and_then(Value::as_str).unwrap_or_default().to_string();
+    let label = object.get("activity").or_else(|| object.get("category")).and_then(Value::as_str);
+    Parsed {
+        category: label.and_then(Category::from_label).unwrap_or(Category::Unknown),
+        confidence: object.get("confidence").and_then(Value::as_f64).map(normalize_confidence),
+        description: text("description"),
+        reason: text("reason"),
+        kind,
+    }
+}
+
+/// Pulls whatever string fields made it into a truncated JSON object.
+fn from_partial(json: &str) -> Option<Parsed> {
+    let label = partial_string(json, "activity").or_else(|| partial_string(json, "category"));
+    let description = partial_string(json, "description");
+    let reason = partial_string(json, "reason");
+    if label.is_none() && description.is_none() && reason.is_none() {
+        return None;
+    }
+    Some(Parsed {
+        category: label.as_deref().and_then(Category::from_label).unwrap_or(Category::Unknown),
+        confidence: partial_number(json, "confidence").map(normalize_confidence),
+        description: description.unwrap_or_default(),
+        reason: reason.unwrap_or_default(),
+        kind: ParseKind::PartialJson,
+    })
+}
+
+/// Models report confidence as 0..1 or as a percentage.
+fn normalize_confidence(value: f64) -> f32 {
+    let value = if value > 1.0 { value / 100.0 } else { value };
+    value.clamp(0.0, 1.0) as f32
+}
+
+/// The contents of the first markdown code block, which may be unterminated.
+fn strip_fence(text: &str) -> Option<&str> {
+    let start = text.find("```")?;
+    let rest = &text[start + 3..];
+    // Skip the language tag.
+    let rest = rest.find('\n').map_or(rest, |newline| &rest[newline + 1..]);
+    Some(rest.find("```").map_or(rest, |end| &rest[..end]))
+}
+
+fn outermost_braces(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    (start < end).then(|| &text[start..=end])
+}
+
+/// The value of `"key": "..."`, up to the closing quote or the end of the text.
+fn partial_string(json: &str, key: &str) -> Option<String> {
+    let value = after_key(json, key)?.strip_prefix('"')?;
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => break,
+            },
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+fn partial_number(json: &str, key: &str) -> Option<f64> {
+    let value = after_key(json, key)?;
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(value.len());
+    value[..end].parse().ok()
+}
+
+/// The text after `"key":`, with leading whitespace removed.
+fn after_key<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let quoted = format!("\"{}\"", key);
+    let rest = &json[json.find(&quoted)? + quoted.len()..];
+    Some(rest.trim_start().strip_prefix(':')?.trim_start())
+}
+
+/// The category label that appears first in `text`, as a whole word. Only the exact
+/// labels count here; in prose, words like "productive" are too easily negated.
+fn first_label(text: &str) -> Option<Category> {
+    text.split(|c: char| !c.is_alphanumeric()).find_map(|word| match word {
+        "work" => Some(Category::Work),
+        "distraction" => Some(Category::Distraction),
+        "unknown" => Some(Category::Unknown),
+        _ => None,
+    })
+}
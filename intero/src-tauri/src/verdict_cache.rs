@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+
+use image::imageops::{self, FilterType};
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+
+use crate::foreground::ForegroundInfo;
+use crate::prompt::Prompt;
+use crate::url::url_host;
+use crate::verdict::Verdict;
+
+/// Difference hash of a 17×16 grayscale thumbnail: one bit per horizontally adjacent
+/// pixel pair, set when the left one is brighter. Small edits to the screen flip few bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerceptualHash([u64; 4]);
+
+impl PerceptualHash {
+    pub fn of_image(image: &GrayImage) -> Self {
+        let thumbnail = imageops::resize(image, 17, 16, FilterType::Triangle);
+        let mut bits = [0u64; 4];
+        for y in 0..16 {
+            for x in 0..16 {
+                if thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0] {
+                    let bit = (y * 16 + x) as usize;
+                    bits[bit / 64] |= 1 << (bit % 64);
+                }
+            }
+        }
+        PerceptualHash(bits)
+    }
+
+    /// Hashes an encoded image. `None` if it cannot be decoded.
+    pub fn of_encoded(bytes: &[u8]) -> Option<Self> {
+        let image = image::load_from_memory(bytes).ok()?;
+        Some(Self::of_image(&image.to_luma8()))
+    }
+
+    /// Number of differing bits, 0..=256.
+    pub fn distance(&self, other: &PerceptualHash) -> u32 {
+        self.0.iter().zip(other.0).map(|(a, b)| (a ^ b).count_ones()).sum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// How long a verdict can be reused.
+    pub ttl_ms: u64,
+    /// Frames whose hashes differ in at most this many bits count as the same screen.
+    pub max_distance: u32,
+    pub capacity: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl_ms: 10 * 60 * 1000,
+            max_distance: 8,
+            capacity: 256,
+        }
+    }
+}
+
+/// Returned by the `classifier_diagnostics` command.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub config: CacheConfig,
+}
+
+/// What a verdict depends on besides the pixels: the model, the prompt, which names
+/// the task, and what was in front, since the same screen can be work in one app or
+/// site and a distraction in another. Only the app and the URL's host count: titles
+/// and paths change with every tab or unread count while the screen stays the same.
+pub fn cache_key(model: &str, prompt: &Prompt, foreground: Option<&ForegroundInfo>) -> String {
+    let foreground = foreground.map(|info| (&info.app_id, info.url.as_deref().and_then(url_host)));
+    serde_json::json!([model, prompt.system, prompt.user, foreground]).to_string()
+}
+
+struct Entry {
+    hash: PerceptualHash,
+    key: String,
+    timestamp: u64,
+    verdict: Verdict,
+}
+
+/// Recent verdicts keyed by the perceptual hash of the frame they were made for, so
+/// returning to a screen the model has just seen does not ask it again. Entries are
+/// only reused under the same key, see `cache_key`. Times are ms since the Unix epoch.
+pub struct VerdictCache {
+    pub config: CacheConfig,
+    entries: VecDeque<Entry>,
+    hits: u64,
+    misses: u64,
+}
+
+impl VerdictCache {
+    pub fn new(config: CacheConfig) -> Self {
+        VerdictCache {
+            config,
+            entries: VecDeque::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The most recent verdict stored under `key` for a frame close to `hash`.
+    pub fn get(&mut self, hash: &PerceptualHash, key: &str, now: u64) -> Option<Verdict> {
+        self.expire(now);
+        let found = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key == key && entry.hash.distance(hash) <= self.config.max_distance);
+        match found {
+            Some(entry) => {
+                self.hits += 1;
+                Some(entry.verdict.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, hash: PerceptualHash, key: String, now: u64, verdict: Verdict) {
+        self.expire(now);
+        self.entries.push_back(Entry {
+            hash,
+            key,
+            timestamp: now,
+            verdict,
+        });
+        while self.entries.len() > self.config.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+            config: self.config,
+        }
+    }
+
+    fn expire(&mut self, now: u64) {
+        while self
+            .entries
+            .front()
+            .is_some_and(|entry| entry.timestamp + self.config.ttl_ms <= now)
+        {
+            self.entries.pop_front();
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::future::Future;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::time::{self, Instant};
+
+use crate::classifier::ClassifierError;
+use crate::verdict::Verdict;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    /// How often to grab and diff a frame.
+    pub capture_interval_ms: u64,
+    /// How long the screen has to stay unchanged before the latest changed frame is
+    /// classified, so a burst of changes (scrolling, switching windows) costs one request.
+    pub debounce_ms: u64,
+    /// The longest a change waits for the screen to settle, so a screen that never stops
+    /// changing (a video, a scrolling log) still gets classified.
+    pub max_wait_ms: u64,
+    /// How long a running classification may lag behind a newer frame before it is
+    /// cancelled. Until then it finishes, so slow models still produce verdicts.
+    pub stale_after_ms: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            capture_interval_ms: 500,
+            debounce_ms: 1000,
+            max_wait_ms: 5000,
+            stale_after_ms: 3000,
+        }
+    }
+}
+
+/// Payload of the `verdict` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct VerdictEvent {
+    pub frame_id: u64,
+    pub verdict: Verdict,
+}
+
+/// Classifies the frame ids published on `frames`, one at a time.
+///
+/// Waits until no new frame has arrived for `debounce_ms`, or `max_wait_ms` after the
+/// first unclassified change, then classifies the latest frame. A classification that
+/// is still running `stale_after_ms` after a newer frame arrived is cancelled. `config`
+/// is re-read as it goes. Returns once the sender is dropped.
+pub async fn classify_changes<F, Fut>(
+    mut frames: watch::Receiver<Option<u64>>,
+    config: impl Fn() -> WatchConfig,
+    classify: F,
+    mut on_verdict: impl FnMut(VerdictEvent),
+) where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<Verdict, ClassifierError>>,
+{
+    // When the oldest and the latest frame not classified yet arrived.
+    let mut pending: Option<(Instant, Instant)> = None;
+    loop {
+        let (first, mut last) = match pending.take() {
+            Some(pending) => pending,
+            None => {
+                if frames.changed().await.is_err() {
+                    return;
+                }
+                (Instant::now(), Instant::now())
+            }
+        };
+        let deadline = first + Duration::from_millis(config().max_wait_ms);
+        loop {
+            let settled = last + Duration::from_millis(config().debounce_ms);
+            tokio::select! {
+                _ = time::sleep_until(settled.min(deadline)) => break,
+                changed = frames.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    last = Instant::now();
+                }
+            }
+        }
+        let Some(frame_id) = *frames.borrow_and_update() else {
+            continue;
+        };
+        let classification = classify(frame_id);
+        tokio::pin!(classification);
+        // When the classification counts as stale, once a newer frame has arrived.
+        let mut stale_at: Option<Instant> = None;
+        loop {
+            tokio::select! {
+                result = &mut classification => {
+                    match result {
+                        Ok(verdict) => on_verdict(VerdictEvent { frame_id, verdict }),
+                        Err(err) => println!("[error]: could not classify frame {}: {}", frame_id, err),
+                    }
+                    break;
+                }
+                changed = frames.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                    let now = Instant::now();
+                    pending = Some((pending.map_or(now, |(first, _)| first), now));
+                    stale_at.get_or_insert(now + Duration::from_millis(config().stale_after_ms));
+                }
+                _ = time::sleep_until(stale_at.unwrap_or_else(Instant::now)), if stale_at.is_some() => {
+                    println!("[info]: cancelled classifying frame {}, the screen changed", frame_id);
+                    break;
+                }
+            }
+        }
+    }
+}
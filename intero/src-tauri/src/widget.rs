@@ -1,6 +1,10 @@
 // Panel
+#[cfg(target_os = "macos")]
 use crate::{main_window::position_window_fullscreen, panel_ext::init_as_panel};
-use tauri::{AppHandle, Manager, Wry};
+use tauri::{AppHandle, Manager};
+#[cfg(target_os = "macos")]
+use tauri::Wry;
+#[cfg(target_os = "macos")]
 use tauri_nspanel::ManagerExt;
 
 #[tauri::command]
@@ -50,19 +54,28 @@ pub fn open_widget_window(app: &AppHandle) {
         // }
         let _ = window.set_ignore_cursor_events(true);
         // let _ = window.show().ok();
-        position_window_fullscreen(&window, 1.0);
-        // position_window(&window, |display_pos, display_size, win_frame_size| {
-        //     NSPoint {
-        //         x: (display_pos.x + (display_size.width)) - (win_frame_size.width),
-        //         // y: (display_pos.y + (display_size.height)) - (win_frame_size.height), // 160 from the top
-        //         y: display_pos.y
-        //     }
-        // });
-        init_as_panel(window);
-        open_panel(app);
+        #[cfg(not(target_os = "macos"))]
+        {
+            window.maximize().ok();
+            window.show().ok();
+        }
+        #[cfg(target_os = "macos")]
+        {
+            position_window_fullscreen(&window, 1.0);
+            // position_window(&window, |display_pos, display_size, win_frame_size| {
+            //     NSPoint {
+            //         x: (display_pos.x + (display_size.width)) - (win_frame_size.width),
+            //         // y: (display_pos.y + (display_size.height)) - (win_frame_size.height), // 160 from the top
+            //         y: display_pos.y
+            //     }
+            // });
+            init_as_panel(window);
+            open_panel(app);
+        }
     }
 }
 
+#[cfg(target_os = "macos")]
 fn open_panel(handle: &AppHandle<Wry>) {
   let panel = handle.get_panel("widget").unwrap();
   panel.show();
@@ -0,0 +1,241 @@
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use x11rb::connection::{Connection, RequestConnection};
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::shm::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{ConnectionExt as _, ImageFormat, ImageOrder, Window};
+use x11rb::rust_connection::RustConnection;
+
+use crate::frame_source::{CaptureError, CapturedFrame, FrameSource, PixelFormat, ScreenRect};
+
+/// Grabs the root window of the default X11 screen, or one RandR monitor of it.
+/// Uses MIT-SHM when the server supports it and falls back to plain `GetImage`.
+pub struct X11Source {
+    monitor: Option<usize>,
+    capture: Mutex<Option<X11Capture>>,
+}
+
+impl X11Source {
+    /// `monitor` is an index into the RandR monitor list; `None` captures the whole root window.
+    pub fn new(monitor: Option<usize>) -> Self {
+        X11Source {
+            monitor,
+            capture: Mutex::new(None),
+        }
+    }
+}
+
+impl FrameSource for X11Source {
+    fn start(&self) -> Result<(), CaptureError> {
+        let mut capture = self.capture.lock().unwrap();
+        if capture.is_none() {
+            *capture = Some(X11Capture::connect(self.monitor).map_err(CaptureError::failed)?);
+        }
+        Ok(())
+    }
+
+    fn stop(&self) {
+        self.capture.lock().unwrap().take();
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        let mut capture = self.capture.lock().unwrap();
+        let capture = capture.as_mut().ok_or(CaptureError::NotStarted)?;
+        let data = capture.grab().map_err(CaptureError::failed)?;
+        capture.sequence += 1;
+        Ok(CapturedFrame {
+            timestamp: SystemTime::now(),
+            sequence: capture.sequence,
+            width: capture.region.width as u32,
+            height: capture.region.height as u32,
+            display_id: capture.display_id,
+            screen_rect: ScreenRect {
+                x: capture.region.x as f64,
+                y: capture.region.y as f64,
+                width: capture.region.width as f64,
+                height: capture.region.height as f64,
+            },
+            stride: capture.region.width as usize * 4,
+            format: PixelFormat::Bgra,
+            data,
+        })
+    }
+}
+
+struct Region {
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+}
+
+struct X11Capture {
+    conn: RustConnection,
+    root: Window,
+    region: Region,
+    display_id: u32,
+    sequence: u64,
+    shm: Option<ShmSegment>,
+}
+
+impl X11Capture {
+    fn connect(monitor: Option<usize>) -> Result<Self, String> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(|e| e.to_string())?;
+        let setup = conn.setup();
+        let screen = &setup.roots[screen_num];
+        let root = screen.root;
+
+        // We only convert 32bpp little-endian ZPixmaps (every TrueColor server in practice).
+        let bits_per_pixel = setup
+            .pixmap_formats
+            .iter()
+            .find(|f| f.depth == screen.root_depth)
+            .map(|f| f.bits_per_pixel);
+        if bits_per_pixel != Some(32) || setup.image_byte_order != ImageOrder::LSB_FIRST {
+            return Err(format!(
+                "Unsupported X11 pixel format: depth {} bpp {:?}",
+                screen.root_depth, bits_per_pixel
+            ));
+        }
+
+        let (region, display_id) = match monitor {
+            None => (
+                Region {
+                    x: 0,
+                    y: 0,
+                    width: screen.width_in_pixels,
+                    height: screen.height_in_pixels,
+                },
+                0,
+            ),
+            Some(index) => {
+                let monitors = conn
+                    .randr_get_monitors(root, true)
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| e.to_string())?
+                    .monitors;
+                let info = monitors
+                    .get(index)
+                    .ok_or_else(|| format!("No X11 monitor {} ({} found)", index, monitors.len()))?;
+                (
+                    Region {
+                        x: info.x,
+                        y: info.y,
+                        width: info.width,
+                        height: info.height,
+                    },
+                    index as u32,
+                )
+            }
+        };
+
+        let shm = match ShmSegment::new(&conn, region.width as usize * region.height as usize * 4) {
+            Ok(shm) => Some(shm),
+            Err(err) => {
+                println!("[info]: MIT-SHM unavailable, using GetImage: {}", err);
+                None
+            }
+        };
+
+        Ok(X11Capture {
+            conn,
+            root,
+            region,
+            display_id,
+            sequence: 0,
+            shm,
+        })
+    }
+
+    /// Returns tightly packed BGRX rows.
+    fn grab(&mut self) -> Result<Vec<u8>, String> {
+        let Region { x, y, width, height } = self.region;
+        let bgrx = match &self.shm {
+            Some(shm) => {
+                self.conn
+                    .shm_get_image(self.root, x, y, width, height, !0, ImageFormat::Z_PIXMAP.into(), shm.seg, 0)
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| e.to_string())?;
+                shm.bytes().to_vec()
+            }
+            None => {
+                self.conn
+                    .get_image(ImageFormat::Z_PIXMAP, self.root, x, y, width, height, !0)
+                    .map_err(|e| e.to_string())?
+                    .reply()
+                    .map_err(|e| e.to_string())?
+                    .data
+            }
+        };
+        if bgrx.len() < width as usize * height as usize * 4 {
+            return Err("X11 returned a short image".to_string());
+        }
+        Ok(bgrx)
+    }
+}
+
+impl Drop for X11Capture {
+    fn drop(&mut self) {
+        if let Some(shm) = self.shm.take() {
+            let _ = self.conn.shm_detach(shm.seg);
+            let _ = self.conn.flush();
+        }
+    }
+}
+
+/// A SysV shared memory segment attached to both this process and the X server.
+struct ShmSegment {
+    seg: shm::Seg,
+    addr: *mut libc::c_void,
+    size: usize,
+}
+
+// The mapping is only touched while holding the `X11Source` mutex.
+unsafe impl Send for ShmSegment {}
+
+impl ShmSegment {
+    fn new(conn: &RustConnection, size: usize) -> Result<Self, String> {
+        if conn
+            .extension_information(shm::X11_EXTENSION_NAME)
+            .map_err(|e| e.to_string())?
+            .is_none()
+        {
+            return Err("server has no MIT-SHM extension".to_string());
+        }
+
+        let seg = conn.generate_id().map_err(|e| e.to_string())?;
+        let shmid = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if shmid < 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        let addr = unsafe { libc::shmat(shmid, std::ptr::null(), 0) };
+        if addr as isize == -1 {
+            let err = std::io::Error::last_os_error().to_string();
+            unsafe { libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut()) };
+            return Err(err);
+        }
+
+        let attached = conn
+            .shm_attach(seg, shmid as u32, false)
+            .map_err(|e| e.to_string())
+            .and_then(|cookie| cookie.check().map_err(|e| e.to_string()));
+        // Mark for removal now; the segment lives until both sides detach.
+        unsafe { libc::shmctl(shmid, libc::IPC_RMID, std::ptr::null_mut()) };
+        let segment = ShmSegment { seg, addr, size };
+        attached?;
+        Ok(segment)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, self.size) }
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        unsafe { libc::shmdt(self.addr) };
+    }
+}
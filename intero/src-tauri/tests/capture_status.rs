@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clippy_app::capture_status::{CaptureController, CaptureStatus};
+use clippy_app::frame_source::{CaptureError, CapturedFrame, FakeSource, FrameSource, Permission};
+
+use CaptureStatus::*;
+
+/// Lets the test keep a handle on the source the controller owns.
+struct Shared(Arc<FakeSource>);
+
+impl FrameSource for Shared {
+    fn start(&self) -> Result<(), CaptureError> {
+        self.0.start()
+    }
+
+    fn stop(&self) {
+        self.0.stop()
+    }
+
+    fn latest_frame(&self) -> Result<CapturedFrame, CaptureError> {
+        self.0.latest_frame()
+    }
+
+    fn permission(&self) -> Permission {
+        self.0.permission()
+    }
+
+    fn request_permission(&self) -> Permission {
+        self.0.request_permission()
+    }
+}
+
+/// A controller over a fake source, and every status it has reported so far.
+fn controller(permission: Permission) -> (CaptureController, Arc<FakeSource>, Arc<Mutex<Vec<CaptureStatus>>>) {
+    let source = Arc::new(FakeSource::new(permission));
+    let controller = CaptureController::new(Box::new(Shared(source.clone())));
+    let seen = Arc::new(Mutex::new(vec![]));
+    let listener = seen.clone();
+    controller.set_listener(Box::new(move |change| listener.lock().unwrap().push(change.status)));
+    (controller, source, seen)
+}
+
+fn take(seen: &Mutex<Vec<CaptureStatus>>) -> Vec<CaptureStatus> {
+    std::mem::take(&mut seen.lock().unwrap())
+}
+
+#[test]
+fn runs_from_the_first_frame_until_stopped_or_failed() {
+    let (capture, source, seen) = controller(Permission::Granted);
+    source.fail_stream(Some(CaptureError::NoFrameYet));
+    capture.start().unwrap();
+    assert_eq!(take(&seen), [Requesting, Granted, Starting]);
+    assert_eq!(capture.latest_frame().unwrap_err(), CaptureError::NoFrameYet);
+    assert_eq!(capture.refresh(), Starting);
+
+    source.fail_stream(None);
+    assert_eq!(capture.refresh(), Running);
+    capture.stop();
+    assert_eq!(take(&seen), [Running, Granted]);
+
+    // A stream that dies moves to `Failed`; starting again recovers.
+    capture.start().unwrap();
+    capture.latest_frame().unwrap();
+    source.fail_stream(Some(CaptureError::failed("display went away")));
+    assert_eq!(capture.refresh(), Failed);
+    source.fail_stream(None);
+    capture.start().unwrap();
+    capture.latest_frame().unwrap();
+    assert_eq!(take(&seen), [Requesting, Granted, Starting, Running, Failed, Requesting, Granted, Starting, Running]);
+}
+
+#[test]
+fn a_timed_pause_restarts_through_starting() {
+    let (capture, _source, seen) = controller(Permission::Granted);
+    capture.start().unwrap();
+    capture.latest_frame().unwrap();
+    capture.pause(Some(Duration::ZERO));
+    assert_eq!(capture.latest_frame().unwrap_err(), CaptureError::Paused);
+    assert_eq!(capture.refresh(), Starting);
+    capture.latest_frame().unwrap();
+    assert_eq!(take(&seen), [Requesting, Granted, Starting, Running, Paused, Requesting, Granted, Starting, Running]);
+}
+
+#[test]
+fn permission_denied_until_granted() {
+    let (capture, source, seen) = controller(Permission::Denied);
+    assert_eq!(capture.start().unwrap_err(), CaptureError::PermissionDenied);
+    assert_eq!(capture.latest_frame().unwrap_err(), CaptureError::PermissionDenied);
+    assert_eq!(capture.refresh(), Denied);
+
+    // Granted in System Settings.
+    source.set_permission(Permission::Granted);
+    assert_eq!(capture.refresh(), Granted);
+    capture.start().unwrap();
+    capture.latest_frame().unwrap();
+
+    // Revoked while running.
+    source.fail_stream(Some(CaptureError::PermissionDenied));
+    assert_eq!(capture.refresh(), Denied);
+    assert_eq!(take(&seen), [Requesting, Denied, Granted, Requesting, Granted, Starting, Running, Denied]);
+}
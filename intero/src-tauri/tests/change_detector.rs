@@ -0,0 +1,66 @@
+use clippy_app::change_detector::{ChangeDetector, ChangeDetectorConfig};
+use clippy_app::frame_source::Rect;
+use image::{Rgba, RgbaImage};
+
+const CONFIG: ChangeDetectorConfig = ChangeDetectorConfig {
+    threshold: 0.1,
+    min_changed_pixels: 100,
+    block_size: 10,
+};
+
+fn white() -> RgbaImage {
+    RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255]))
+}
+
+/// White with the first `count` pixels, row by row, set to `color`.
+fn with_pixels(count: u32, color: [u8; 4]) -> RgbaImage {
+    let mut image = white();
+    for i in 0..count {
+        image.put_pixel(i % 100, i / 100, Rgba(color));
+    }
+    image
+}
+
+#[test]
+fn identical_frames_do_not_change() {
+    let mut detector = ChangeDetector::new(CONFIG);
+    assert!(detector.observe(white()).changed);
+    let diff = detector.observe(white());
+    assert!(!diff.changed);
+    assert_eq!((diff.score, diff.changed_pixels), (0.0, 0));
+    assert!(diff.regions.is_empty());
+}
+
+#[test]
+fn small_diffs_stay_below_the_threshold() {
+    let mut detector = ChangeDetector::new(CONFIG);
+    detector.observe(white());
+    // Too faint to count as changed pixels at all.
+    assert_eq!(detector.observe(with_pixels(5000, [250, 250, 250, 255])).changed_pixels, 0);
+    // Exactly `min_changed_pixels` is not enough, as in the frontend's pixelmatch check.
+    let diff = detector.observe(with_pixels(100, [0, 0, 0, 255]));
+    assert_eq!(diff.changed_pixels, 100);
+    assert!(!diff.changed);
+    assert_eq!(diff.score, 0.01);
+}
+
+#[test]
+fn larger_diffs_change_and_become_the_reference() {
+    let mut detector = ChangeDetector::new(CONFIG);
+    detector.observe(white());
+    let diff = detector.observe(with_pixels(101, [0, 0, 0, 255]));
+    assert!(diff.changed);
+    assert_eq!(diff.changed_pixels, 101);
+    assert_eq!(
+        diff.regions,
+        [Rect {
+            x: 0,
+            y: 0,
+            width: 100,
+            height: 10,
+        }]
+    );
+    // Later frames are compared against the changed one.
+    assert!(!detector.observe(with_pixels(101, [0, 0, 0, 255])).changed);
+    assert!(detector.observe(white()).changed);
+}
@@ -0,0 +1,177 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+use clippy_app::prompt::Prompt;
+use clippy_app::classifier::{
+    Classifier, ClassifierConfig, ClassifierError, Image, MoondreamClassifier, OllamaClassifier, OpenAiClassifier,
+};
+use clippy_app::secrets::{FileStore, Secrets};
+
+const IMAGE: Image = Image {
+    bytes: b"not really a png",
+    mime_type: "image/png",
+};
+
+struct Request {
+    line: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Serves one request with a recorded response and hands back what it received.
+fn replay(status: &str, content_type: &str, fixture: &str) -> (String, JoinHandle<Request>) {
+    let body = std::fs::read(format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture)).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (status, content_type) = (status.to_string(), content_type.to_string());
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let mut headers = vec![];
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            let Some((key, value)) = header.trim_end().split_once(": ") else {
+                break;
+            };
+            headers.push((key.to_string(), value.to_string()));
+        }
+        let mut request = Request {
+            line: line.trim_end().to_string(),
+            headers,
+            body: vec![],
+        };
+        if let Some(length) = request.header("content-length") {
+            request.body = vec![0; length.parse().unwrap()];
+            reader.read_exact(&mut request.body).unwrap();
+        } else if request.header("transfer-encoding") == Some("chunked") {
+            loop {
+                let mut size = String::new();
+                reader.read_line(&mut size).unwrap();
+                let size = usize::from_str_radix(size.trim_end(), 16).unwrap();
+                let mut chunk = vec![0; size + 2];
+                reader.read_exact(&mut chunk).unwrap();
+                if size == 0 {
+                    break;
+                }
+                request.body.extend_from_slice(&chunk[..size]);
+            }
+        }
+        let mut stream = stream;
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            status,
+            content_type,
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(&body).unwrap();
+        request
+    });
+    (url, handle)
+}
+
+#[tokio::test]
+async fn openai_returns_message_content() {
+    let (url, server) = replay("200 OK", "application/json", "openai_chat_completion.json");
+    let classifier = OpenAiClassifier::new(format!("{}/v1", url), "gpt-4-vision-preview", Some("sk-test".into()));
+    let result = classifier.classify(&IMAGE, &Prompt::default()).await.unwrap();
+    assert!(result.text.contains("\"activity\": \"work\""));
+    assert_eq!(result.model, "openai:gpt-4-vision-preview");
+
+    let request = server.join().unwrap();
+    assert_eq!(request.line, "POST /v1/chat/completions HTTP/1.1");
+    assert_eq!(request.header("authorization"), Some("Bearer sk-test"));
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    let image_url = body["messages"][1]["content"][1]["image_url"]["url"].as_str().unwrap();
+    assert!(image_url.starts_with("data:image/png;base64,"));
+}
+
+#[tokio::test]
+async fn openai_rejection_is_an_error() {
+    let (url, server) = replay("400 Bad Request", "application/json", "openai_content_policy.json");
+    let classifier = OpenAiClassifier::new(url, "gpt-4-vision-preview", None);
+    match classifier.classify(&IMAGE, &Prompt::default()).await {
+        Err(ClassifierError::Status { status, body }) => {
+            assert_eq!(status, 400);
+            assert!(body.contains("content_policy_violation"));
+        }
+        other => panic!("expected a status error, got {:?}", other.map(|c| c.text)),
+    }
+    server.join().unwrap();
+}
+
+#[tokio::test]
+async fn ollama_returns_response_field() {
+    let (url, server) = replay("200 OK", "application/json", "ollama_generate.json");
+    let classifier = OllamaClassifier::new(url, "llava");
+    let result = classifier.classify(&IMAGE, &Prompt::default()).await.unwrap();
+    assert!(result.text.contains("\"activity\": \"distraction\""));
+
+    let request = server.join().unwrap();
+    assert_eq!(request.line, "POST /api/generate HTTP/1.1");
+    let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(body["model"], "llava");
+    assert_eq!(body["stream"], false);
+    assert_eq!(body["images"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn moondream_joins_streamed_chunks() {
+    let (url, server) = replay("200 OK", "text/event-stream", "moondream_inference.sse");
+    let classifier = MoondreamClassifier::new(format!("{}/api/inference", url));
+    let result = classifier.classify(&IMAGE, &Prompt::default()).await.unwrap();
+    assert_eq!(result.text, "Work - the user is editing a spreadsheet.");
+
+    let request = server.join().unwrap();
+    assert_eq!(request.line, "POST /api/inference HTTP/1.1");
+    assert!(request.header("content-type").unwrap().starts_with("multipart/form-data"));
+    let body = String::from_utf8_lossy(&request.body);
+    assert!(body.contains("name=\"prompt\""));
+    assert!(body.contains("not really a png"));
+}
+
+#[tokio::test]
+async fn local_only_refuses_remote_backends_before_sending_anything() {
+    let dir = std::env::temp_dir().join(format!("intero-local-only-{}", std::process::id()));
+    let secrets = Secrets::new(Box::new(FileStore::new(&dir)));
+    // Unroutable, so a request that did go out would fail differently.
+    let remote = ClassifierConfig::Ollama {
+        base_url: "http://192.0.2.1:11434".to_string(),
+        model: "llava".to_string(),
+    };
+    let refused = remote.build(&secrets, true);
+    assert_eq!(refused.model(), "ollama:llava");
+    assert_eq!(
+        refused.classify(&IMAGE, &Prompt::default()).await.unwrap_err(),
+        ClassifierError::RemoteNotAllowed {
+            model: "ollama:llava".to_string()
+        }
+    );
+    let refused = ClassifierConfig::openai("gpt-4o").build(&secrets, true);
+    assert!(matches!(
+        refused.classify(&IMAGE, &Prompt::default()).await,
+        Err(ClassifierError::RemoteNotAllowed { .. })
+    ));
+
+    let (url, server) = replay("200 OK", "application/json", "ollama_generate.json");
+    let local = ClassifierConfig::Ollama {
+        base_url: url,
+        model: "llava".to_string(),
+    };
+    local.build(&secrets, true).classify(&IMAGE, &Prompt::default()).await.unwrap();
+    server.join().unwrap();
+}
@@ -0,0 +1,133 @@
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use clippy_app::db::{ActivityKind, ActivityRow, Db, Graph, Relation, VerdictRow, SCHEMA_VERSION};
+
+fn temp_db(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("intero-db-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn user_version(path: &PathBuf) -> usize {
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.pragma_query_value(None, "user_version", |row| row.get(0)).unwrap()
+}
+
+fn activity(activity_id: &str, kind: ActivityKind, created_at: i64) -> ActivityRow {
+    ActivityRow {
+        id: None,
+        activity_id: activity_id.to_string(),
+        kind,
+        created_at,
+        end_time: None,
+    }
+}
+
+fn verdict(timestamp: i64, frame_id: Option<i64>) -> VerdictRow {
+    VerdictRow {
+        id: None,
+        timestamp,
+        frame_id,
+        activity_id: None,
+        classifier: "test".to_string(),
+        prompt_version: Some("v1".to_string()),
+        verdict: json!({ "category": "work" }),
+    }
+}
+
+#[test]
+fn migrations_run_once_and_record_user_version() {
+    let path = temp_db("migrations");
+    Db::open(&path).unwrap().put_node("a", &json!({ "value": "A" })).unwrap();
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+
+    // Back to schema 2, as an older app left it.
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "DROP TABLE undo_log;
+         DROP TABLE prompt_templates;
+         ALTER TABLE verdicts DROP COLUMN prompt_version;
+         PRAGMA user_version = 2;",
+    )
+    .unwrap();
+    drop(conn);
+
+    let db = Db::open(&path).unwrap();
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+    assert_eq!(db.get_node("a").unwrap(), Some(json!({ "value": "A" })));
+    db.insert_verdict(&verdict(1, None)).unwrap();
+    assert_eq!(db.verdicts(None, None).unwrap()[0].prompt_version.as_deref(), Some("v1"));
+    drop(db);
+
+    // Opening again applies nothing.
+    Db::open(&path).unwrap();
+    assert_eq!(user_version(&path), SCHEMA_VERSION);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn replace_graph_round_trips() {
+    let mut db = Db::open_in_memory().unwrap();
+    let graph: Graph = serde_json::from_value(json!({
+        "nodes": {
+            "goal": { "value": "Goal", "createdAt": "2024-01-02T03:04:05.000Z", "__maxVec": [0, 0, -3, 0] },
+            "task": { "value": "Task", "estimatedTime": 30, "__parents": ["goal"] },
+            "other": { "value": "Other" },
+        },
+        "relations": { "depends": [{ "parent": "goal", "child": "task" }] },
+    }))
+    .unwrap();
+    db.replace_graph(&graph).unwrap();
+
+    let stored = db.graph().unwrap();
+    assert_eq!(stored.nodes["goal"], json!({ "value": "Goal", "createdAt": "2024-01-02T03:04:05.000Z" }));
+    assert_eq!(stored.nodes["task"], json!({ "value": "Task", "estimatedTime": 30 }));
+    assert_eq!(
+        stored.relations["depends"],
+        [Relation {
+            parent: "goal".to_string(),
+            child: "task".to_string(),
+        }]
+    );
+
+    // Replacing again drops what the new graph does not have.
+    let mut smaller = stored.clone();
+    smaller.nodes.remove("goal");
+    smaller.relations.clear();
+    db.replace_graph(&smaller).unwrap();
+    let stored = db.graph().unwrap();
+    assert_eq!(stored.nodes.len(), 2);
+    assert!(stored.relations.is_empty());
+}
+
+#[test]
+fn activity_and_verdicts_are_queried_by_range() {
+    let db = Db::open_in_memory().unwrap();
+    for (i, kind) in [ActivityKind::Start, ActivityKind::Stop, ActivityKind::Start].into_iter().enumerate() {
+        db.insert_activity(&activity("a", kind, 10 * (i as i64 + 1))).unwrap();
+    }
+    let times = |rows: Vec<ActivityRow>| rows.iter().map(|row| row.created_at).collect::<Vec<_>>();
+    assert_eq!(times(db.activity(None, None).unwrap()), [10, 20, 30]);
+    assert_eq!(times(db.activity(Some(20), Some(30)).unwrap()), [20]);
+    assert_eq!(times(db.activity(Some(11), None).unwrap()), [20, 30]);
+    // Repeating the last row is skipped; every window logs the same start.
+    assert_eq!(db.log_activity(&activity("a", ActivityKind::Start, 40)).unwrap(), None);
+    assert_eq!(db.last_activity().unwrap().unwrap().created_at, 30);
+
+    db.insert_frame(1, 100, "1.png", None).unwrap();
+    db.insert_frame(2, 200, "2.png", None).unwrap();
+    // Frame ids are never reused, so a taken one is an error rather than a replace.
+    assert!(db.insert_frame(2, 300, "3.png", None).is_err());
+    db.insert_verdict(&verdict(100, Some(1))).unwrap();
+    db.insert_verdict(&verdict(200, Some(2))).unwrap();
+    assert_eq!(db.verdicts(Some(150), Some(201)).unwrap().len(), 1);
+    assert_eq!(db.verdicts(Some(0), Some(100)).unwrap().len(), 0);
+
+    // Verdicts outlive their pruned frames.
+    assert_eq!(db.delete_frames_before(150).unwrap(), 1);
+    let frames: Vec<_> = db.verdicts(None, None).unwrap().iter().map(|row| row.frame_id).collect();
+    assert_eq!(frames, [None, Some(2)]);
+    assert_eq!(db.next_frame_id().unwrap(), 3);
+}
@@ -0,0 +1,78 @@
+// Generates toposort-parity-expected.json from toposort-parity-input.json with the
+// frontend Toposorter, orderNodes and orderEdges, types stripped, from before the UI
+// rendered graph_sorted. Run: node toposort-parity.mjs toposort-parity-input.json
+import fs from "node:fs";
+
+class TNode {
+  constructor(id, data, ctx) { this.id = id; this.data = data; this.ctx = ctx; }
+  children() { return this.data.children; }
+  parents() { return this.data.__parents ?? []; }
+  get pinned() { return this.data.pinned; }
+  get createdAt() { return this.data.createdAt; }
+  get priority() { return this.data.priority; }
+  get status() { return this.data.status; }
+  maxVec() { return this.data.__maxVec; }
+}
+
+class Toposorter {
+  visited = new Set();
+  outputData = {};
+  constructor(entries) { this.inputData = Object.fromEntries(entries); }
+  static sort(entries) { return new Toposorter(entries).sort(); }
+  sort() {
+    const nodes = {};
+    for (let key of Object.keys(this.inputData)) this.visitChildren(key, nodes);
+    let entries = [...this.visited].map((x) => [x, nodes[x]]);
+    this.outputData = Object.fromEntries(entries);
+    return entries;
+  }
+  visitChildren(key, nodes) {
+    if (this.visited.has(key)) return;
+    const data = this.inputData[key];
+    if (!data) return;
+    const node = new TNode(key, { ...data }, this);
+    let maxVec = makeScoreVector(node);
+    for (let childId of node.children()) {
+      this.visitChildren(childId, nodes);
+      maxVec = chooseMaxVec(maxVec, nodes[childId].maxVec());
+      nodes[childId].parents().push(key);
+    }
+    nodes[key] = new TNode(key, { ...this.inputData[key], __maxVec: maxVec, __parents: [] }, this);
+    this.visited.add(key);
+  }
+}
+
+function chooseMaxVec(a, b) { return compareVecs(a, b) > 0 ? b : a; }
+function compareVecs(a, b) {
+  for (let i = 0; i < a.length; i++) {
+    if (a[i] < b[i]) return 1;
+    if (a[i] > b[i]) return -1;
+  }
+  return 0;
+}
+function makeScoreVector(node) {
+  return [node.pinned === true ? 1 : 0, statusToPoints(node.status), -1 * (node.priority ?? 3), node.createdAt.getTime()];
+}
+function statusToPoints(status) { return status === "done" ? -1 : status === "active" ? 1 : 0; }
+
+function orderNodes(entries) {
+  return [...entries].sort(([, a], [, b]) => compareVecs(a.maxVec(), b.maxVec()));
+}
+
+function orderEdges(entries) {
+  const edges = [];
+  const idx = new Map();
+  entries.forEach(([id], i) => idx.set(id, i));
+  for (let [i, node] of entries) {
+    const ordered = [...node.children()].filter((c) => idx.has(c)).sort((a, b) => idx.get(a) - idx.get(b));
+    for (let child of ordered) edges.push([i, child]);
+  }
+  return edges;
+}
+
+const input = JSON.parse(fs.readFileSync(process.argv[2], "utf8"), (k, v) => (k === "createdAt" ? new Date(v) : v));
+const entries = orderNodes(Toposorter.sort(Object.entries(input.nodes)));
+console.log(JSON.stringify({
+  order: entries.map(([id]) => id),
+  edges: orderEdges(entries),
+}, null, 2));
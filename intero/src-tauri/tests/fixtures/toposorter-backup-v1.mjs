@@ -0,0 +1,99 @@
+// Generates toposorter-backup-v1.json: what the frontend `backup` command wrote, i.e. the
+// "toposorter" localStorage entry, after a session of edits. The actions are
+// ToposorterStateManager's from before the graph moved to the database, types
+// stripped, with immer's produce swapped for a copy. Run: node toposorter-backup-v1.mjs
+import crypto from "node:crypto";
+import fs from "node:fs";
+
+const produce = (recipe) => (state) => {
+  const draft = structuredClone(state);
+  recipe(draft, state);
+  return draft;
+};
+
+class StateManager {
+  state = { nodes: {} };
+
+  apply(change) {
+    this.state = change(this.state);
+  }
+
+  add(from, connectionType) {
+    const id = crypto.randomUUID();
+    this.apply(produce((draft) => {
+      draft.nodes[id] = {
+        value: "",
+        createdAt: new Date(),
+        children: [],
+      };
+      if (!connectionType || !from) {
+        return;
+      }
+      if (connectionType === "child") {
+        draft.nodes[from].children.push(id);
+      } else if (connectionType === "parent") {
+        draft.nodes[id].children.push(from);
+      }
+    }));
+    return id;
+  }
+
+  deleteNode(id) {
+    this.apply(produce((draft, original) => {
+      delete draft.nodes[id];
+      for (const key of Object.keys(original.nodes)) {
+        if (key === id) {
+          continue;
+        }
+        draft.nodes[key].children = original.nodes[key].children.filter((childId) => childId !== id);
+      }
+    }));
+  }
+
+  addEdge(from, to) {
+    this.apply(produce((draft) => {
+      draft.nodes[from].children.push(to);
+    }));
+  }
+
+  set(id, field, value) {
+    this.apply(produce((draft) => {
+      draft.nodes[id][field] = value;
+    }));
+  }
+}
+
+const wait = (ms) => new Promise((resolve) => setTimeout(resolve, ms));
+
+const manager = new StateManager();
+const goal = manager.add();
+manager.set(goal, "value", "Ship the focus report");
+manager.set(goal, "type", "goal");
+manager.set(goal, "priority", 1);
+manager.set(goal, "pinned", true);
+await wait(20);
+const sum = manager.add(goal, "child");
+manager.set(sum, "value", "Sum time per task");
+manager.set(sum, "status", "active");
+manager.set(sum, "estimatedTime", 90);
+await wait(20);
+const csv = manager.add(goal, "child");
+manager.set(csv, "value", "Export as CSV");
+manager.set(csv, "status", "done");
+manager.set(csv, "notes", "Use the same columns as the spreadsheet");
+await wait(20);
+const read = manager.add(sum, "child");
+manager.set(read, "value", "Read the activity log");
+manager.set(read, "type", "task");
+manager.set(read, "priority", 2);
+await wait(20);
+const spike = manager.add(read, "child");
+manager.set(spike, "value", "Try a time-series database");
+manager.deleteNode(spike);
+// `child <id> <id>` on the command line did not check that the nodes exist, so linking
+// to an id copied before the delete left an edge to nothing.
+manager.addEdge(read, spike);
+
+// useLocalStorageState drops `__` fields; `backup` wrote the entry as is.
+const json = JSON.stringify(manager.state, (key, value) => (key.startsWith("__") ? undefined : value));
+fs.writeFileSync(new URL("toposorter-backup-v1.json", import.meta.url), json);
@@ -0,0 +1,115 @@
+use clippy_app::focus::{FocusChange, FocusConfig, FocusState, FocusTracker};
+use clippy_app::verdict::{Category, ParseKind, Verdict};
+
+const CONFIG: FocusConfig = FocusConfig {
+    window_ms: 60_000,
+    threshold: 0.6,
+    min_dwell_ms: 10_000,
+    switch_grace_ms: 15_000,
+};
+
+fn verdict(category: Category, confidence: Option<f32>) -> Verdict {
+    Verdict {
+        category,
+        confidence,
+        description: String::new(),
+        reason: String::new(),
+        parse: ParseKind::Json,
+        raw: String::new(),
+        model: "test:model".to_string(),
+        latency_ms: 0,
+        foreground: None,
+        prompt_version: None,
+    }
+}
+
+/// Feeds `(seconds, category)` verdicts in order and returns the transitions.
+fn run(tracker: &mut FocusTracker, script: &[(u64, Category)]) -> Vec<(u64, FocusState)> {
+    script
+        .iter()
+        .filter_map(|&(at, category)| tracker.observe(at * 1000, &verdict(category, None)))
+        .map(|change| (change.timestamp / 1000, change.state))
+        .collect()
+}
+
+use Category::{Distraction, Unknown, Work};
+
+#[test]
+fn settles_after_the_dwell_time() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    let changes = run(&mut tracker, &[(0, Work), (5, Work), (10, Work), (15, Work)]);
+    assert_eq!(changes, vec![(10, FocusState::Focused)]);
+}
+
+#[test]
+fn a_single_distraction_does_not_flip() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    run(&mut tracker, &[(0, Work), (10, Work)]);
+    let changes = run(&mut tracker, &[(12, Distraction), (14, Work), (30, Work), (45, Work)]);
+    assert!(changes.is_empty());
+    assert_eq!(tracker.state(), FocusState::Focused);
+}
+
+#[test]
+fn sustained_distraction_flips_once_it_leads_long_enough() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    run(&mut tracker, &[(0, Work), (10, Work)]);
+    let script: Vec<_> = (11..=40).map(|at| (at, Distraction)).collect();
+    let changes = run(&mut tracker, &script);
+    // Distraction passes 60% of the window at 13s, then has to lead for 10s more.
+    assert_eq!(changes, vec![(23, FocusState::Distracted)]);
+}
+
+#[test]
+fn one_work_frame_does_not_clear_distracted() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    run(&mut tracker, &[(0, Distraction), (10, Distraction)]);
+    assert_eq!(tracker.state(), FocusState::Distracted);
+    let changes = run(&mut tracker, &[(20, Work), (22, Distraction), (24, Distraction)]);
+    assert!(changes.is_empty());
+}
+
+#[test]
+fn unknown_verdicts_dilute_the_lead() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    let changes = run(&mut tracker, &[(0, Distraction), (5, Unknown), (10, Distraction), (15, Unknown)]);
+    assert!(changes.is_empty());
+    assert_eq!(tracker.state(), FocusState::Unknown);
+}
+
+#[test]
+fn confident_verdicts_outweigh_unsure_ones() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    for at in [0, 5, 10] {
+        tracker.observe(at * 1000, &verdict(Work, Some(0.9)));
+        tracker.observe(at * 1000 + 1, &verdict(Distraction, Some(0.3)));
+    }
+    assert_eq!(tracker.state(), FocusState::Focused);
+}
+
+#[test]
+fn no_distraction_during_the_switch_grace_period() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    run(&mut tracker, &[(0, Work), (10, Work)]);
+    tracker.context_switch(20_000);
+    let script: Vec<_> = (20..=50).step_by(5).map(|at| (at, Distraction)).collect();
+    let changes = run(&mut tracker, &script);
+    // Grace ends at 35s; the lead counts from the first verdict after that.
+    assert_eq!(changes, vec![(45, FocusState::Distracted)]);
+}
+
+#[test]
+fn an_unchanged_screen_settles_on_tick() {
+    let mut tracker = FocusTracker::new(CONFIG);
+    assert_eq!(tracker.observe(0, &verdict(Distraction, None)), None);
+    assert_eq!(tracker.tick(5_000), None);
+    // Long past the window, the last verdict still stands.
+    assert_eq!(
+        tracker.tick(90_000),
+        Some(FocusChange {
+            state: FocusState::Distracted,
+            previous: FocusState::Unknown,
+            timestamp: 90_000,
+        })
+    );
+}
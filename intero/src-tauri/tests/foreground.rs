@@ -0,0 +1,52 @@
+use clippy_app::foreground::{self, ForegroundInfo, ForegroundWatcher, ScriptedForeground};
+
+fn app(app_id: &str, title: &str) -> Option<ForegroundInfo> {
+    Some(ForegroundInfo {
+        app_id: app_id.to_string(),
+        app_name: app_id.to_string(),
+        pid: Some(42),
+        title: Some(title.to_string()),
+        url: None,
+    })
+}
+
+#[test]
+fn reports_only_changes() {
+    let watcher = ForegroundWatcher::new(Box::new(ScriptedForeground::new([
+        app("code", "main.rs"),
+        app("code", "main.rs"),
+        app("code", "lib.rs"),
+        app("firefox", "YouTube"),
+        None,
+    ])));
+    let changes: Vec<_> = (0..6).filter_map(|now| watcher.poll(now)).collect();
+    let timestamps: Vec<_> = changes.iter().map(|change| change.timestamp).collect();
+    assert_eq!(timestamps, vec![0, 2, 3, 4]);
+    assert_eq!(changes[1].previous, app("code", "main.rs"));
+    assert_eq!(watcher.current(), None);
+}
+
+#[test]
+fn tells_app_switches_from_title_changes() {
+    let provider = ScriptedForeground::new([app("code", "main.rs")]);
+    provider.push(app("code", "lib.rs"));
+    provider.push(app("firefox", "YouTube"));
+    let watcher = ForegroundWatcher::new(Box::new(provider));
+    let switched: Vec<_> = (0..3).filter_map(|now| watcher.poll(now)).map(|change| change.switched_app()).collect();
+    assert_eq!(switched, vec![true, false, true]);
+}
+
+#[test]
+fn plays_back_a_script_file() {
+    let path = std::env::temp_dir().join(format!("intero-foreground-{}.json", std::process::id()));
+    std::fs::write(&path, serde_json::to_string(&[app("code", "main.rs"), None]).unwrap()).unwrap();
+    let provider = foreground::from_spec(&format!("fake:{}", path.display())).unwrap();
+    assert_eq!(provider.foreground(), app("code", "main.rs"));
+    assert_eq!(provider.foreground(), None);
+    assert_eq!(provider.foreground(), None);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(foreground::from_spec("fake").unwrap().foreground(), None);
+    assert!(foreground::from_spec(&format!("fake:{}", path.display())).is_err());
+    assert_eq!(foreground::from_spec("wayland").err().unwrap(), "Unknown foreground provider \"wayland\"");
+}
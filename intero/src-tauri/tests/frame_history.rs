@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use clippy_app::encoding::Encoding;
+use clippy_app::frame_history::{FrameHistory, FrameHistoryConfig, RetentionPolicy};
+use clippy_app::frame_source::CapturedFrame;
+use image::{Rgba, RgbaImage};
+
+const T0: u64 = 1_700_000_000_000;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("intero-frames-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn config(max_bytes: u64, max_age_secs: u64) -> FrameHistoryConfig {
+    FrameHistoryConfig {
+        retention: RetentionPolicy { max_bytes, max_age_secs },
+        max_width: None,
+        encoding: Encoding::Png,
+    }
+}
+
+/// A 4×4 frame taken `ms` after `T0`. Same-sized frames encode to the same size.
+fn frame(ms: u64) -> CapturedFrame {
+    let timestamp = UNIX_EPOCH + Duration::from_millis(T0 + ms);
+    CapturedFrame::from_rgba(RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255])), timestamp, 0, 0)
+}
+
+fn files(dir: &PathBuf) -> Vec<String> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".png"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn ids(history: &FrameHistory, from: Option<u64>, to: Option<u64>, limit: Option<usize>) -> Vec<u64> {
+    history.list(from, to, limit).iter().map(|record| record.id).collect()
+}
+
+#[test]
+fn lists_ranges_oldest_first() {
+    let dir = temp_dir("list");
+    let mut history = FrameHistory::open(&dir, config(u64::MAX, u64::MAX)).unwrap();
+    for ms in [0, 2000, 1000, 3000] {
+        history.insert(&frame(ms), 0.5, None).unwrap();
+    }
+    assert_eq!(ids(&history, None, None, None), [0, 2, 1, 3]);
+    assert_eq!(ids(&history, Some(T0 + 1000), Some(T0 + 3000), None), [2, 1]);
+    assert_eq!(ids(&history, Some(T0 + 1000), None, Some(1)), [2]);
+    assert_eq!(ids(&history, Some(T0 + 3001), None, None), [] as [u64; 0]);
+    assert_eq!(history.at(T0 + 2500).unwrap().unwrap().record.id, 1);
+    assert!(history.at(T0 - 1).unwrap().is_none());
+
+    // The index survives a reopen.
+    let history = FrameHistory::open(&dir, config(u64::MAX, u64::MAX)).unwrap();
+    assert_eq!(ids(&history, None, None, None), [0, 2, 1, 3]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prunes_by_age_and_by_size() {
+    let dir = temp_dir("age");
+    let mut history = FrameHistory::open(&dir, config(u64::MAX, 60)).unwrap();
+    for ms in [0, 30_000, 90_000] {
+        history.insert(&frame(ms), 0.5, None).unwrap();
+    }
+    // Inserting at 90s drops everything older than 30s.
+    assert_eq!(ids(&history, None, None, None), [1, 2]);
+    history.prune(T0 + 100_000).unwrap();
+    assert_eq!(ids(&history, None, None, None), [2]);
+    assert_eq!(files(&dir), [format!("{}-2.png", T0 + 90_000)]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let dir = temp_dir("size");
+    let size = FrameHistory::open(&dir, config(u64::MAX, u64::MAX)).unwrap().insert(&frame(0), 0.5, None).unwrap().size;
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut history = FrameHistory::open(&dir, config(2 * size, u64::MAX)).unwrap();
+    for ms in [0, 1000, 2000] {
+        history.insert(&frame(ms), 0.5, None).unwrap();
+    }
+    assert_eq!(ids(&history, None, None, None), [1, 2]);
+    assert_eq!(files(&dir).len(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn ids_are_not_reused_after_pruning() {
+    let dir = temp_dir("ids");
+    let mut history = FrameHistory::open(&dir, config(u64::MAX, 60)).unwrap();
+    history.insert(&frame(0), 0.5, None).unwrap();
+    history.insert(&frame(1000), 0.5, None).unwrap();
+    history.prune(T0 + 3_600_000).unwrap();
+    assert!(history.list(None, None, None).is_empty());
+
+    let mut history = FrameHistory::open(&dir, config(u64::MAX, 60)).unwrap();
+    assert_eq!(history.insert(&frame(3_600_000), 0.5, None).unwrap().id, 2);
+    history.skip_ids_below(10).unwrap();
+    assert_eq!(history.insert(&frame(3_601_000), 0.5, None).unwrap().id, 10);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_failed_index_append_leaves_no_image_behind() {
+    let dir = temp_dir("orphan");
+    let mut history = FrameHistory::open(&dir, config(u64::MAX, u64::MAX)).unwrap();
+    // Appending to a directory fails.
+    std::fs::create_dir(dir.join("index.jsonl")).unwrap();
+    assert!(history.insert(&frame(0), 0.5, None).is_err());
+    assert!(files(&dir).is_empty());
+    assert!(history.list(None, None, None).is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clippy_app::frame_source::{self, CaptureError, FrameSource, PatternSource, Permission, ReplaySource};
+use image::{Rgba, RgbaImage};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("intero-replay-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn parses_source_specs() {
+    for spec in ["", "native", "pattern", "fake", "replay:/nonexistent"] {
+        assert!(frame_source::from_spec(spec).is_ok(), "{}", spec);
+    }
+    assert_eq!(frame_source::from_spec("fake:denied").unwrap().permission(), Permission::Denied);
+    assert_eq!(frame_source::from_spec("fake").unwrap().permission(), Permission::Granted);
+
+    assert_eq!(frame_source::from_spec("webcam").err().unwrap(), "Unknown frame source \"webcam\"");
+    assert_eq!(frame_source::from_spec("replay:").err().unwrap(), "replay needs a path, as in replay:<path>");
+    if cfg!(target_os = "linux") {
+        assert!(frame_source::from_spec("x11").is_ok());
+        assert!(frame_source::from_spec("x11:1").is_ok());
+        assert_eq!(frame_source::from_spec("x11:left").err().unwrap(), "Bad X11 monitor index \"left\"");
+    }
+}
+
+#[test]
+fn pattern_frames_follow_the_clock() {
+    let source = PatternSource::new(16, 4, Duration::from_millis(1));
+    assert_eq!(source.latest_frame().unwrap_err(), CaptureError::NotStarted);
+    source.start().unwrap();
+    std::thread::sleep(Duration::from_millis(5));
+    let frame = source.latest_frame().unwrap();
+    assert!(frame.sequence >= 5);
+    assert_eq!((frame.width, frame.height), (16, 4));
+    assert_eq!(frame.to_rgba(), source.render(frame.sequence as usize));
+    // The bars shift by one each step.
+    assert_ne!(source.render(0), source.render(1));
+    source.stop();
+    assert_eq!(source.latest_frame().unwrap_err(), CaptureError::NotStarted);
+}
+
+#[test]
+fn replays_images_in_name_order_and_loops() {
+    let dir = temp_dir("order");
+    for (name, color) in [("b.png", GREEN), ("a.png", RED), ("c.png", BLUE)] {
+        RgbaImage::from_pixel(2, 2, Rgba(color)).save(dir.join(name)).unwrap();
+    }
+    std::fs::write(dir.join("notes.txt"), "not an image").unwrap();
+
+    let source = ReplaySource::new(&dir, Duration::from_millis(10));
+    assert_eq!(source.latest_frame().unwrap_err(), CaptureError::NotStarted);
+    source.start().unwrap();
+    let mut seen = vec![];
+    while seen.len() < 5 {
+        let frame = source.latest_frame().unwrap();
+        if seen.last() != Some(&frame.sequence) {
+            assert_eq!(frame.to_rgba().get_pixel(0, 0).0, [RED, GREEN, BLUE][frame.sequence as usize % 3]);
+            seen.push(frame.sequence);
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    // Past the last image it starts over.
+    assert!(seen.iter().any(|&step| step >= 3));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let dir = temp_dir("empty");
+    assert!(matches!(ReplaySource::new(&dir, Duration::from_millis(10)).start(), Err(CaptureError::Failed { .. })));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use clippy_app::db::{Db, Graph, Relation};
+use clippy_app::graph_schema::{self, DanglingEdge, SchemaError, GRAPH_SCHEMA_VERSION};
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn edge(parent: &str, child: &str) -> Relation {
+    Relation {
+        parent: parent.to_string(),
+        child: child.to_string(),
+    }
+}
+
+/// Fixture ids are random; look nodes up by title.
+fn id(graph: &Graph, value: &str) -> String {
+    graph.nodes.iter().find(|(_, data)| data["value"] == value).unwrap().0.clone()
+}
+
+#[test]
+fn legacy_backups_move_children_into_depends() {
+    let json = fixture("toposorter-backup-v1.json");
+    let legacy: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let (graph, report) = graph_schema::load(&json).unwrap();
+    let [goal, sum, export, read] =
+        ["Ship the focus report", "Sum time per task", "Export as CSV", "Read the activity log"].map(|v| id(&graph, v));
+    assert_eq!(report.from_version, 1);
+    assert_eq!(report.converted, 4);
+    // Linked after it was deleted.
+    let deleted = legacy["nodes"][&read]["children"][0].as_str().unwrap();
+    assert!(!graph.nodes.contains_key(deleted));
+    assert_eq!(
+        report.dangling,
+        [DanglingEdge {
+            kind: "depends".to_string(),
+            parent: read.clone(),
+            child: deleted.to_string(),
+        }]
+    );
+    assert_eq!(graph.relations["depends"], [edge(&goal, &sum), edge(&goal, &export), edge(&sum, &read)]);
+    assert!(graph.nodes.values().all(|data| data.get("children").is_none()));
+    assert_eq!(graph.nodes[&sum]["estimatedTime"], 90);
+
+    // Saving and loading again is a no-op.
+    let (again, report) = graph_schema::load(&graph_schema::to_json(&graph).to_string()).unwrap();
+    assert_eq!((report.from_version, report.converted), (GRAPH_SCHEMA_VERSION, 0));
+    assert_eq!(again.relations, graph.relations);
+    assert_eq!(again.nodes, graph.nodes);
+}
+
+#[test]
+fn future_backups_are_refused() {
+    let (graph, _) = graph_schema::load(&fixture("toposorter-backup-v1.json")).unwrap();
+    let mut backup = graph_schema::to_json(&graph);
+    backup["version"] = (GRAPH_SCHEMA_VERSION + 1).into();
+    let err = graph_schema::load(&backup.to_string()).unwrap_err();
+    assert_eq!(
+        err,
+        SchemaError::FutureVersion {
+            version: GRAPH_SCHEMA_VERSION + 1,
+            supported: GRAPH_SCHEMA_VERSION,
+        }
+    );
+    assert!(matches!(graph_schema::load("[]"), Err(SchemaError::Invalid { .. })));
+}
+
+#[test]
+fn the_database_stores_edges_as_relations() {
+    let path: PathBuf = std::env::temp_dir().join(format!("intero-graph-schema-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let (graph, _) = graph_schema::load(&fixture("toposorter-backup-v1.json")).unwrap();
+    let [goal, sum, export, read] =
+        ["Ship the focus report", "Sum time per task", "Export as CSV", "Read the activity log"].map(|v| id(&graph, v));
+    Db::open(&path).unwrap();
+
+    // A node as the frontend wrote it before schema 2.
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    for (id, data) in &graph.nodes {
+        let mut data = data.clone();
+        if *id == goal {
+            data["children"] = serde_json::json!([sum, "missing"]);
+        }
+        conn.execute(
+            "INSERT INTO nodes (id, data, updated_at) VALUES (?1, ?2, 0)",
+            [id.as_str(), &data.to_string()],
+        )
+        .unwrap();
+    }
+    // Back to schema 3, undoing the later migrations.
+    conn.execute_batch("DROP TABLE undo_log").unwrap();
+    conn.pragma_update(None, "user_version", 3).unwrap();
+    drop(conn);
+
+    let db = Db::open(&path).unwrap();
+    let stored = db.graph().unwrap();
+    assert_eq!(stored.relations["depends"], [edge(&goal, &sum)]);
+    assert!(stored.nodes[&goal].get("children").is_none());
+    db.put_node(&export, &serde_json::json!({ "value": "Export", "children": [read] })).unwrap();
+    assert_eq!(db.graph().unwrap().relations["depends"], [edge(&goal, &sum), edge(&export, &read)]);
+    drop(db);
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.pragma_update(None, "user_version", 99).unwrap();
+    drop(conn);
+    let err = Db::open(&path).err().unwrap();
+    assert!(err.to_string().contains("schema version 99"));
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,50 @@
+use clippy_app::db::{Graph, Relation};
+use clippy_app::prompt::{Prompt, TaskContext, GENERIC_OLLAMA_V1, GENERIC_V1, TASK_V1};
+use serde_json::json;
+
+fn graph() -> Graph {
+    let nodes = json!({
+        "ship": { "value": "Ship the 1.0 release", "children": ["docs"] },
+        "docs": { "value": "Write the docs", "type": "project" },
+        "guide": { "value": "Write the setup guide", "type": "task", "notes": "Cover Linux too" },
+    });
+    Graph {
+        nodes: nodes.as_object().unwrap().clone(),
+        relations: [(
+            "depends".to_string(),
+            vec![Relation {
+                parent: "docs".to_string(),
+                child: "guide".to_string(),
+            }],
+        )]
+        .into(),
+    }
+}
+
+#[test]
+fn reads_the_task_and_its_goals() {
+    let task = TaskContext::from_graph(&graph(), "guide").unwrap();
+    assert_eq!(task.title, "Write the setup guide");
+    assert_eq!(task.kind.as_deref(), Some("task"));
+    assert_eq!(task.notes.as_deref(), Some("Cover Linux too"));
+    assert_eq!(task.ancestors, vec!["Ship the 1.0 release", "Write the docs"]);
+    assert_eq!(TaskContext::from_graph(&graph(), "missing"), None);
+}
+
+#[test]
+fn fills_in_the_task_prompt() {
+    let task = TaskContext::from_graph(&graph(), "guide").unwrap();
+    let prompt = Prompt::for_task(Some(&task), "ollama:llava");
+    assert_eq!(prompt.version, TASK_V1.version);
+    assert!(prompt.user.contains("Task: Write the setup guide\nType: task\nPart of: Ship the 1.0 release > Write the docs"));
+    assert!(!prompt.user.contains("{{"));
+
+    // Only Ollama was ever told to lean towards "distraction".
+    let backends = [("moondream:moondream", GENERIC_V1), ("openai:gpt-4o", GENERIC_V1), ("ollama:llava", GENERIC_OLLAMA_V1)];
+    for (model, template) in backends {
+        let generic = Prompt::for_task(None, model);
+        assert_eq!((generic.version, generic.user.as_str()), (template.version, template.user));
+    }
+    assert!(GENERIC_OLLAMA_V1.user.contains("Be liberal with the \"distraction\" category."));
+    assert!(!GENERIC_V1.user.contains("liberal"));
+}
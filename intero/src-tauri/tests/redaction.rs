@@ -0,0 +1,154 @@
+use std::time::SystemTime;
+
+use clippy_app::classifier::ClassifierConfig;
+use clippy_app::frame_source::{CapturedFrame, FrameSource, PatternSource, ScreenRect};
+use clippy_app::redaction::{
+    redact, FixedLayout, RedactionConfig, RedactionStyle, Redactor, ScreenLayout, WindowInfo,
+};
+use image::{Rgba, RgbaImage};
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+/// A 200×100 white frame of a 400×200 point screen, so one pixel is two points.
+fn frame() -> CapturedFrame {
+    let mut frame = CapturedFrame::from_rgba(RgbaImage::from_pixel(200, 100, Rgba(WHITE)), SystemTime::now(), 0, 0);
+    frame.screen_rect = ScreenRect {
+        x: 0.0,
+        y: 0.0,
+        width: 400.0,
+        height: 200.0,
+    };
+    frame
+}
+
+fn rect(x: f64, y: f64, width: f64, height: f64) -> ScreenRect {
+    ScreenRect { x, y, width, height }
+}
+
+fn pixel(frame: &CapturedFrame, x: u32, y: u32) -> [u8; 4] {
+    frame.to_rgba().get_pixel(x, y).0
+}
+
+fn window(app_id: &str, rect: ScreenRect) -> WindowInfo {
+    WindowInfo {
+        app_id: app_id.to_string(),
+        rect,
+        notification: false,
+    }
+}
+
+#[test]
+fn blacks_out_regions_in_screen_coordinates() {
+    let mut frame = frame();
+    let config = RedactionConfig {
+        regions: vec![rect(20.0, 20.0, 40.0, 20.0)],
+        ..RedactionConfig::default()
+    };
+    let report = redact(&mut frame, &config, &ScreenLayout::default());
+    assert_eq!((report.regions, report.pixels), (1, 20 * 10));
+    assert_eq!(pixel(&frame, 10, 10), BLACK);
+    assert_eq!(pixel(&frame, 29, 14), BLACK);
+    assert_eq!(pixel(&frame, 30, 10), WHITE);
+    assert_eq!(pixel(&frame, 9, 10), WHITE);
+}
+
+#[test]
+fn hides_app_windows_only_where_visible() {
+    let mut frame = frame();
+    let layout = ScreenLayout {
+        // Front to back: an editor covers the left half of the password manager.
+        windows: vec![
+            window("code", rect(0.0, 0.0, 100.0, 200.0)),
+            window("com.1password.1password", rect(0.0, 0.0, 200.0, 200.0)),
+            WindowInfo {
+                notification: true,
+                ..window("com.apple.notificationcenterui", rect(300.0, 0.0, 100.0, 40.0))
+            },
+        ],
+        password_fields: vec![rect(240.0, 100.0, 40.0, 20.0)],
+    };
+    let config = RedactionConfig {
+        apps: vec!["com.1Password.1password".to_string()],
+        ..RedactionConfig::default()
+    };
+    let report = redact(&mut frame, &config, &layout);
+    assert_eq!((report.windows, report.notifications, report.password_fields), (1, 1, 1));
+    assert_eq!(pixel(&frame, 20, 50), WHITE);
+    assert_eq!(pixel(&frame, 70, 50), BLACK);
+    assert_eq!(pixel(&frame, 170, 10), BLACK);
+    assert_eq!(pixel(&frame, 130, 55), BLACK);
+    assert_eq!(pixel(&frame, 130, 80), WHITE);
+
+    let mut untouched = self::frame();
+    let config = RedactionConfig {
+        password_fields: false,
+        notifications: false,
+        ..RedactionConfig::default()
+    };
+    assert_eq!(redact(&mut untouched, &config, &layout).pixels, 0);
+    assert_eq!(untouched.data, self::frame().data);
+}
+
+#[test]
+fn blur_averages_blocks_inside_the_mask() {
+    // Alternating black and white columns, in BGRA.
+    let mut frame = CapturedFrame::from_rgba(
+        RgbaImage::from_fn(16, 8, |x, _| Rgba(if x % 2 == 0 { BLACK } else { WHITE })),
+        SystemTime::now(),
+        0,
+        0,
+    );
+    frame.format = clippy_app::frame_source::PixelFormat::Bgra;
+    let config = RedactionConfig {
+        style: RedactionStyle::Blur { block_size: 4 },
+        regions: vec![rect(0.0, 0.0, 8.0, 8.0)],
+        ..RedactionConfig::default()
+    };
+    redact(&mut frame, &config, &ScreenLayout::default());
+    assert_eq!(pixel(&frame, 0, 0), [127, 127, 127, 255]);
+    assert_eq!(pixel(&frame, 7, 7), [127, 127, 127, 255]);
+    assert_eq!(pixel(&frame, 8, 0), BLACK);
+    assert_eq!(pixel(&frame, 9, 0), WHITE);
+}
+
+#[test]
+fn wrapped_sources_hand_out_redacted_frames() {
+    let source = PatternSource::new(64, 40, std::time::Duration::from_secs(60));
+    let layout = FixedLayout::default();
+    let redactor = Redactor::new(
+        RedactionConfig {
+            apps: vec!["firefox".to_string()],
+            ..RedactionConfig::default()
+        },
+        Box::new(layout),
+    );
+    let wrapped = redactor.wrap(&source);
+    wrapped.start().unwrap();
+    let before = wrapped.latest_frame().unwrap();
+    assert_ne!(pixel(&before, 0, 0), BLACK);
+
+    redactor
+        .set_config(RedactionConfig {
+            regions: vec![rect(0.0, 0.0, 64.0, 40.0)],
+            ..redactor.config()
+        })
+        .unwrap();
+    let after = wrapped.latest_frame().unwrap();
+    assert!(after.to_rgba().pixels().all(|px| px.0 == BLACK));
+}
+
+#[test]
+fn only_loopback_backends_count_as_local() {
+    let with_url = |base_url: &str| ClassifierConfig::Ollama {
+        base_url: base_url.to_string(),
+        model: "llava".to_string(),
+    };
+    assert!(ClassifierConfig::ollama("llava").is_local());
+    assert!(ClassifierConfig::moondream().is_local());
+    assert!(with_url("http://127.0.0.1:11434").is_local());
+    assert!(with_url("http://[::1]:11434/").is_local());
+    assert!(!with_url("http://192.168.1.20:11434").is_local());
+    assert!(!with_url("http://localhost.example.com").is_local());
+    assert!(!ClassifierConfig::openai("gpt-4o").is_local());
+}
@@ -0,0 +1,116 @@
+use chrono::{FixedOffset, TimeZone};
+use serde_json::json;
+
+use clippy_app::db::{ActivityKind, ActivityRow, Graph, VerdictRow};
+use clippy_app::reports::{self, ExportFormat, Report, Totals};
+
+const MINUTE: i64 = 60_000;
+
+fn row(activity_id: &str, kind: ActivityKind, created_at: i64) -> ActivityRow {
+    ActivityRow {
+        id: None,
+        activity_id: activity_id.to_string(),
+        kind,
+        created_at,
+        end_time: None,
+    }
+}
+
+fn verdict(timestamp: i64, category: &str) -> VerdictRow {
+    VerdictRow {
+        id: None,
+        timestamp,
+        frame_id: None,
+        activity_id: None,
+        classifier: "test".to_string(),
+        prompt_version: None,
+        verdict: json!({ "category": category }),
+    }
+}
+
+fn totals(tracked: i64, focused: i64, distracted: i64) -> Totals {
+    Totals {
+        tracked_ms: tracked * MINUTE,
+        focused_ms: focused * MINUTE,
+        distracted_ms: distracted * MINUTE,
+    }
+}
+
+/// `area` depends on `goal` and `task`, and `goal` on `task`. Work on `task` starts at
+/// 23:00 on Sunday 2026-10-18, UTC+2, and runs past midnight into the next ISO week.
+fn sample() -> Report {
+    let graph: Graph = serde_json::from_value(json!({
+        "nodes": {
+            "area": { "value": "Area" },
+            "goal": { "value": "Goal", "estimatedTime": 60 },
+            "task": { "value": "Write, then \"test\"", "estimatedTime": 90 },
+        },
+        "relations": { "depends": [
+            { "parent": "area", "child": "goal" },
+            { "parent": "area", "child": "task" },
+            { "parent": "goal", "child": "task" },
+        ] },
+    }))
+    .unwrap();
+    let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+    let t0 = tz.with_ymd_and_hms(2026, 10, 18, 23, 0, 0).unwrap().timestamp_millis();
+    let activity = [
+        row("task", ActivityKind::Start, t0),
+        row("task", ActivityKind::Stop, t0 + 120 * MINUTE),
+        row("goal", ActivityKind::Start, t0 + 180 * MINUTE),
+        // Deleted since, and still running.
+        row("gone", ActivityKind::Start, t0 + 190 * MINUTE),
+    ];
+    let verdicts = [
+        verdict(t0, "work"),
+        verdict(t0 + 5 * MINUTE, "work"),
+        verdict(t0 + 10 * MINUTE, "distraction"),
+        verdict(t0 + 190 * MINUTE, "unknown"),
+    ];
+    let now = t0 + 200 * MINUTE;
+    reports::report(&graph, &activity, &verdicts, t0 - 60 * MINUTE, now, now, &tz)
+}
+
+#[test]
+fn rolls_time_up_through_ancestors() {
+    let report = sample();
+    // The last verdict before the stop only counts for `VERDICT_SPAN_MS`.
+    assert_eq!(report.totals, totals(140, 10, 5));
+
+    let nodes: Vec<_> = report.nodes.iter().map(|node| (node.id.as_str(), node.total.tracked_ms / MINUTE)).collect();
+    // `area` reaches `task` along two paths but counts it once.
+    assert_eq!(nodes, [("area", 130), ("goal", 130), ("task", 120), ("gone", 10)]);
+    let goal = &report.nodes[1];
+    assert_eq!((goal.own, goal.total), (totals(10, 0, 0), totals(130, 10, 5)));
+    assert_eq!(goal.over_estimate_ms(), Some(70 * MINUTE));
+    assert_eq!(report.nodes[2].over_estimate_ms(), Some(30 * MINUTE));
+    assert_eq!((report.nodes[3].title.as_deref(), report.nodes[3].estimate_ms), (None, None));
+
+    let days: Vec<_> = report.days.iter().map(|day| (day.label.as_str(), day.totals)).collect();
+    assert_eq!(days, [("2026-10-18", totals(60, 10, 5)), ("2026-10-19", totals(80, 0, 0))]);
+    let weeks: Vec<_> = report.weeks.iter().map(|week| (week.label.as_str(), week.totals)).collect();
+    assert_eq!(weeks, [("2026-W42", totals(60, 10, 5)), ("2026-W43", totals(80, 0, 0))]);
+    assert_eq!(report.days[1].to - report.days[1].from, 24 * 60 * MINUTE);
+}
+
+#[test]
+fn exports_csv_and_markdown() {
+    let report = sample();
+    let csv = reports::export(&report, ExportFormat::NodesCsv);
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines[0], "id,title,total_min,own_min,focused_min,distracted_min,estimate_min,over_estimate_min");
+    assert_eq!(lines[3], "task,\"Write, then \"\"test\"\"\",120.0,120.0,10.0,5.0,90.0,30.0");
+    assert_eq!(lines[4], "gone,,10.0,10.0,0.0,0.0,,");
+    assert_eq!(
+        reports::export(&report, ExportFormat::WeeksCsv),
+        "period,tracked_min,focused_min,distracted_min,unclassified_min\n\
+         2026-W42,60.0,10.0,5.0,45.0\n\
+         2026-W43,80.0,0.0,0.0,80.0\n"
+    );
+
+    let markdown = reports::export(&report, ExportFormat::Markdown);
+    assert!(markdown.starts_with("# Time report, 2026-10-18 to 2026-10-19\n\nTracked 2h 20m: 10m focused, 5m distracted, 2h 05m unclassified.\n"));
+    assert!(markdown.contains("| Goal | 2h 10m | 10m | 10m | 5m | 1h 00m | 1h 10m |\n"));
+    assert!(markdown.contains("| gone (deleted) | 10m | 10m | 0m | 0m |  |  |\n"));
+    assert!(markdown.contains("| 2026-10-19 | 1h 20m | 0m | 0m | 1h 20m |\n"));
+}
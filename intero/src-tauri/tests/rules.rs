@@ -0,0 +1,142 @@
+use std::fs::{self, File};
+use std::time::{Duration, UNIX_EPOCH};
+
+use clippy_app::foreground::ForegroundInfo;
+use clippy_app::rules::{RuleContext, Rules, RulesWatcher};
+use clippy_app::verdict::{Category, ParseKind};
+
+const RULES: &str = r#"
+[[rule]]
+name = "Editors"
+category = "work"
+app_id = ["com.microsoft.VSCode", "code"]
+
+[[rule]]
+name = "Video sites"
+category = "distraction"
+url_host = ["youtube.com", "twitch.tv"]
+
+[[rule]]
+name = "Late-night pull requests"
+category = "work"
+title = "(?i)pull request"
+hours = "22:00-02:00"
+
+[[rule]]
+name = "Research"
+category = "work"
+app_id = ["firefox"]
+task_type = ["problem"]
+"#;
+
+fn foreground(app_id: &str, title: &str, url: Option<&str>) -> ForegroundInfo {
+    ForegroundInfo {
+        app_id: app_id.to_string(),
+        app_name: app_id.to_string(),
+        pid: None,
+        title: Some(title.to_string()),
+        url: url.map(str::to_string),
+    }
+}
+
+fn matched<'a>(rules: &'a Rules, foreground: &ForegroundInfo, minute_of_day: u32, task_type: Option<&str>) -> Option<&'a str> {
+    let context = RuleContext {
+        foreground: Some(foreground),
+        minute_of_day,
+        task_type,
+    };
+    rules.evaluate(&context).map(|rule| rule.name.as_str())
+}
+
+#[test]
+fn matches_app_url_title_hours_and_task_type() {
+    let rules = Rules::from_toml(RULES).unwrap();
+    assert_eq!(rules.len(), 4);
+    let noon = 12 * 60;
+
+    assert_eq!(matched(&rules, &foreground("Code", "main.rs", None), noon, None), Some("Editors"));
+    let video = foreground("firefox", "Cats", Some("https://m.youtube.com/watch?v=1"));
+    assert_eq!(matched(&rules, &video, noon, None), Some("Video sites"));
+    let not_video = foreground("firefox", "Cats", Some("https://notyoutube.com/"));
+    assert_eq!(matched(&rules, &not_video, noon, None), None);
+
+    let pull_request = foreground("firefox", "Fix parser · Pull Request #12", None);
+    assert_eq!(matched(&rules, &pull_request, noon, None), None);
+    assert_eq!(matched(&rules, &pull_request, 23 * 60, None), Some("Late-night pull requests"));
+    assert_eq!(matched(&rules, &pull_request, 60, None), Some("Late-night pull requests"));
+
+    let docs = foreground("firefox", "Docs", Some("https://docs.rs/"));
+    assert_eq!(matched(&rules, &docs, noon, Some("task")), None);
+    assert_eq!(matched(&rules, &docs, noon, Some("problem")), Some("Research"));
+}
+
+#[test]
+fn first_match_wins_and_produces_a_rule_verdict() {
+    let rules = Rules::from_json(
+        r#"{ "rules": [
+            { "name": "All firefox", "category": "distraction", "app_id": ["firefox"] },
+            { "name": "Docs", "category": "work", "url_host": ["docs.rs"] }
+        ] }"#,
+    )
+    .unwrap();
+    let docs = foreground("firefox", "Docs", Some("https://docs.rs/"));
+    let rule = rules
+        .evaluate(&RuleContext {
+            foreground: Some(&docs),
+            ..RuleContext::default()
+        })
+        .unwrap();
+    let verdict = rule.verdict(Some(docs.clone()));
+    assert_eq!(verdict.category, Category::Distraction);
+    assert_eq!(verdict.parse, ParseKind::Rule);
+    assert_eq!(verdict.foreground, Some(docs));
+}
+
+#[test]
+fn rejects_bad_rules() {
+    let no_conditions = "[[rule]]\nname = \"Everything\"\ncategory = \"work\"\n";
+    assert!(Rules::from_toml(no_conditions).unwrap_err().message.contains("no conditions"));
+    let bad_regex = "[[rule]]\nname = \"Broken\"\ncategory = \"work\"\ntitle = \"(\"\n";
+    assert!(Rules::from_toml(bad_regex).unwrap_err().message.contains("Broken"));
+    let bad_hours = "[[rule]]\nname = \"Hours\"\ncategory = \"work\"\nhours = \"9am-5pm\"\n";
+    assert!(Rules::from_toml(bad_hours).is_err());
+    let hours = |hours: &str| {
+        Rules::from_toml(&format!("[[rule]]\nname = \"Hours\"\ncategory = \"work\"\nhours = \"{}\"\n", hours))
+    };
+    assert!(hours("18:00-24:00").is_ok());
+    assert!(hours("18:00-24:30").is_err());
+    assert!(hours("25:00-02:00").is_err());
+}
+
+#[test]
+fn reloads_the_rules_file_when_it_changes() {
+    let dir = std::env::temp_dir().join(format!("intero-rules-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let mut watcher = RulesWatcher::open(&dir);
+    let path = dir.join("rules.toml");
+    assert_eq!(watcher.status().path.as_ref(), Some(&path));
+    assert!(!watcher.reload_if_changed());
+
+    // Set the time explicitly, as two writes can land within one mtime tick.
+    let write = |text: &str, modified: u64| {
+        fs::write(&path, text).unwrap();
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(modified)).unwrap();
+    };
+    write(RULES, 1_000);
+    assert!(watcher.reload_if_changed());
+    assert_eq!((watcher.status().rules, watcher.status().error), (4, None));
+    assert!(!watcher.reload_if_changed());
+
+    // A broken edit keeps the previous rules.
+    write("[[rule]]\nname = \"Half-typed\"\n", 2_000);
+    assert!(watcher.reload_if_changed());
+    let status = watcher.status();
+    assert_eq!(status.rules, 4);
+    assert!(status.error.is_some());
+
+    fs::remove_file(&path).unwrap();
+    assert!(watcher.reload_if_changed());
+    assert_eq!((watcher.status().path, watcher.status().rules), (None, 0));
+    fs::remove_dir_all(&dir).unwrap();
+}
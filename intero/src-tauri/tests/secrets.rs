@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::PathBuf;
+
+use clippy_app::secrets::{FileStore, SecretName, SecretStore, Secrets, SecretsError};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("intero-secrets-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn file_store_keeps_keys_encrypted() {
+    let dir = temp_dir("roundtrip");
+    let secrets = Secrets::new(Box::new(FileStore::new(&dir)));
+    assert_eq!(secrets.get(SecretName::OpenAi), None);
+    secrets.set(SecretName::OpenAi, "sk-test-1234").unwrap();
+
+    let reopened = Secrets::new(Box::new(FileStore::new(&dir)));
+    assert_eq!(reopened.get(SecretName::OpenAi).as_deref(), Some("sk-test-1234"));
+    assert_eq!(reopened.status().unwrap().set, vec![SecretName::OpenAi]);
+    let stored = fs::read(dir.join("secrets.bin")).unwrap();
+    assert!(!String::from_utf8_lossy(&stored).contains("sk-test"));
+
+    assert!(reopened.clear(SecretName::OpenAi).unwrap());
+    assert!(!reopened.clear(SecretName::OpenAi).unwrap());
+    assert_eq!(reopened.status().unwrap().set, vec![]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_replaced_key_file_is_reported_not_ignored() {
+    let dir = temp_dir("rekeyed");
+    let store = FileStore::new(&dir);
+    store.set("openai", "sk-test-1234").unwrap();
+    fs::write(dir.join("secrets.key"), [7u8; 32]).unwrap();
+    assert!(matches!(store.get("openai"), Err(SecretsError::Corrupt { .. })));
+    fs::remove_dir_all(dir).unwrap();
+}
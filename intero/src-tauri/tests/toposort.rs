@@ -0,0 +1,230 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use clippy_app::db::{Db, Graph, Relation};
+use clippy_app::toposort::{self, compare_vecs, Connection, GraphError};
+use proptest::prelude::*;
+use serde_json::json;
+
+fn node(created_at: i64, children: &[&str]) -> serde_json::Value {
+    json!({ "value": "", "createdAt": created_at, "children": children })
+}
+
+/// Up to 12 nodes with random scores and random edges, named `n00, n01, ...`.
+/// With `acyclic`, edges only point from lower to higher numbered nodes.
+fn graphs(acyclic: bool) -> impl Strategy<Value = Graph> {
+    (1usize..12)
+        .prop_flat_map(move |n| {
+            let scores = prop::collection::vec((any::<bool>(), 0u8..3, prop::option::of(1u8..5), 0i64..4), n);
+            let edges = prop::collection::vec((0..n, 0..n, any::<bool>()), 0..n * 2);
+            (scores, edges)
+        })
+        .prop_map(move |(scores, edges)| {
+            let mut graph = Graph::default();
+            for (i, (pinned, status, priority, created_at)) in scores.iter().enumerate() {
+                let mut data = node(*created_at, &[]);
+                data["pinned"] = json!(pinned);
+                data["status"] = json!(["active", "done", "unset"][*status as usize]);
+                if let Some(priority) = priority {
+                    data["priority"] = json!(priority);
+                }
+                graph.nodes.insert(format!("n{:02}", i), data);
+            }
+            for (a, b, as_relation) in edges {
+                let (parent, child) = if acyclic { (a.min(b), a.max(b)) } else { (a, b) };
+                if acyclic && parent == child {
+                    continue;
+                }
+                let (parent, child) = (format!("n{:02}", parent), format!("n{:02}", child));
+                // Written directly, like data saved before links were checked.
+                if as_relation {
+                    graph.relations.entry("depends".to_string()).or_default().push(Relation { parent, child });
+                } else {
+                    graph.nodes[&parent]["children"].as_array_mut().unwrap().push(json!(child));
+                }
+            }
+            graph
+        })
+}
+
+proptest! {
+    #[test]
+    fn dags_sort_children_first_and_rank_by_best_descendant(graph in graphs(true)) {
+        let sorted = toposort::toposort(&graph);
+        prop_assert_eq!(sorted.len(), graph.nodes.len());
+        let index: HashMap<_, _> = sorted.iter().enumerate().map(|(i, n)| (n.id.clone(), i)).collect();
+        for (parent, children) in toposort::children(&graph) {
+            for child in children {
+                prop_assert!(index[child] < index[parent]);
+                let (p, c) = (&sorted[index[parent]], &sorted[index[child]]);
+                prop_assert_ne!(compare_vecs(&p.max_vec, &c.max_vec), Ordering::Greater);
+                prop_assert!(c.parents.contains(&p.id));
+            }
+        }
+
+        let ranked = toposort::sorted(&graph);
+        for pair in ranked.nodes.windows(2) {
+            prop_assert_ne!(compare_vecs(&pair[0].max_vec, &pair[1].max_vec), Ordering::Greater);
+        }
+        let rank: HashMap<_, _> = ranked.nodes.iter().enumerate().map(|(i, n)| (n.id.clone(), i)).collect();
+        let mut last = None;
+        for (parent, child) in &ranked.edges {
+            let key = (rank[parent], rank[child]);
+            prop_assert!(last < Some(key));
+            last = Some(key);
+        }
+    }
+
+    #[test]
+    fn cycles_keep_every_node_once(graph in graphs(false)) {
+        let sorted = toposort::toposort(&graph);
+        let mut ids: Vec<_> = sorted.iter().map(|n| n.id.clone()).collect();
+        ids.sort();
+        prop_assert_eq!(ids, graph.nodes.keys().cloned().collect::<Vec<_>>());
+        let edges = toposort::sorted(&graph).edges;
+        prop_assert!(edges.len() <= toposort::children(&graph).values().map(Vec::len).sum::<usize>());
+    }
+
+    #[test]
+    fn links_never_close_a_cycle(graph in graphs(false)) {
+        let cycles = toposort::cycles(&graph);
+        let mut rebuilt = graph.clone();
+        rebuilt.relations.clear();
+        for data in rebuilt.nodes.values_mut() {
+            data["children"] = json!([]);
+        }
+        let mut rejected = false;
+        for (parent, children) in toposort::children(&graph) {
+            for child in children {
+                match toposort::link(&mut rebuilt, parent, child) {
+                    Ok(_) => {}
+                    Err(GraphError::Cycle { path }) => {
+                        rejected = true;
+                        prop_assert_eq!(path.first(), Some(&parent.to_string()));
+                        prop_assert_eq!(path.last(), Some(&parent.to_string()));
+                        for step in path.windows(2) {
+                            prop_assert!(toposort::children(&rebuilt)[step[0].as_str()].contains(&step[1].as_str())
+                                || step == [parent, child]);
+                        }
+                    }
+                    Err(err) => prop_assert!(false, "{}", err),
+                }
+            }
+        }
+        prop_assert!(toposort::cycles(&rebuilt).is_empty());
+        prop_assert_eq!(rejected, !cycles.is_empty());
+        for cycle in cycles {
+            prop_assert_eq!(cycle.path.first(), cycle.path.last());
+            prop_assert!(cycle.path.iter().all(|id| cycle.nodes.contains(id)));
+        }
+    }
+}
+
+#[test]
+fn matches_the_frontend_ordering() {
+    let mut graph = Graph::default();
+    graph.nodes.insert("goal".to_string(), node(1, &["old", "new"]));
+    graph.nodes.insert("old".to_string(), node(2, &[]));
+    graph.nodes.insert("new".to_string(), json!({ "value": "", "createdAt": "1970-01-01T00:00:00.003Z" }));
+    graph.nodes.insert("urgent".to_string(), json!({ "value": "", "createdAt": 0, "priority": 1 }));
+    graph.nodes.insert("done".to_string(), json!({ "value": "", "createdAt": 9, "status": "done" }));
+
+    let sorted = toposort::sorted(&graph);
+    let ids: Vec<_> = sorted.nodes.iter().map(|n| n.id.as_str()).collect();
+    assert_eq!(ids, ["urgent", "new", "goal", "old", "done"]);
+    assert_eq!(sorted.nodes[2].max_vec, [0.0, 0.0, -3.0, 3.0]);
+    assert_eq!(sorted.nodes[1].parents, ["goal"]);
+    assert_eq!(
+        sorted.edges,
+        [("goal".to_string(), "new".to_string()), ("goal".to_string(), "old".to_string())]
+    );
+}
+
+fn fixture(name: &str) -> serde_json::Value {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// The expected output comes from the frontend's own code; see `toposort-parity.mjs`.
+/// Several nodes tie on every score, so this also checks that ties keep insertion order.
+#[test]
+fn ties_break_in_insertion_order_like_the_frontend() {
+    let graph: Graph = serde_json::from_value(fixture("toposort-parity-input.json")).unwrap();
+    let expected = fixture("toposort-parity-expected.json");
+    let expected_edges: Vec<(String, String)> = serde_json::from_value(expected["edges"].clone()).unwrap();
+
+    let mut db = Db::open_in_memory().unwrap();
+    db.replace_graph(&graph).unwrap();
+    for graph in [graph, db.graph().unwrap()] {
+        let sorted = toposort::sorted(&graph);
+        let ids: Vec<_> = sorted.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, expected["order"].as_array().unwrap().iter().map(|id| id.as_str().unwrap()).collect::<Vec<_>>());
+        assert_eq!(sorted.edges, expected_edges);
+    }
+}
+
+#[test]
+fn mutations_follow_the_state_manager() {
+    let mut graph = Graph::default();
+    let now = chrono::Utc::now();
+    toposort::add(&mut graph, "a", now, None).unwrap();
+    toposort::add(&mut graph, "b", now, Some(("a", Connection::Child))).unwrap();
+    toposort::add(&mut graph, "c", now, Some(("a", Connection::Parent))).unwrap();
+    let edge = |parent: &str, child: &str| Relation {
+        parent: parent.to_string(),
+        child: child.to_string(),
+    };
+    assert_eq!(graph.relations["depends"], [edge("a", "b"), edge("c", "a")]);
+    assert!(graph.nodes["a"].get("children").is_none());
+    assert!(!toposort::link(&mut graph, "a", "b").unwrap());
+    assert!(toposort::add(&mut graph, "d", now, Some(("missing", Connection::Child))).is_err());
+    assert!(!graph.nodes.contains_key("d"));
+
+    toposort::set_status(&mut graph, "a", "active").unwrap();
+    assert_eq!(graph.nodes["a"]["status"], "active");
+    toposort::set_status(&mut graph, "a", "unset").unwrap();
+    assert!(graph.nodes["a"].get("status").is_none());
+    assert!(toposort::set_status(&mut graph, "a", "paused").is_err());
+}
+
+#[test]
+fn rejected_links_name_the_cycle() {
+    let mut graph = Graph::default();
+    for (id, children) in [("a", &["b"][..]), ("b", &["c"]), ("c", &[]), ("d", &["d", "e"]), ("e", &["d"])] {
+        graph.nodes.insert(id.to_string(), node(0, children));
+    }
+    let err = toposort::link(&mut graph, "c", "a").unwrap_err();
+    assert_eq!(err.to_string(), "this link would create a cycle: c → a → b → c");
+    assert!(matches!(toposort::link(&mut graph, "a", "a"), Err(GraphError::Cycle { path }) if path == ["a", "a"]));
+    assert!(graph.relations.is_empty());
+
+    let cycles = toposort::cycles(&graph);
+    assert_eq!(cycles.len(), 1);
+    assert_eq!(cycles[0].nodes, ["d", "e"]);
+    assert_eq!(cycles[0].path, ["d", "d"]);
+    assert!(toposort::unlink(&mut graph, "d", "d").unwrap());
+    assert_eq!(toposort::cycles(&graph)[0].path, ["d", "e", "d"]);
+}
+
+#[test]
+fn replacing_the_graph_cannot_add_a_cycle() {
+    let mut before = Graph::default();
+    for (id, children) in [("a", &["b"][..]), ("b", &[]), ("c", &[]), ("d", &["e"]), ("e", &["d"])] {
+        before.nodes.insert(id.to_string(), node(0, children));
+    }
+    // The `d ⇄ e` cycle predates the replace, so it does not block unrelated edits.
+    let mut after = before.clone();
+    after.nodes["b"]["children"] = json!(["c"]);
+    toposort::check_new_edges(&before, &after).unwrap();
+
+    after.relations.insert(
+        "depends".to_string(),
+        vec![Relation {
+            parent: "c".to_string(),
+            child: "a".to_string(),
+        }],
+    );
+    // Both `b → c` and `c → a` are new; the first one closing the loop is named.
+    let err = toposort::check_new_edges(&before, &after).unwrap_err();
+    assert_eq!(err.to_string(), "this link would create a cycle: b → c → a → b");
+}
@@ -0,0 +1,184 @@
+use clippy_app::db::{ActivityKind, ActivityRow, Db, Graph, Relation};
+use clippy_app::toposort::{self, Connection};
+use clippy_app::undo::{self, Op, UndoLog, GROUP_TIMEOUT_MS};
+use serde_json::json;
+
+fn edit(db: &Db, log: &mut UndoLog, label: &str, change: impl FnOnce(&mut Graph)) {
+    edit_as(db, log, "main", label, 0, change);
+}
+
+/// Applies `change` and records it for `caller`, like `update_graph` in the app.
+fn edit_as(db: &Db, log: &mut UndoLog, caller: &str, label: &str, now: i64, change: impl FnOnce(&mut Graph)) {
+    let ((), ops) = undo::change_graph(db, |graph| {
+        change(graph);
+        Ok(())
+    })
+    .unwrap();
+    log.record(db, caller, label, ops, now).unwrap();
+}
+
+fn ids(db: &Db) -> Vec<String> {
+    db.graph().unwrap().nodes.keys().cloned().collect()
+}
+
+fn depends(db: &Db) -> Vec<Relation> {
+    db.graph().unwrap().relations.get("depends").cloned().unwrap_or_default()
+}
+
+#[test]
+fn groups_undo_and_redo_in_one_step() {
+    let db = Db::open_in_memory().unwrap();
+    let mut log = UndoLog::new(10);
+    let now = chrono::Utc::now();
+    edit(&db, &mut log, "add node", |g| toposort::add(g, "goal", now, None).unwrap());
+
+    log.begin_group("main", "add child and link", 0);
+    edit(&db, &mut log, "add node", |g| toposort::add(g, "task", now, None).unwrap());
+    edit(&db, &mut log, "link", |g| {
+        toposort::link(g, "goal", "task").unwrap();
+    });
+    log.end_group("main");
+    edit(&db, &mut log, "set status", |g| toposort::set_status(g, "task", "done").unwrap());
+    assert_eq!(log.status(&db).unwrap().undo.as_deref(), Some("set status"));
+
+    assert_eq!(log.undo(&db).unwrap().unwrap().label, "set status");
+    assert!(db.graph().unwrap().nodes["task"].get("status").is_none());
+    assert_eq!(log.undo(&db).unwrap().unwrap().label, "add child and link");
+    assert_eq!((ids(&db), depends(&db)), (vec!["goal".to_string()], vec![]));
+
+    let status = log.status(&db).unwrap();
+    assert_eq!((status.undo.as_deref(), status.redo.as_deref()), (Some("add node"), Some("add child and link")));
+    log.redo(&db).unwrap();
+    assert_eq!(depends(&db).len(), 1);
+
+    // A new change drops what was left to redo.
+    edit(&db, &mut log, "add parent", |g| {
+        toposort::add(g, "area", now, Some(("goal", Connection::Parent))).unwrap()
+    });
+    assert_eq!(log.status(&db).unwrap().redo, None);
+    assert!(log.redo(&db).unwrap().is_none());
+}
+
+#[test]
+fn groups_belong_to_one_caller() {
+    let db = Db::open_in_memory().unwrap();
+    let mut log = UndoLog::new(10);
+    let now = chrono::Utc::now();
+    log.begin_group("main", "delete", 0);
+    edit_as(&db, &mut log, "main", "add node", 0, |g| toposort::add(g, "a", now, None).unwrap());
+    edit_as(&db, &mut log, "other", "add node", 0, |g| toposort::add(g, "b", now, None).unwrap());
+    edit_as(&db, &mut log, "main", "add node", 0, |g| toposort::add(g, "c", now, None).unwrap());
+    log.end_group("main");
+
+    // The other window's change stays its own step, splitting the group in two.
+    for (label, left) in [("delete", vec!["a", "b"]), ("add node", vec!["a"]), ("delete", vec![])] {
+        assert_eq!(log.undo(&db).unwrap().unwrap().label, label);
+        assert_eq!(ids(&db), left);
+    }
+}
+
+#[test]
+fn open_groups_are_stored_and_time_out() {
+    let path = std::env::temp_dir().join(format!("intero-undo-group-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let now = chrono::Utc::now();
+    {
+        let db = Db::open(&path).unwrap();
+        let mut log = UndoLog::new(10);
+        log.begin_group("main", "paste", 0);
+        edit_as(&db, &mut log, "main", "add node", 0, |g| toposort::add(g, "a", now, None).unwrap());
+        edit_as(&db, &mut log, "main", "add node", 1000, |g| toposort::add(g, "b", now, None).unwrap());
+        // Never ended, so it closes by itself.
+        edit_as(&db, &mut log, "main", "add node", GROUP_TIMEOUT_MS, |g| {
+            toposort::add(g, "c", now, None).unwrap()
+        });
+    }
+
+    // Quit without ending the group: what it had is still one step.
+    let db = Db::open(&path).unwrap();
+    let mut log = UndoLog::new(10);
+    assert_eq!(log.undo(&db).unwrap().unwrap().label, "add node");
+    assert_eq!(log.undo(&db).unwrap().unwrap().label, "paste");
+    assert!(ids(&db).is_empty());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn legacy_children_cannot_add_a_cycle() {
+    let db = Db::open_in_memory().unwrap();
+    let mut log = UndoLog::new(10);
+    edit(&db, &mut log, "add node", |g| {
+        toposort::add(g, "goal", chrono::Utc::now(), None).unwrap();
+        toposort::add(g, "task", chrono::Utc::now(), Some(("goal", Connection::Child))).unwrap();
+    });
+    let before = db.graph().unwrap();
+
+    // What `db_put_node` does with data an old frontend sent.
+    let err = undo::change_graph(&db, |g| {
+        let mut task = g.nodes["task"].clone();
+        task["children"] = json!(["goal"]);
+        g.nodes.insert("task".to_string(), task);
+        Ok(())
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "this link would create a cycle: task → goal → task");
+    let after = db.graph().unwrap();
+    assert_eq!((after.nodes, after.relations), (before.nodes, before.relations));
+    assert_eq!(log.status(&db).unwrap().undo.as_deref(), Some("add node"));
+}
+
+#[test]
+fn deleting_a_node_comes_back_with_its_edges() {
+    let db = Db::open_in_memory().unwrap();
+    let mut log = UndoLog::new(10);
+    let now = chrono::Utc::now();
+    edit(&db, &mut log, "add node", |g| {
+        toposort::add(g, "goal", now, None).unwrap();
+        toposort::add(g, "task", now, Some(("goal", Connection::Child))).unwrap();
+    });
+    let before = db.graph().unwrap();
+    edit(&db, &mut log, "delete node", |g| {
+        g.nodes.remove("goal");
+    });
+    assert!(depends(&db).is_empty());
+    log.undo(&db).unwrap();
+    let after = db.graph().unwrap();
+    assert_eq!((after.nodes, after.relations), (before.nodes, before.relations));
+}
+
+#[test]
+fn history_is_bounded_and_survives_restarts() {
+    let path = std::env::temp_dir().join(format!("intero-undo-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    {
+        let db = Db::open(&path).unwrap();
+        let mut log = UndoLog::new(2);
+        for id in ["a", "b", "c"] {
+            edit(&db, &mut log, id, |g| toposort::add(g, id, chrono::Utc::now(), None).unwrap());
+        }
+        let row = ActivityRow {
+            id: None,
+            activity_id: "a".to_string(),
+            kind: ActivityKind::Start,
+            created_at: 1,
+            end_time: None,
+        };
+        let id = db.log_activity(&row).unwrap();
+        let op = Op::InsertActivity {
+            row: ActivityRow { id, ..row },
+        };
+        log.record(&db, "main", "log activity", vec![op], 0).unwrap();
+    }
+
+    let db = Db::open(&path).unwrap();
+    let mut log = UndoLog::new(2);
+    assert_eq!(log.undo(&db).unwrap().unwrap().label, "log activity");
+    assert!(db.activity(None, None).unwrap().is_empty());
+    assert_eq!(log.undo(&db).unwrap().unwrap().label, "c");
+    assert!(log.undo(&db).unwrap().is_none());
+    assert_eq!(ids(&db), ["a", "b"]);
+    log.redo(&db).unwrap();
+    log.redo(&db).unwrap();
+    assert_eq!(db.activity(None, None).unwrap().len(), 1);
+    std::fs::remove_file(&path).unwrap();
+}
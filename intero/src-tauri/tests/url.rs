@@ -0,0 +1,8 @@
+use clippy_app::url::url_host;
+
+#[test]
+fn reads_hosts_from_urls() {
+    assert_eq!(url_host("https://user@www.example.com:8080/path?q#f"), Some("www.example.com"));
+    assert_eq!(url_host("example.com/path"), Some("example.com"));
+    assert_eq!(url_host("https:///path"), None);
+}
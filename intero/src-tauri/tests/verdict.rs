@@ -0,0 +1,65 @@
+use clippy_app::verdict::{parse, Category, ParseKind};
+
+#[test]
+fn strict_json() {
+    let parsed = parse(r#"{"description": "An editor", "activity": "work", "reason": "Coding", "confidence": 0.9}"#);
+    assert_eq!(parsed.kind, ParseKind::Json);
+    assert_eq!(parsed.category, Category::Work);
+    assert_eq!(parsed.confidence, Some(0.9));
+    assert_eq!(parsed.description, "An editor");
+    assert_eq!(parsed.reason, "Coding");
+}
+
+#[test]
+fn fenced_json() {
+    let parsed = parse("```json\n{\"description\": \"Twitter\", \"activity\": \"Distraction\", \"reason\": \"Social media\"}\n```");
+    assert_eq!(parsed.kind, ParseKind::FencedJson);
+    assert_eq!(parsed.category, Category::Distraction);
+    assert_eq!(parsed.confidence, None);
+}
+
+#[test]
+fn json_among_prose() {
+    let parsed = parse("Here is my answer: {\"activity\": \"unknown\", \"reason\": \"Switching windows\"} Hope that helps!");
+    assert_eq!(parsed.kind, ParseKind::FencedJson);
+    assert_eq!(parsed.category, Category::Unknown);
+    assert_eq!(parsed.reason, "Switching windows");
+}
+
+#[test]
+fn truncated_json() {
+    let parsed = parse("```json\n{\"description\": \"A terminal running \\\"cargo test\\\"\", \"activity\": \"work\", \"confidence\": 85, \"reason\": \"The user is runn");
+    assert_eq!(parsed.kind, ParseKind::PartialJson);
+    assert_eq!(parsed.category, Category::Work);
+    assert_eq!(parsed.confidence, Some(0.85));
+    assert_eq!(parsed.description, "A terminal running \"cargo test\"");
+    assert_eq!(parsed.reason, "The user is runn");
+}
+
+#[test]
+fn free_text_label() {
+    let parsed = parse("Work - the user is editing a spreadsheet.");
+    assert_eq!(parsed.kind, ParseKind::Label);
+    assert_eq!(parsed.category, Category::Work);
+
+    let parsed = parse("This looks like a distraction: a YouTube video about work from home.");
+    assert_eq!(parsed.category, Category::Distraction);
+}
+
+#[test]
+fn refusal_is_not_a_distraction() {
+    let parsed = parse("I'm sorry, but I can't help with identifying what is on this screen.");
+    assert_eq!(parsed.kind, ParseKind::Refusal);
+    assert_eq!(parsed.category, Category::Unknown);
+}
+
+#[test]
+fn unrecognized() {
+    let parsed = parse("A screenshot of a desktop.");
+    assert_eq!(parsed.kind, ParseKind::Unrecognized);
+    assert_eq!(parsed.category, Category::Unknown);
+
+    let parsed = parse(r#"{"activity": "gaming"}"#);
+    assert_eq!(parsed.kind, ParseKind::Json);
+    assert_eq!(parsed.category, Category::Unknown);
+}
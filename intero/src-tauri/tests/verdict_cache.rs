@@ -0,0 +1,119 @@
+use clippy_app::foreground::ForegroundInfo;
+use clippy_app::prompt::Prompt;
+use clippy_app::verdict::{Category, ParseKind, Verdict};
+use clippy_app::verdict_cache::{self, CacheConfig, PerceptualHash, VerdictCache};
+use image::{GrayImage, Luma};
+
+fn verdict(model: &str) -> Verdict {
+    Verdict {
+        category: Category::Work,
+        confidence: None,
+        description: "an editor".to_string(),
+        reason: String::new(),
+        parse: ParseKind::Json,
+        raw: String::new(),
+        model: model.to_string(),
+        latency_ms: 900,
+        foreground: None,
+        prompt_version: None,
+    }
+}
+
+/// A screen-sized gradient with a bright box at `x`.
+fn screen(x: u32) -> GrayImage {
+    GrayImage::from_fn(640, 400, |px, py| {
+        if (x..x + 160).contains(&px) && (100..300).contains(&py) {
+            Luma([255])
+        } else {
+            Luma([((px + py) / 5) as u8])
+        }
+    })
+}
+
+#[test]
+fn small_edits_barely_change_the_hash() {
+    let base = PerceptualHash::of_image(&screen(100));
+    let mut edited = screen(100);
+    for x in 300..310 {
+        edited.put_pixel(x, 20, Luma([0]));
+    }
+    assert!(base.distance(&PerceptualHash::of_image(&edited)) <= 2);
+    assert!(base.distance(&PerceptualHash::of_image(&screen(400))) > 8);
+}
+
+#[test]
+fn reuses_verdicts_for_the_same_screen_and_key() {
+    let mut cache = VerdictCache::new(CacheConfig::default());
+    let hash = PerceptualHash::of_image(&screen(100));
+    assert_eq!(cache.get(&hash, "ollama:llava", 0), None);
+    cache.insert(hash, "ollama:llava".to_string(), 0, verdict("ollama:llava"));
+
+    assert_eq!(cache.get(&hash, "ollama:llava", 1_000), Some(verdict("ollama:llava")));
+    assert_eq!(cache.get(&hash, "openai:gpt-4o", 1_000), None);
+    assert_eq!(cache.get(&PerceptualHash::of_image(&screen(400)), "ollama:llava", 1_000), None);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (1, 3, 1));
+}
+
+#[test]
+fn verdicts_expire() {
+    let config = CacheConfig {
+        ttl_ms: 60_000,
+        ..CacheConfig::default()
+    };
+    let mut cache = VerdictCache::new(config);
+    let hash = PerceptualHash::of_image(&screen(100));
+    cache.insert(hash, "ollama:llava".to_string(), 0, verdict("ollama:llava"));
+    assert!(cache.get(&hash, "ollama:llava", 59_999).is_some());
+    assert!(cache.get(&hash, "ollama:llava", 60_000).is_none());
+    assert_eq!(cache.stats().entries, 0);
+}
+
+#[test]
+fn keys_tell_apart_what_was_in_front() {
+    let app = |app_id: &str, url: Option<&str>| ForegroundInfo {
+        app_id: app_id.to_string(),
+        app_name: app_id.to_string(),
+        pid: Some(1),
+        title: Some("Home".to_string()),
+        url: url.map(str::to_string),
+    };
+    let prompt = Prompt::default();
+    let key = |foreground: Option<&ForegroundInfo>| verdict_cache::cache_key("ollama:llava", &prompt, foreground);
+    let docs = app("com.google.Chrome", Some("https://docs.rs"));
+    let video = app("com.google.Chrome", Some("https://youtube.com"));
+
+    assert_eq!(key(Some(&docs)), key(Some(&ForegroundInfo { pid: Some(2), ..docs.clone() })));
+    assert_ne!(key(Some(&docs)), key(Some(&video)));
+    assert_ne!(key(Some(&docs)), key(Some(&app("com.apple.Safari", Some("https://docs.rs")))));
+    assert_ne!(key(Some(&docs)), key(None));
+
+    let mut cache = VerdictCache::new(CacheConfig::default());
+    let hash = PerceptualHash::of_image(&screen(100));
+    cache.insert(hash, key(Some(&docs)), 0, verdict("ollama:llava"));
+    assert!(cache.get(&hash, &key(Some(&video)), 1_000).is_none());
+}
+
+#[test]
+fn title_and_path_changes_still_hit_the_cache() {
+    let docs = ForegroundInfo {
+        app_id: "com.google.Chrome".to_string(),
+        app_name: "Google Chrome".to_string(),
+        pid: Some(1),
+        title: Some("serde - Rust".to_string()),
+        url: Some("https://docs.rs/serde".to_string()),
+    };
+    let retitled = ForegroundInfo {
+        title: Some("(1) serde - Rust".to_string()),
+        url: Some("https://docs.rs/serde/latest/serde/#modules".to_string()),
+        ..docs.clone()
+    };
+    let prompt = Prompt::default();
+    let key = |foreground: &ForegroundInfo| verdict_cache::cache_key("ollama:llava", &prompt, Some(foreground));
+
+    let mut cache = VerdictCache::new(CacheConfig::default());
+    let hash = PerceptualHash::of_image(&screen(100));
+    cache.insert(hash, key(&docs), 0, verdict("ollama:llava"));
+    assert_eq!(cache.get(&hash, &key(&retitled), 1_000), Some(verdict("ollama:llava")));
+}
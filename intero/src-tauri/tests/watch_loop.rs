@@ -0,0 +1,91 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::watch;
+use tokio::time::{self, Instant};
+
+use clippy_app::verdict::Verdict;
+use clippy_app::watch_loop::{classify_changes, WatchConfig};
+
+const CONFIG: WatchConfig = WatchConfig {
+    capture_interval_ms: 500,
+    debounce_ms: 1000,
+    max_wait_ms: 5000,
+    stale_after_ms: 3000,
+};
+
+fn verdict() -> Verdict {
+    serde_json::from_value(json!({
+        "category": "work",
+        "confidence": null,
+        "description": "",
+        "reason": "",
+        "parse": "json",
+        "raw": "",
+        "model": "test",
+        "latency_ms": 0,
+    }))
+    .unwrap()
+}
+
+/// Sends `(at_ms, frame_id)` in order, then closes the channel after `end_ms`.
+/// Returns when each classification started and, for the ones that finished,
+/// when they did, both in ms from the start.
+async fn run(frames: &[(u64, u64)], end_ms: u64, classify_ms: u64) -> (Vec<(u64, u64)>, Vec<(u64, u64)>) {
+    let start = Instant::now();
+    let elapsed = move || start.elapsed().as_millis() as u64;
+    let started = RefCell::new(vec![]);
+    let finished = RefCell::new(vec![]);
+    let (sender, receiver) = watch::channel(None);
+    let send = async move {
+        for &(at, id) in frames {
+            time::sleep_until(start + Duration::from_millis(at)).await;
+            sender.send_replace(Some(id));
+        }
+        time::sleep_until(start + Duration::from_millis(end_ms)).await;
+    };
+    let classify = classify_changes(
+        receiver,
+        || CONFIG,
+        |id| {
+            started.borrow_mut().push((elapsed(), id));
+            async move {
+                time::sleep(Duration::from_millis(classify_ms)).await;
+                Ok(verdict())
+            }
+        },
+        |event| finished.borrow_mut().push((elapsed(), event.frame_id)),
+    );
+    tokio::join!(send, classify);
+    (started.into_inner(), finished.into_inner())
+}
+
+#[tokio::test(start_paused = true)]
+async fn classifies_once_the_screen_settles() {
+    let (started, finished) = run(&[(0, 1), (400, 2), (800, 3)], 10_000, 100).await;
+    assert_eq!(started, [(1800, 3)]);
+    assert_eq!(finished, [(1900, 3)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn a_screen_that_keeps_changing_waits_at_most_max_wait() {
+    let frames: Vec<_> = (0..30).map(|i| (i * 400, i)).collect();
+    let (started, _) = run(&frames, 20_000, 100).await;
+    // Counted from the first change, and again from the first change after that.
+    assert_eq!(started, [(5000, 12), (10_200, 25), (12_600, 29)]);
+}
+
+#[tokio::test(start_paused = true)]
+async fn only_stale_classifications_are_cancelled() {
+    // A newer frame arrives 1s in, the classification finishes 1s later.
+    let (started, finished) = run(&[(0, 1), (2000, 2)], 10_000, 2000).await;
+    assert_eq!(started, [(1000, 1), (3000, 2)]);
+    assert_eq!(finished, [(3000, 1), (5000, 2)]);
+
+    // Still running `stale_after_ms` after the newer frame: cancelled, the newer frame
+    // goes next once it has settled.
+    let (started, finished) = run(&[(0, 1), (2000, 2)], 20_000, 5000).await;
+    assert_eq!(started, [(1000, 1), (5000, 2)]);
+    assert_eq!(finished, [(10_000, 2)]);
+}
@@ -0,0 +1,52 @@
+#![cfg(target_os = "linux")]
+
+use clippy_app::frame_source::FrameSource;
+use clippy_app::x11_source::X11Source;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ChangeGCAux, ConnectionExt as _, CreateGCAux, Rectangle, SubwindowMode};
+
+const RED: u32 = 0xff0000;
+const BLUE: u32 = 0x0000ff;
+
+/// Paints the left half of the root window red and the right half blue, then checks
+/// the captured frame. Needs a bare X server with a 24-bit TrueColor visual and no
+/// windows on top, e.g.:
+///
+///     xvfb-run -s "-screen 0 320x200x24" cargo test --test x11_source -- --ignored
+#[test]
+#[ignore = "needs an X server such as Xvfb"]
+fn captures_what_is_drawn_on_the_root_window() {
+    if std::env::var("DISPLAY").unwrap_or_default().is_empty() {
+        eprintln!("DISPLAY is not set, skipping");
+        return;
+    }
+    let (conn, screen) = x11rb::connect(None).unwrap();
+    let root = conn.setup().roots[screen].root;
+    let (width, height) = {
+        let screen = &conn.setup().roots[screen];
+        (screen.width_in_pixels, screen.height_in_pixels)
+    };
+    let gc = conn.generate_id().unwrap();
+    let aux = CreateGCAux::new().foreground(RED).subwindow_mode(SubwindowMode::INCLUDE_INFERIORS);
+    conn.create_gc(gc, root, &aux).unwrap();
+    let half = width / 2;
+    conn.poly_fill_rectangle(root, gc, &[Rectangle { x: 0, y: 0, width: half, height }]).unwrap();
+    conn.change_gc(gc, &ChangeGCAux::new().foreground(BLUE)).unwrap();
+    conn.poly_fill_rectangle(root, gc, &[Rectangle { x: half as i16, y: 0, width: width - half, height }])
+        .unwrap();
+    // A round trip makes sure the server has drawn before we grab.
+    conn.get_input_focus().unwrap().reply().unwrap();
+
+    let source = X11Source::new(None);
+    source.start().unwrap();
+    let frame = source.latest_frame().unwrap();
+    assert_eq!((frame.width, frame.height), (width as u32, height as u32));
+    let pixels = frame.to_rgba();
+    let y = height as u32 / 2;
+    assert_eq!(pixels.get_pixel(half as u32 / 2, y).0, [255, 0, 0, 255]);
+    assert_eq!(pixels.get_pixel(half as u32 + half as u32 / 2, y).0, [0, 0, 255, 255]);
+    // Both sides of the seam, to catch an off-by-one row stride.
+    assert_eq!(pixels.get_pixel(half as u32 - 1, height as u32 - 1).0, [255, 0, 0, 255]);
+    assert_eq!(pixels.get_pixel(half as u32, 0).0, [0, 0, 255, 255]);
+    source.stop();
+}
@@ -1,4 +1,4 @@
-import { ToposorterStateManagerContext } from "./ToposorterState";
+import { SetErrorContext, ToposorterStateManagerContext } from "./ToposorterState";
 import ReactFlow, {
   Controls,
   applyNodeChanges,
@@ -37,6 +37,7 @@ export function Canvas() {
   const canvasManager = useContext(CanvasManagerContext)!;
 
   const stateManager = useContext(ToposorterStateManagerContext)!;
+  const setError = useContext(SetErrorContext)!;
   const nodesInitialized = useNodesInitialized();
 
   const [, setSelectedNode] = useSelectedNode();
@@ -46,28 +47,32 @@ export function Canvas() {
   const onNodesChange: OnNodesChange = useCallback(
     (changes) =>
       canvasManager.setNodes((nds) => {
-        for (const change of changes) {
-          // Propagate canvas deletions to state deletions.
-          if (change.type === "remove") {
-            stateManager.deleteNode(change.id);
-          }
+        // Propagate canvas deletions to state deletions.
+        const removed = changes.flatMap((change) => (change.type === "remove" ? [change.id] : []));
+        if (removed.length > 0) {
+          stateManager.deleteNodes(removed).catch((e) => {
+            console.error(e);
+            setError(e);
+          });
         }
         return applyNodeChanges(changes, nds);
       }),
-    [canvasManager.setNodes, nodesInitialized, setSelectedNode, uiState]
+    [canvasManager.setNodes, nodesInitialized, setSelectedNode, uiState, stateManager, setError]
   );
 
   const onEdgesChange: OnEdgesChange = useCallback(
     (changes) =>
       canvasManager.setEdges((eds) => {
-        for (const change of changes) {
-          if (change.type === "remove") {
-            stateManager.deleteEdge(change.id);
-          }
+        const removed = changes.flatMap((change) => (change.type === "remove" ? [change.id] : []));
+        if (removed.length > 0) {
+          stateManager.deleteEdges(removed).catch((e) => {
+            console.error(e);
+            setError(e);
+          });
         }
         return applyEdgeChanges(changes, eds);
       }),
-    [canvasManager.setEdges]
+    [canvasManager.setEdges, stateManager, setError]
   );
 
   const onConnect = useCallback(
@@ -77,9 +82,12 @@ export function Canvas() {
       if (!fromId || !toId) {
         return;
       }
-      stateManager?.addEdge(fromId, toId);
+      stateManager?.addEdge(fromId, toId).catch((e) => {
+        console.error(e);
+        setError(e);
+      });
     },
-    [stateManager]
+    [stateManager, setError]
   );
 
   return (
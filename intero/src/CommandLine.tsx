@@ -12,9 +12,8 @@ import { useSelectedNode } from "./Selection";
 import { UIStateContext } from "./ui_state";
 import { CanvasManager, CanvasManagerContext } from "./canvas_controller";
 import { ActionManager, ActionManagerContext } from "./action_manager";
-import { BaseDirectory, createDir, writeBinaryFile } from "@tauri-apps/api/fs";
-import { appDataDir } from "@tauri-apps/api/path";
 import { SearchContainer, SearchInput } from "./Box";
+import * as db from "./db";
 import { BoolOptionsObj, HideObj, PreferencesManager, PreferencesManagerContext } from "./preference_state";
 
 class ArgType<_T> {
@@ -53,7 +52,7 @@ class Command<A extends ArgsShape> {
           actionManager: ActionManager;
           preferencesManager: PreferencesManager;
         }
-      ): void;
+      ): void | Promise<void>;
     }
   ) {}
 }
@@ -72,8 +71,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { stateManager }) {
-        stateManager.deleteNode(args.subject);
+      async runCommand(args, { stateManager }) {
+        await stateManager.deleteNode(args.subject);
       },
     }),
     new Command({
@@ -82,8 +81,8 @@ const commands = Object.fromEntries(
         subject: ArgType.Id,
         object: ArgType.parentOrChild,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.add(args.subject, args.object);
+      async runCommand(args, { actionManager }) {
+        await actionManager.add(args.subject, args.object);
       },
     }),
     new Command({
@@ -92,8 +91,8 @@ const commands = Object.fromEntries(
         subject: ArgType.Id,
         object: ArgType.Id,
       },
-      runCommand(args, { stateManager }) {
-        stateManager.addEdge(args.subject, args.object);
+      async runCommand(args, { stateManager }) {
+        await stateManager.addEdge(args.subject, args.object);
       },
     }),
     new Command({
@@ -102,8 +101,8 @@ const commands = Object.fromEntries(
         subject: ArgType.Id,
         object: ArgType.string,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setStatus(args.subject, args.object);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setStatus(args.subject, args.object);
       },
     }),
     new Command({
@@ -112,8 +111,8 @@ const commands = Object.fromEntries(
         subject: ArgType.Id,
       },
 
-      runCommand(args, { actionManager }) {
-        actionManager.setStatus(args.subject, "unset");
+      async runCommand(args, { actionManager }) {
+        await actionManager.setStatus(args.subject, "unset");
       },
     }),
     new Command({
@@ -121,8 +120,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setStatus(args.subject, "active");
+      async runCommand(args, { actionManager }) {
+        await actionManager.setStatus(args.subject, "active");
       },
     }),
     new Command({
@@ -130,8 +129,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setStatus(args.subject, "done");
+      async runCommand(args, { actionManager }) {
+        await actionManager.setStatus(args.subject, "done");
       },
     }),
     new Command({
@@ -139,8 +138,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPriority(args.subject, 1);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPriority(args.subject, 1);
       },
     }),
     new Command({
@@ -148,8 +147,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPriority(args.subject, 1);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPriority(args.subject, 1);
       },
     }),
     new Command({
@@ -157,8 +156,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPriority(args.subject, 2);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPriority(args.subject, 2);
       },
     }),
     new Command({
@@ -166,8 +165,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPriority(args.subject, 3);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPriority(args.subject, 3);
       },
     }),
     new Command({
@@ -175,8 +174,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPriority(args.subject, 4);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPriority(args.subject, 4);
       },
     }),
     new Command({
@@ -184,8 +183,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPinned(args.subject, true);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPinned(args.subject, true);
       },
     }),
     new Command({
@@ -193,8 +192,8 @@ const commands = Object.fromEntries(
       argsShape: {
         subject: ArgType.Id,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setPinned(args.subject, false);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setPinned(args.subject, false);
       },
     }),
     new Command({
@@ -203,8 +202,8 @@ const commands = Object.fromEntries(
         subject: ArgType.Id,
         object: ArgType.string,
       },
-      runCommand(args, { actionManager }) {
-        actionManager.setType(args.subject, args.object);
+      async runCommand(args, { actionManager }) {
+        await actionManager.setType(args.subject, args.object);
       },
     }),
     new Command({
@@ -277,6 +276,20 @@ const commands = Object.fromEntries(
         canvasManager.layoutNodes();
       },
     }),
+    new Command({
+      command: "undo",
+      argsShape: {},
+      async runCommand(_args, { stateManager }) {
+        await stateManager.undo();
+      },
+    }),
+    new Command({
+      command: "redo",
+      argsShape: {},
+      async runCommand(_args, { stateManager }) {
+        await stateManager.redo();
+      },
+    }),
     new Command({
       command: "reload",
       argsShape: {},
@@ -288,20 +301,8 @@ const commands = Object.fromEntries(
       command: "backup",
       argsShape: {},
       async runCommand(_args, _ctx) {
-        const data = window.localStorage.getItem("toposorter");
-        if (data) {
-          const blob = new Blob([data], { type: "text/json" });
-          const fileName = `toposorter-backup-${new Date().toISOString()}.json`;
-          console.log(`Writing backup to ${fileName}`);
-          const appDataDirPath = await appDataDir();
-          await createDir(appDataDirPath, { recursive: true });
-          await writeBinaryFile(fileName, await blob.arrayBuffer(), {
-            dir: BaseDirectory.AppData,
-          });
-          alert("Backup written to " + appDataDirPath + fileName);
-        } else {
-          console.error("No data found in localStorage for 'toposorter'");
-        }
+        const path = await db.backup();
+        alert("Backup written to " + path);
       },
     }),
   ].map((command) => [command.data.command, command])
@@ -406,7 +407,7 @@ export function CommandLine() {
       for (const [i, arg] of args.entries()) {
         mapArgs[i](arg);
       }
-      command.data.runCommand(variables, {
+      const done = command.data.runCommand(variables, {
         actionManager,
         stateManager,
         canvasManager,
@@ -414,12 +415,19 @@ export function CommandLine() {
       });
       setError(null);
       setInput("");
+      // Backend commands fail after the input is cleared, e.g. a link that would
+      // create a cycle.
+      Promise.resolve(done).catch(onError);
     } catch (e: unknown) {
-      console.error(e);
-      setError(e as Error);
+      onError(e);
     }
   };
 
+  const onError = (e: unknown) => {
+    console.error(e);
+    setError(e as Error);
+  };
+
   const handleOnChange = (e: React.ChangeEvent<HTMLInputElement>) => {
     if (e.currentTarget) {
       setInput(e.currentTarget.value);
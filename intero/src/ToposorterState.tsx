@@ -1,7 +1,7 @@
-import { Draft, original, produce } from "immer";
+import { Draft, produce } from "immer";
 import * as React from "react";
-import { v4 as uuidv4 } from "uuid";
-import { useLocalStorageState, useMakeStateAsync } from "./state";
+import { useMakeStateAsync, useRefState } from "./state";
+import * as db from "./db";
 
 export type Id = string;
 
@@ -38,7 +38,7 @@ const memoizeFn = <T extends () => any>(fn: T): T => {
 }
 
 export class TNode {
-  constructor(readonly id: Id, readonly data: TNodeData, readonly ctx: Toposorter) {}
+  constructor(readonly id: Id, readonly data: TNodeData, readonly ctx: NodeLookup) {}
   children() {
     return this.data.children;
   }
@@ -99,70 +99,8 @@ export interface ToposorterStateData {
 }
 
 
-export interface NodeContext {
-  nodes(): Record<Id, TNodeData>;
-  getChildren(node: Id): Id[];
-}
-
-export class Toposorter {
-  private visited = new Set<Id>();
-  private readonly inputData: Record<Id, TNodeData>;
-  outputData: Record<Id, TNode> = {};
-
-  public getNode(id: Id): TNode {
-    return this.outputData[id];
-  }
-
-  private constructor(entries: [key: Id, value: TNodeData][]) {
-    this.inputData = Object.fromEntries(entries);
-  }
-
-  // Returns topologically sorted nodes.
-  static sort(entries: [key: Id, value: TNodeData][]): [key: Id, value: TNode][] {
-    return new Toposorter(entries).sort();
-  }
-
-  // Visit all nodes.
-  // Visit all of a node's children recursively before visiting a node.
-  // Then insertion order should be topologically sorted.
-  private sort(): [key: Id, value: TNode][] {
-    const nodes: Record<Id, TNode> = {};
-
-    for (let key of Object.keys(this.inputData)) {
-      this.visitChildren(key, nodes);
-    }
-
-    let entries = [...this.visited].map((x) => [x, nodes[x]]) as [Id, TNode][];
-    this.outputData = Object.fromEntries(entries);
-    return entries;
-  }
-
-  // DFS
-  private visitChildren(key: Id, nodes: Record<Id, TNode>) {
-    const visted = this.visited.has(key);
-    if (visted) {
-      return;
-    }
-
-    const data = this.inputData[key];
-    if (!data) {
-      return;
-    }
-
-    const node = new TNode(key, {...data}, this);
-    let maxVec = makeScoreVector(node);
-    for (let childId of node.children()) {
-      this.visitChildren(childId, nodes);
-      maxVec = chooseMaxVec(maxVec, nodes[childId].maxVec()!);
-      nodes[childId].parents().push(key);
-    }
-    nodes[key] = new TNode(key, {...this.inputData[key], __maxVec: maxVec, __parents: [] }, this);
-    this.visited.add(key);
-  }
-}
-
-function chooseMaxVec(vecA: number[], vecB: number[]): number[] {
-  return compareVecs(vecA, vecB) > 0 ? vecB : vecA;
+export interface NodeLookup {
+  getNode(id: Id): TNode;
 }
 
 export function compareVecs(vecA: number[], vecB: number[]): number {
@@ -177,19 +115,6 @@ export function compareVecs(vecA: number[], vecB: number[]): number {
   return 0;
 }
 
-/**
- * Higher score is better.
- */
-function makeScoreVector(node: TNode): number[] {
-  return [
-    node.pinned === true ? 1 : 0,
-    statusToPoints(node.status),
-    priorityToPoints(node.priority),
-    node.createdAt.getTime(),
-  ];
-}
-
-
 export function statusToPoints(status: Status): number {
   switch (status) {
     case "done":
@@ -221,10 +146,22 @@ export type TNodeRow = {
 export class ToposorterState {
   nodes: Record<Id, TNode>;
 
-  constructor(state: ToposorterStateData) {
-    let entries = Toposorter.sort(Object.entries(state.nodes));
-    entries = orderNodes(entries);
-    this.nodes = Object.fromEntries(entries);
+  // Nodes come ranked by the backend's `graph_sorted`.
+  constructor(graph: db.SortedGraph) {
+    this.nodes = {};
+    for (const node of graph.nodes) {
+      this.nodes[node.id] = new TNode(
+        node.id,
+        {
+          ...node.data,
+          createdAt: new Date(node.data.createdAt),
+          children: node.children,
+          __maxVec: node.max_vec,
+          __parents: node.parents,
+        },
+        this
+      );
+    }
   }
 
   getNode(id: Id): TNode{
@@ -302,43 +239,6 @@ export class ToposorterState {
   }
 }
 
-// Ranks nodes and rearranges children ordering
-// TODO: move ordering earlier
-function orderNodes(entries: [string, TNode][]): [string, TNode][] {
-  // rank nodes
-  let out = [...entries];
-  // out = Toposorter.sort(entries);
-  // let out = entries;
-  out = out.sort(([_keyA, a], [_keyB, b]) => {
-    return compareVecs(a.maxVec()!, b.maxVec()!);
-  });
-
-  // Re-order edges in place
-  const nodeIndicies = new Map<string, number>();
-  for (let [i, [id]] of out.entries()) {
-    nodeIndicies.set(id, i);
-  }
-
-  const newOut: [string, TNode][] = [];
-
-  for (let [i, node] of out) {
-    const newNode = node;
-    // TODO: move this logic to Toposorter
-
-    // const newNode = { ...node };
-    // newNode.children = [...node.children]
-    //   .filter((child) => nodeIndicies.has(child))
-    //   .sort((a, b) => {
-    //     const aIndex = nodeIndicies.get(a)!;
-    //     const bIndex = nodeIndicies.get(b)!;
-    //     return aIndex - bIndex;
-    //   });
-    newOut.push([i, newNode]);
-  }
-
-  return newOut;
-}
-
 export function orderEdges(entries: [Id, TNode][]): [parent: Id, child: Id][] {
   const edges: [Id, Id][] = [];
   let nodeIndicies = new Map<string, number>();
@@ -367,160 +267,150 @@ export function orderEdges(entries: [Id, TNode][]): [parent: Id, child: Id][] {
 }
 
 
-function withNormalization(fn: (state: ToposorterStateData) => ToposorterStateData): (state: ToposorterStateData) => ToposorterStateData {
-  return (state: ToposorterStateData) => {
-    const out = fn(state);
-    // const entries = Object.entries(state.nodes);
-    // const sortedEntries = Toposorter.sort(entries);
-    // const out = {
-    //   ...input,
-    //   nodes: Object.fromEntries(sortedEntries)
-    // };
-    return out;
-  };
-}
-
+// Every change goes through a backend command, which validates it and records it for
+// undo. Other windows pick it up from `db-changed`.
 export class ToposorterStateManager {
   constructor(
-    readonly trySetState: (
-      fn: (state: ToposorterStateData) => ToposorterStateData
-    ) => Promise<void>,
-    readonly stateRef: React.MutableRefObject<ToposorterStateData>
+    readonly reload: () => Promise<void>,
+    readonly stateRef: React.MutableRefObject<db.SortedGraph>
   ) {}
 
   state() {
     return new ToposorterState(this.stateRef.current);
   }
 
-  bindAction<Args extends any[]>(
-    action: (
-      ...args: Args
-    ) => (state: ToposorterStateData) => ToposorterStateData
-  ): (...args: Args) => Promise<void> {
-    return async (...args: Args) => {
-      await this.trySetState(action(...args));
-    };
+  // Changes run one at a time in the order they were made, so each starts from what
+  // the one before saved, and undo waits for every change made before it.
+  private queue: Promise<unknown> = Promise.resolve();
+
+  // Resolves once this window shows the change.
+  private run<T>(change: () => Promise<T>): Promise<T> {
+    const out = this.queue.then(async () => {
+      let out: T;
+      try {
+        out = await change();
+      } catch (e: unknown) {
+        throw this.toError(e);
+      }
+      await this.reload();
+      return out;
+    });
+    this.queue = out.catch(() => {});
+    return out;
   }
 
-  async add(from?: Id, connectionType?: "parent" | "child") {
-    const id = uuidv4();
-    await this.bindAction((from?: Id, connectionType?: "parent" | "child") => {
-      return produce((draft: ToposorterStateData) => {
-        draft.nodes[id] = {
-          value: "",
-          createdAt: new Date(),
-          children: [],
-        };
-        if (!connectionType || !from) {
-          return;
-        }
-        if (connectionType === "child") {
-          draft.nodes[from].children.push(id);
-        } else if (connectionType === "parent") {
-          draft.nodes[id].children.push(from);
-        } else {
-          throw new Error(`Invalid connectionType ${connectionType}`);
-        }
+  // Commands reject with a `GraphError` or a message; name nodes by their titles.
+  private toError(e: unknown): unknown {
+    if (typeof e === "string") {
+      return new Error(e);
+    }
+    if (!db.isGraphError(e)) {
+      return e;
+    }
+    const name = (id: Id) => this.state().getNode(id)?.value || id;
+    switch (e.kind) {
+      case "unknown_node":
+        return new Error(`No node with id ${e.id}`);
+      case "invalid_status":
+        return new Error(`Invalid status ${e.status}, expected active, done or unset`);
+      case "cycle":
+        return new Error(`This link would create a cycle: ${e.path.map(name).join(" → ")}`);
+      case "storage":
+        return new Error(`Could not save the graph: ${e.message}`);
+    }
+  }
+
+  // `label` names the change in the undo history.
+  private async updateNode(id: Id, label: string, update: (draft: Draft<db.NodeData>) => void) {
+    try {
+      await this.run(() => {
+        const next = produce(db.toNodeData(this.state().getNode(id).data), update);
+        return db.putNode(id, next, label);
       });
-    })(from, connectionType);
-    return id;
+    } catch (e: unknown) {
+      if (e instanceof AbortError) {
+        return;
+      }
+      throw e;
+    }
   }
 
-  deleteNode = this.bindAction((id: Id) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      const state = new ToposorterState(original(draft)!);
-      let nodes = state.getNodes();
+  add = async (from?: Id, connectionType?: "parent" | "child"): Promise<Id> => {
+    return await this.run(() => db.graphAdd(from, connectionType));
+  };
 
-      // Delete the node.
-      delete draft.nodes[id];
+  deleteNode = async (id: Id) => {
+    await this.run(() => db.deleteNode(id));
+  };
 
-      // Delete all edges to this node.
-      for (const key of Object.keys(nodes)) {
-        if (key === id) {
-          continue;
-        }
-        const newChildren = nodes[key].children().filter(
-          (childId) => childId !== id
-        );
-        draft.nodes[key].children = newChildren;
-      }
-    });
-  });
+  // Undone in one step.
+  deleteNodes = async (ids: Id[]) => {
+    const label = ids.length > 1 ? "delete nodes" : "delete node";
+    await this.run(() => db.historyGroup(label, () => Promise.all(ids.map(db.deleteNode))));
+  };
 
-  deleteEdge = this.bindAction((edgeId: Id) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
+  deleteEdges = async (edgeIds: Id[]) => {
+    const unlink = (edgeId: Id) => {
       const [from, to] = edgeId.split("--");
-      draft.nodes[from].children = draft.nodes[from].children.filter(
-        (x) => x !== to
-      );
-    });
-  });
+      return db.graphUnlink(from, to);
+    };
+    await this.run(() => db.historyGroup("unlink", () => Promise.all(edgeIds.map(unlink))));
+  };
 
-  addEdge = this.bindAction((from: Id, to: Id) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      draft.nodes[from].children.push(to);
-    });
-  });
+  undo = async () => {
+    await this.run(() => db.undo());
+  };
 
-  setStatus = this.bindAction((id: Id, status: string) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      if (status === "unset") {
-        delete draft.nodes[id].status;
-        return;
-      }
-      if (status !== "active" && status !== "done") {
-        throw new Error(
-          `Invalid status ${status}. Expected "active" or "done" or "unset"`
-        );
-      }
-      draft.nodes[id].status = status as "active" | "done";
-    });
-  });
+  redo = async () => {
+    await this.run(() => db.redo());
+  };
+
+  addEdge = async (from: Id, to: Id) => {
+    await this.run(() => db.graphLink(from, to));
+  };
+
+  setStatus = async (id: Id, status: string) => {
+    await this.run(() => db.graphSetStatus(id, status as "active" | "done" | "unset"));
+  };
 
-  setPinned = this.bindAction((id: Id, value: boolean) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      draft.nodes[id].pinned = value;
+  setPinned = (id: Id, value: boolean) =>
+    this.updateNode(id, value ? "pin" : "unpin", (draft) => {
+      draft.pinned = value;
     });
-  });
 
-  setType = this.bindAction((id: Id, type: string) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
+  setType = (id: Id, type: string) =>
+    this.updateNode(id, "set type", (draft) => {
       if (type !== "task" && type !== "goal" && type !== "project" && type !== 'problem') {
         throw new Error(`Invalid type ${type}.`);
       }
-      draft.nodes[id].type = type;
+      draft.type = type;
     });
-  });
 
-  setPriority = this.bindAction((id: Id, priority: number) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      if (draft.nodes[id].priority === priority) {
+  setPriority = (id: Id, priority: number) =>
+    this.updateNode(id, "set priority", (draft) => {
+      if (draft.priority === priority) {
         throw new AbortError();
       }
-      draft.nodes[id].priority = priority;
+      draft.priority = priority;
     });
-  });
 
-  setValue = this.bindAction((id: Id, value: string) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      if (draft.nodes[id].value === value) {
+  setValue = (id: Id, value: string) =>
+    this.updateNode(id, "rename", (draft) => {
+      if (draft.value === value) {
         throw new AbortError();
       }
-      draft.nodes[id].value = value;
+      draft.value = value;
     });
-  });
 
-  setNotes = this.bindAction((id: Id, notes: string) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      draft.nodes[id].notes = notes;
+  setNotes = (id: Id, notes: string) =>
+    this.updateNode(id, "edit notes", (draft) => {
+      draft.notes = notes;
     });
-  });
 
-  setEstimatedTime = this.bindAction((id: Id, estimatedTime: number) => {
-    return produce((draft: Draft<ToposorterStateData>) => {
-      draft.nodes[id].estimatedTime = estimatedTime;
+  setEstimatedTime = (id: Id, estimatedTime: number) =>
+    this.updateNode(id, "set estimate", (draft) => {
+      draft.estimatedTime = estimatedTime;
     });
-  });
 }
 
 export const ToposorterStateManagerContext =
@@ -543,33 +433,39 @@ export function ToposorterStateProvider({
 }: {
   children: React.ReactNode;
 }) {
-  const [__state, _setState, stateRef] =
-    useLocalStorageState<ToposorterStateData>(
-      "toposorter",
-      {
-        nodes: {},
-      },
-      withNormalization((x) => x)
-    );
+  const [__state, _setState, stateRef] = useRefState<db.SortedGraph>(() => ({
+    nodes: [],
+    edges: [],
+  }));
   const [state, setState] = useMakeStateAsync([__state, _setState]);
-
   const [error, setError] = React.useState<null | Error>(null);
-  const trySetState = async (
-    fn: (value: ToposorterStateData) => ToposorterStateData
-  ) => {
-    try {
-      await setState(fn(stateRef.current));
-    } catch (e: unknown) {
-      if (e instanceof AbortError) {
-        return;
-      }
-      throw e;
+
+  const reload = React.useCallback(async () => {
+    await setState(await db.graphSorted());
+  }, [setState]);
+
+  // The database is the source of truth: every window reads the graph from it, and
+  // again whenever any window changes it.
+  React.useEffect(() => {
+    if (!window.__TAURI__) {
+      return;
     }
-  };
+    db.importLocalStorage()
+      .then(reload)
+      .catch((e) => console.error("Could not load graph", e));
+    const unlisten = db.onDbChanged((event) => {
+      if (event.table === "nodes" || event.table === "relations") {
+        reload().catch((e) => console.error("Could not reload graph", e));
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
 
   const stateManager = React.useMemo(
-    () => new ToposorterStateManager(trySetState, stateRef),
-    [trySetState, stateRef]
+    () => new ToposorterStateManager(reload, stateRef),
+    [reload, stateRef]
   );
 
   const toposorterState = React.useMemo(() => {
@@ -5,10 +5,11 @@ import {
   useContext,
   useEffect,
   useMemo,
+  useState,
 } from "react";
 import { Id, ToposorterStateManager, ToposorterStateManagerContext, useToposorterState } from "./ToposorterState";
-import { useLocalStorageState } from "./state";
-import { Draft, original, produce } from "immer";
+import { produce } from "immer";
+import * as db from "./db";
 
 // Writes through to activity state.
 export function useSyncActivityState() {
@@ -71,30 +72,33 @@ class ActivityLog {
   }
 
   private stopActivity(activityId: Id) {
-    this.setState(
-      produce((draft) => {
-        draft.rows.push({
-          activityId,
-          createdAt: new Date(),
-          type: "stop",
-        });
-      })
-    );
+    this.push({
+      activityId,
+      createdAt: new Date(),
+      type: "stop",
+    });
   }
 
   private startActivity(activityId: Id) {
+    const node = this.toposorterStateManager.state().getNode(activityId);
+    const timerDuration = node.estimatedTime ?? 15; // minutes
+    this.push({
+      activityId,
+      createdAt: new Date(),
+      endTime: new Date(Date.now() + timerDuration * 60 * 1000),
+      type: "start",
+    });
+  }
+
+  private push(row: LogRow) {
     this.setState(
       produce((draft) => {
-        const node = this.toposorterStateManager.state().getNode(activityId);
-        const timerDuration = node.estimatedTime ?? 15; // minutes
-        draft.rows.push({
-          activityId,
-          createdAt: new Date(),
-          endTime: new Date(Date.now() + timerDuration * 60 * 1000),
-          type: "start",
-        });
+        draft.rows.push(row);
       })
     );
+    if (window.__TAURI__) {
+      db.logActivity(row).catch((e) => console.error("Could not log activity", e));
+    }
   }
 
   getActiveActivity(): LogRow | null {
@@ -111,29 +115,42 @@ class ActivityLog {
 
 export const ActivityLogContext = createContext<ActivityLog | null>(null);
 
+// Reads the log from the database, and again whenever any window changes it.
+function useDatabaseSync(setState: Dispatch<SetStateAction<ActivityLogData>>) {
+  useEffect(() => {
+    if (!window.__TAURI__) {
+      return;
+    }
+    const reload = async () => {
+      const rows = await db.listActivity();
+      setState({ rows: rows.map(db.fromActivityRow) });
+    };
+    db.importLocalStorage()
+      .then(reload)
+      .catch((e) => console.error("Could not load activity", e));
+    const unlisten = db.onDbChanged((event) => {
+      if (event.table === "activity") {
+        reload().catch((e) => console.error("Could not reload activity", e));
+      }
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+}
+
 export function ActivityLogProvider({
   children,
 }: {
   children: React.ReactNode;
 }) {
   const toposorterStateManager = useContext(ToposorterStateManagerContext)!;
-  const [state, setState] = useLocalStorageState<ActivityLogData>(
-    "activityLog",
-    {
-      rows: [],
-    },
-    produce((draft: Draft<ActivityLogData>) => {
-      const state = original(draft)!;
-      if (state.rows === undefined) {
-        draft.rows = [];
-      }
-      delete (draft as any).nodes;
-    })
-  );
+  const [state, setState] = useState<ActivityLogData>({ rows: [] });
   const activityLog = useMemo(
     () => new ActivityLog(state, setState, toposorterStateManager),
     [state, setState]
   );
+  useDatabaseSync(setState);
   return (
     <ActivityLogContext.Provider value={activityLog}>
       {children}
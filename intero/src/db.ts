@@ -0,0 +1,304 @@
+import { invoke } from "@tauri-apps/api";
+import { listen } from "@tauri-apps/api/event";
+import type { Id, TNodeData, ToposorterStateData } from "./ToposorterState";
+import type { LogRow } from "./activity";
+import { parseJSON } from "./state";
+
+// Client for the SQLite store in the Rust backend. Times are ms since the epoch.
+
+export interface Relation {
+  parent: Id;
+  child: Id;
+}
+
+export interface Graph {
+  nodes: Record<Id, TNodeData>;
+  relations: Record<string, Relation[]>;
+}
+
+export interface ActivityRow {
+  id?: number;
+  activity_id: Id;
+  type: "start" | "stop";
+  created_at: number;
+  end_time?: number;
+}
+
+export interface PromptTemplate {
+  version: string;
+  system: string;
+  user: string;
+}
+
+export interface SortedNode {
+  id: Id;
+  data: TNodeData;
+  max_vec: number[];
+  parents: Id[];
+  children: Id[];
+}
+
+/** The graph ranked by the backend; `ToposorterState` renders it. */
+export interface SortedGraph {
+  nodes: SortedNode[];
+  edges: [parent: Id, child: Id][];
+}
+
+/** Nodes that reach each other, from data saved before links were checked. */
+export interface Cycle {
+  nodes: Id[];
+  /** Starts and ends at the same node. */
+  path: Id[];
+}
+
+export type GraphError =
+  | { kind: "unknown_node"; id: Id }
+  | { kind: "invalid_status"; status: string }
+  | { kind: "cycle"; path: Id[] }
+  | { kind: "storage"; message: string };
+
+/** `graph_*` commands reject with the `GraphError` itself rather than an `Error`. */
+export function isGraphError(e: unknown): e is GraphError {
+  return typeof e === "object" && e !== null && "kind" in e && !(e instanceof Error);
+}
+
+export interface MigrationReport {
+  from_version: number;
+  converted: number;
+  dangling: { kind: string; parent: Id; child: Id }[];
+}
+
+/** Labels of what undo and redo would apply next. */
+export interface UndoStatus {
+  undo: string | null;
+  redo: string | null;
+}
+
+/** Durations are in ms. */
+export interface Totals {
+  tracked_ms: number;
+  focused_ms: number;
+  distracted_ms: number;
+}
+
+export interface NodeTime {
+  id: Id;
+  /** `null` for nodes that have been deleted. */
+  title: string | null;
+  own: Totals;
+  /** Including descendants. */
+  total: Totals;
+  estimate_ms: number | null;
+}
+
+export interface Period {
+  label: string;
+  from: number;
+  to: number;
+  totals: Totals;
+}
+
+export interface TimeReport {
+  from: number;
+  to: number;
+  totals: Totals;
+  nodes: NodeTime[];
+  days: Period[];
+  weeks: Period[];
+}
+
+export type ReportFormat = "nodes_csv" | "days_csv" | "weeks_csv" | "markdown";
+
+export interface DbChanged {
+  table: "nodes" | "relations" | "activity";
+}
+
+export function toGraph(state: ToposorterStateData): Graph {
+  return {
+    nodes: state.nodes,
+    relations: { depends: state.relations?.depends ?? [] },
+  };
+}
+
+/** What the backend stores for a node: edges are relations, `__` fields are derived. */
+export type NodeData = Omit<TNodeData, "children" | "__maxVec" | "__parents">;
+
+export function toNodeData(data: TNodeData): NodeData {
+  const out: Partial<TNodeData> = { ...data };
+  delete out.children;
+  delete out.__maxVec;
+  delete out.__parents;
+  return out as NodeData;
+}
+
+export function fromActivityRow(row: ActivityRow): LogRow {
+  return {
+    activityId: row.activity_id,
+    type: row.type,
+    createdAt: new Date(row.created_at),
+    endTime: row.end_time != null ? new Date(row.end_time) : undefined,
+  };
+}
+
+export function toActivityRow(row: LogRow): ActivityRow {
+  return {
+    activity_id: row.activityId,
+    type: row.type,
+    created_at: row.createdAt.getTime(),
+    end_time: row.endTime?.getTime(),
+  };
+}
+
+export async function getGraph(): Promise<Graph> {
+  return await invoke<Graph>("db_graph");
+}
+
+/** `label` names the change in the undo history, e.g. "rename". */
+export async function putNode(id: Id, data: NodeData, label: string): Promise<void> {
+  await invoke("db_put_node", { id, data, label });
+}
+
+/** Also removes the node's edges. Returns false if it did not exist. */
+export async function deleteNode(id: Id): Promise<boolean> {
+  return await invoke<boolean>("db_delete_node", { id });
+}
+
+export async function logActivity(row: LogRow): Promise<number | null> {
+  return await invoke<number | null>("db_log_activity", {
+    row: toActivityRow(row),
+  });
+}
+
+export async function listActivity(
+  from?: number,
+  to?: number
+): Promise<ActivityRow[]> {
+  return await invoke<ActivityRow[]>("db_activity", { from, to });
+}
+
+/** Every prompt template verdicts were made with. */
+export async function listPromptTemplates(): Promise<PromptTemplate[]> {
+  return await invoke<PromptTemplate[]>("db_prompt_templates");
+}
+
+/** Writes a versioned backup to the app data directory. Returns its path. */
+export async function backup(): Promise<string> {
+  return await invoke<string>("db_backup");
+}
+
+/** Replaces the graph with a backup file of any version this app can read. */
+export async function restore(path: string): Promise<MigrationReport> {
+  return await invoke<MigrationReport>("db_restore", { path });
+}
+
+/** Copies `localStorage` data into an empty database. Returns whether it did. */
+export async function importLocalState(
+  state: ToposorterStateData,
+  rows: LogRow[]
+): Promise<boolean> {
+  return await invoke<boolean>("db_import", {
+    graph: toGraph(state),
+    activity: rows.map(toActivityRow),
+  });
+}
+
+let localStorageImport: Promise<void> | null = null;
+
+/**
+ * Moves the graph and activity log that older versions kept in `localStorage` into
+ * the database, then forgets them. Every window calls this before its first read.
+ */
+export function importLocalStorage(): Promise<void> {
+  localStorageImport ??= (async () => {
+    const graph = localStorage.getItem("toposorter");
+    const activity = localStorage.getItem("activityLog");
+    if (!graph && !activity) {
+      return;
+    }
+    const state = graph ? parseJSON<ToposorterStateData>(graph) : { nodes: {} };
+    const rows = activity ? parseJSON<{ rows?: LogRow[] }>(activity).rows ?? [] : [];
+    await importLocalState(state, rows);
+    localStorage.removeItem("toposorter");
+    localStorage.removeItem("activityLog");
+  })();
+  return localStorageImport;
+}
+
+export async function graphAdd(
+  from?: Id,
+  connection?: "parent" | "child"
+): Promise<Id> {
+  return await invoke<Id>("graph_add", { from, connection });
+}
+
+/** Returns false if the edge already existed. */
+export async function graphLink(parent: Id, child: Id): Promise<boolean> {
+  return await invoke<boolean>("graph_link", { parent, child });
+}
+
+export async function graphUnlink(parent: Id, child: Id): Promise<boolean> {
+  return await invoke<boolean>("graph_unlink", { parent, child });
+}
+
+export async function graphCycles(): Promise<Cycle[]> {
+  return await invoke<Cycle[]>("graph_cycles");
+}
+
+export async function graphSetStatus(
+  id: Id,
+  status: "active" | "done" | "unset"
+): Promise<void> {
+  await invoke("graph_set_status", { id, status });
+}
+
+export async function graphSorted(): Promise<SortedGraph> {
+  return await invoke<SortedGraph>("graph_sorted");
+}
+
+export async function undo(): Promise<UndoStatus> {
+  return await invoke<UndoStatus>("history_undo");
+}
+
+export async function redo(): Promise<UndoStatus> {
+  return await invoke<UndoStatus>("history_redo");
+}
+
+export async function historyStatus(): Promise<UndoStatus> {
+  return await invoke<UndoStatus>("history_status");
+}
+
+/**
+ * Runs `fn` so that everything it changes is undone in one step. Only changes from
+ * this window join the group, and it closes by itself if `fn` hangs.
+ */
+export async function historyGroup<T>(label: string, fn: () => Promise<T>): Promise<T> {
+  await invoke("history_begin_group", { label });
+  try {
+    return await fn();
+  } finally {
+    await invoke("history_end_group");
+  }
+}
+
+/** Fired after undo or redo, with what they would apply next. */
+export function onHistoryChanged(fn: (status: UndoStatus) => void) {
+  return listen<UndoStatus>("history-changed", (event) => fn(event.payload));
+}
+
+/** `from` and `to` are ms since the epoch; defaults to everything logged so far. */
+export async function timeReport(from?: number, to?: number): Promise<TimeReport> {
+  return await invoke<TimeReport>("report_time", { from, to });
+}
+
+export async function exportTimeReport(
+  format: ReportFormat,
+  from?: number,
+  to?: number
+): Promise<string> {
+  return await invoke<string>("report_export", { from, to, format });
+}
+
+/** Fired after any window, or undo and redo, changes a table. */
+export function onDbChanged(fn: (event: DbChanged) => void) {
+  return listen<DbChanged>("db-changed", (event) => fn(event.payload));
+}
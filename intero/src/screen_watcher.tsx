@@ -1,194 +1,296 @@
 import { invoke } from "@tauri-apps/api";
-import OpenAI from "openai";
-import { client } from "@gradio/client";
-import { fetchEventSource } from "@microsoft/fetch-event-source";
 
-const openai = new OpenAI({
-  apiKey: import.meta.env.VITE_OPENAI_API_KEY,
-  dangerouslyAllowBrowser: true,
-});
+export type Category = "work" | "distraction" | "unknown";
 
-export interface Response {
+export type ParseKind =
+  | "json"
+  | "fenced_json"
+  | "partial_json"
+  | "label"
+  | "refusal"
+  | "unrecognized"
+  | "rule";
+
+/** The frontmost application and window. */
+export interface ForegroundInfo {
+  /** Bundle id on macOS, `WM_CLASS` class on X11. */
+  app_id: string;
+  app_name: string;
+  pid: number | null;
+  title: string | null;
+  /** The active tab's URL, for browsers that expose it. */
+  url: string | null;
+}
+
+/** Payload of the `foreground-changed` event. */
+export interface ForegroundChanged {
+  foreground: ForegroundInfo | null;
+  previous: ForegroundInfo | null;
+  timestamp: number;
+}
+
+/** A parsed classifier answer. */
+export interface Verdict {
+  category: Category;
+  /** 0..1, only when the model reported one. */
+  confidence: number | null;
   description: string;
-  activity: string;
   reason: string;
+  parse: ParseKind;
+  raw: string;
+  model: string;
+  latency_ms: number;
+  /** What was in front when the frame was captured. */
+  foreground: ForegroundInfo | null;
+  /** Prompt template the model was asked with; see `listPromptTemplates`. */
+  prompt_version: string | null;
+}
+
+/** Payload of the `verdict` event the backend emits for each classified frame. */
+export interface VerdictEvent {
+  frame_id: number;
+  verdict: Verdict;
+}
+
+export type FocusState = "unknown" | "focused" | "distracted";
+
+/** Payload of the `focus-state-changed` event. */
+export interface FocusChange {
+  state: FocusState;
+  previous: FocusState;
+  timestamp: number;
+}
+
+/** Scheduling of the backend's capture and classify loop. */
+export interface WatchConfig {
+  capture_interval_ms: number;
+  /** How long the screen has to stay unchanged before a frame is classified. */
+  debounce_ms: number;
+  /** The longest a change waits for the screen to settle. */
+  max_wait_ms: number;
+  /** How long a running classification may lag behind a newer frame before it is cancelled. */
+  stale_after_ms: number;
+}
+
+export interface ScreenRect {
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+export interface ScreenshotOptions {
+  max_width?: number;
+  max_height?: number;
+  encoding?: Encoding;
+  crop?: ScreenRect;
+  display_id?: number;
+  max_age_ms?: number;
+}
+
+export type Encoding =
+  | { format: "png" }
+  | { format: "jpeg"; quality: number }
+  | { format: "webp" };
+
+export interface Frame {
+  timestamp: number;
+  sequence: number;
+  display_id: number;
+  scale_factor: number;
+  content_rect: ScreenRect;
+  width: number;
+  height: number;
+  encoding: Encoding;
+  /** base64 */
+  bytes: string;
+}
+
+/** Rejection value of the `screenshot` command. */
+export type CaptureError =
+  | { kind: "not_started" }
+  | { kind: "paused" }
+  | { kind: "permission_denied" }
+  | { kind: "no_frame_yet" }
+  | { kind: "stale_frame"; age_ms: number }
+  | { kind: "failed"; message: string };
+
+export type CaptureStatus =
+  | "unknown"
+  | "requesting"
+  | "granted"
+  | "denied"
+  | "starting"
+  | "running"
+  | "paused"
+  | "failed";
+
+/** Payload of the `capture-status-changed` event. */
+export interface CaptureStatusChanged {
+  status: CaptureStatus;
+  previous: CaptureStatus;
+}
+
+/** A changed frame kept in the on-disk frame history. */
+export interface FrameRecord {
+  id: number;
+  timestamp: number;
+  display_id: number;
+  content_rect: ScreenRect;
+  width: number;
+  height: number;
+  encoding: Encoding;
+  score: number;
+  file: string;
+  size: number;
+  foreground?: ForegroundInfo;
+}
+
+export interface StoredFrame extends FrameRecord {
+  /** base64 */
+  bytes: string;
+}
+
+export type ClassifierConfig =
+  | {
+      backend: "openai";
+      base_url: string;
+      model: string;
+      api_key_env?: string;
+    }
+  | { backend: "ollama"; base_url: string; model: string }
+  | { backend: "moondream"; url: string };
+
+/** Rejection value of the `classify_frame` command. */
+export type ClassifierError =
+  | { kind: "http"; message: string }
+  | { kind: "status"; status: number; body: string }
+  | { kind: "bad_response"; message: string }
+  | { kind: "frame_not_found"; id: number }
+  | { kind: "frame_unreadable"; id: number; message: string }
+  | { kind: "remote_not_allowed"; model: string };
+
+/** What the backend hides from frames before they are stored or classified. */
+export interface RedactionConfig {
+  style: { style: "blackout" } | { style: "blur"; block_size: number };
+  /** In screen coordinates. */
+  regions: ScreenRect[];
+  /** Bundle ids or X11 classes whose windows are hidden. */
+  apps: string[];
+  password_fields: boolean;
+  notifications: boolean;
+  /** Refuse classifiers that are not on localhost. */
+  local_only: boolean;
+}
+
+/** Returned by `rules_status` and the payload of the `rules-reloaded` event. */
+export interface RulesStatus {
+  path: string | null;
+  rules: number;
+  /** Why the rules file could not be loaded; the previous rules stay in effect. */
+  error: string | null;
+}
+
+/** Counters of the backend's perceptual-hash verdict cache. */
+export interface CacheStats {
+  hits: number;
+  misses: number;
+  entries: number;
+  config: { ttl_ms: number; max_distance: number; capacity: number };
 }
 
 export class ScreenWatcher {
   static instance = new ScreenWatcher();
 
-  public async screenshot(): Promise<string> {
-    const results = await invoke<string[]>("screenshot");
-    return results[0];
-  }
-
-  public async getScreenshotDescriptionOpenAI(
-    screenshot: string,
-    abortController: AbortController
-  ): Promise<{
-    description: string;
-    activity: string;
-    reason: string;
-  }> {
-    try {
-      const response = await openai.chat.completions.create(
-        {
-          model: "gpt-4-vision-preview",
-          // NOTE: gpt4-vision-preview does not support json_object response format yet.
-          // response_format: { type: "json_object" },
-          messages: [
-            {
-              role: "system",
-              content:
-                "You are an AI assistant tasked with analyzing the user's screen. You must respond in valid JSON. Use the following typescript type: { description: string; activity: string; reason: string; }. Do not use a markdown code block.",
-            },
-            {
-              role: "user",
-              content: [
-                {
-                  type: "text",
-                  text: `Describe the nature of the activity in the screen with one of the following categories:
-- "work" - includes work-related activities such as coding, writing, etc.
-- "distraction" - includes social media, news, etc.
-- "unknown" - if you are unsure. includes switching windows, etc.
-`,
-                },
-                {
-                  type: "image_url",
-                  image_url: {
-                    url: `data:image/png;base64,${screenshot}`,
-                  },
-                },
-              ],
-            },
-          ],
-          max_tokens: 1500,
-        },
-        {
-          signal: abortController.signal,
-        }
-      );
-
-      let content = response.choices[0].message.content ?? "null";
-      console.log(content);
-      if (content.startsWith("```json")) {
-        // remove code block
-        content = content.slice(7, -3);
-      }
-      const nature = JSON.parse(content);
-      if (nature.activity === undefined) {
-        return {
-          description: "[unsafe]",
-          activity: "distraction",
-          reason: "[unsafe]",
-        };
-      }
-      return nature;
-    } catch (e: any) {
-      console.log(e);
-      if (e.code === 400) {
-        return {
-          description: "[unsafe]",
-          activity: "distraction",
-          reason: "[unsafe]",
-        };
-      }
-      throw e;
-    }
+  /** Rejects with a `CaptureError`. */
+  public async screenshot(options?: ScreenshotOptions): Promise<string> {
+    const frame = await invoke<Frame>("screenshot", { options });
+    return frame.bytes;
   }
 
-  public async getScreenshotDescriptionMoondreamFastApi(
-    screenshot: string,
-    abortController: AbortController
-  ): Promise<{
-    description: string;
-    activity: string;
-    reason: string;
-  }> {
-    const formData = new FormData();
-    formData.append(
-      "prompt",
-      `Describe the nature of the activity in the screen with one of the following categories:
-- "work" - only productive work-related activities.
-- "distraction" - includes social media, news, youtube, etc.
-- "unknown" - if you are unsure.`
-    );
-    formData.append("file", base64ToBlob(`data:image/png;base64,${screenshot}`, "image/png"));
-
-    let buffer = "";
-
-    await fetchEventSource("http://localhost:7861/api/inference", {
-      method: "POST",
-      signal: abortController.signal,
-      body: formData,
-      onmessage: (event) => {
-        if (event.data.length === 0) return;
-        try {
-          buffer += JSON.parse(event.data);
-        } catch (e) {
-          console.error(e);
-        }
-      },
-      onerror: (event) => {
-        console.error(event);
-      },
-    });
-
-    return {
-      description: "",
-      activity: buffer.toLowerCase(),
-      reason: "",
-    };
-  }
-
-  public async getScreenshotDescriptionOllama(
-    screenshot: string,
-    abortController: AbortController
-  ): Promise<{
-    description: string;
-    activity: string;
-    reason: string;
-  }> {
-    const response = await fetch("http://localhost:11434/api/generate", {
-      method: "POST",
-      signal: abortController.signal,
-      body: JSON.stringify({
-        model: "llava",
-        options: {
-          temperature: 0,
-        },
-        system:
-          "You are an AI assistant tasked with analyzing the user's screen. You must respond in valid JSON. Use the following typescript type: { description: string; activity: string; reason: string; }",
-        prompt: `Describe the nature of the activity in the screen with one of the following categories:
-- "work" - only productive work-related activities.
-- "distraction" - includes social media, news, youtube, etc.
-- "unknown" - if you are unsure.
-
-Be liberal with the "distraction" category. All videos should be considered distractions.
-`,
-        format: "json",
-        stream: false,
-        images: [screenshot],
-      }),
-    });
-    const responseJson = await response.json();
-    console.log(responseJson);
-    return JSON.parse(responseJson.response);
-  }
-}
-
-function base64ToBlob(base64: string, mimeType: string) {
-  // Decode the base64 string to a byte array
-  const byteCharacters = atob(base64.split(',')[1]);
-  const byteNumbers = new Array(byteCharacters.length);
-  for (let i = 0; i < byteCharacters.length; i++) {
-      byteNumbers[i] = byteCharacters.charCodeAt(i);
-  }
-  const byteArray = new Uint8Array(byteNumbers);
-
-  // Create a Blob from the byte array
-  return new Blob([byteArray], {type: mimeType});
-}
+  public async captureStatus(): Promise<CaptureStatus> {
+    return await invoke<CaptureStatus>("capture_status");
+  }
+
+  /** Starts or resumes capture. Rejects with a `CaptureError`. */
+  public async startCapture(): Promise<void> {
+    await invoke("start_capture");
+  }
+
+  public async stopCapture(): Promise<void> {
+    await invoke("stop_capture");
+  }
+
+  /** Pauses until `startCapture`, or for `minutes` if given. */
+  public async pauseCapture(minutes?: number): Promise<void> {
+    await invoke("pause_capture", { minutes });
+  }
+
+  /** Frames with `from <= timestamp < to` (ms since the epoch), oldest first. */
+  public async listFrames(
+    from?: number,
+    to?: number,
+    limit?: number
+  ): Promise<FrameRecord[]> {
+    return await invoke<FrameRecord[]>("list_frames", { from, to, limit });
+  }
+
+  public async getFrame(id: number): Promise<StoredFrame | null> {
+    return await invoke<StoredFrame | null>("get_frame", { id });
+  }
+
+  /** The last stored frame taken at or before `timestamp`. */
+  public async frameAt(timestamp: number): Promise<StoredFrame | null> {
+    return await invoke<StoredFrame | null>("frame_at", { timestamp });
+  }
+
+  /** Runs the backend's active classifier on a frame from the frame history. */
+  public async classifyFrame(id: number): Promise<Verdict> {
+    return await invoke<Verdict>("classify_frame", { id });
+  }
+
+  public async classifierDiagnostics(): Promise<CacheStats> {
+    return await invoke<CacheStats>("classifier_diagnostics");
+  }
 
+  /** The frontmost app and window; only tracked while capturing. */
+  public async currentForeground(): Promise<ForegroundInfo | null> {
+    return await invoke<ForegroundInfo | null>("current_foreground");
+  }
+
+  /** The user's rules file, which decides verdicts before the classifier is asked. */
+  public async rulesStatus(): Promise<RulesStatus> {
+    return await invoke<RulesStatus>("rules_status");
+  }
+
+  public async getRedactionConfig(): Promise<RedactionConfig> {
+    return await invoke<RedactionConfig>("get_redaction_config");
+  }
+
+  public async setRedactionConfig(config: RedactionConfig): Promise<void> {
+    await invoke("set_redaction_config", { config });
+  }
+
+  public async getClassifier(): Promise<ClassifierConfig> {
+    return await invoke<ClassifierConfig>("get_classifier");
+  }
+
+  public async setClassifier(config: ClassifierConfig): Promise<void> {
+    await invoke("set_classifier", { config });
+  }
+
+  /** Verdicts smoothed over time; see `focus-state-changed`. */
+  public async focusState(): Promise<FocusState> {
+    return await invoke<FocusState>("focus_state");
+  }
+
+  public async getWatchConfig(): Promise<WatchConfig> {
+    return await invoke<WatchConfig>("get_watch_config");
+  }
+
+  public async setWatchConfig(config: WatchConfig): Promise<void> {
+    await invoke("set_watch_config", { config });
+  }
+}
 
 (window as any).watcher = ScreenWatcher.instance;
@@ -0,0 +1,29 @@
+import { invoke } from "@tauri-apps/api";
+
+// API keys live in the Rust backend (the keychain on macOS, an encrypted file
+// elsewhere). The webview can set, clear and test them but never read them back.
+
+export type SecretName = "openai";
+
+export interface SecretsStatus {
+  backend: "keychain" | "file";
+  set: SecretName[];
+}
+
+export async function secretsStatus(): Promise<SecretsStatus> {
+  return await invoke<SecretsStatus>("secrets_status");
+}
+
+export async function setSecret(name: SecretName, value: string): Promise<void> {
+  await invoke("set_secret", { name, value });
+}
+
+/** Returns whether a key was stored. */
+export async function clearSecret(name: SecretName): Promise<boolean> {
+  return await invoke<boolean>("clear_secret", { name });
+}
+
+/** Rejects with the service's error if the stored key does not work. */
+export async function testSecret(name: SecretName): Promise<void> {
+  await invoke("test_secret", { name });
+}
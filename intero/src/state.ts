@@ -53,7 +53,7 @@ export function useLocalStorageState<T>(
   return [state, setState, ref];
 }
 
-function parseJSON<T>(json: string): T {
+export function parseJSON<T>(json: string): T {
   try {
     return JSON.parse(json, (key, value) => {
       if (key === "createdAt") {
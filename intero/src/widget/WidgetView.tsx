@@ -19,10 +19,20 @@ import {
   ToposorterStateManagerContext,
   ToposorterStateProvider,
 } from "../ToposorterState";
-import { ScreenWatcher } from "../screen_watcher";
+import {
+  FocusChange,
+  FocusState,
+  ScreenWatcher,
+  Verdict,
+  VerdictEvent,
+} from "../screen_watcher";
 import { useInWindow } from "./mouse_hacks";
-import * as pixelmatch from "pixelmatch";
-import { PreferencesContext, PreferencesProvider } from "../preference_state";
+import { listen } from "@tauri-apps/api/event";
+import {
+  PreferencesContext,
+  PreferencesManagerContext,
+  PreferencesProvider,
+} from "../preference_state";
 
 
 function useActiveActivity() {
@@ -34,73 +44,15 @@ function useActiveActivity() {
   return { row: lastRow, activity };
 }
 
-function useLoop({
-  interval,
-  callback,
-}: {
-  interval: number;
-  callback: () => void;
-}) {
-  useEffect(() => {
-    let cancel = false;
-    let lastTimeout: number | null = null;
-    async function loop() {
-      if (cancel) {
-        return;
-      }
-      const timeStart = new Date().getTime();
-      try {
-        await callback();
-      } catch (e) {
-        console.error(e);
-        await new Promise((resolve) => setTimeout(resolve, 1000));
-      }
-      const timeElapsed = new Date().getTime() - timeStart;
-      if (!cancel) {
-        const waitTime = Math.max(interval - timeElapsed, 0);
-        lastTimeout = window.setTimeout(loop, waitTime);
-      }
-    }
-    loop();
-    return () => {
-      cancel = true;
-      if (lastTimeout) {
-        window.clearTimeout(lastTimeout);
-      }
-    };
-  }, []);
-}
-
-async function getImageInfo(
-  image: string
-): Promise<{ imageData: Uint8ClampedArray; width: number; height: number }> {
-  const img = await getImage(image);
-  const canvas = document.createElement("canvas");
-  canvas.width = img.width;
-  canvas.height = img.height;
-
-  // Get the context of the canvas
-  const ctx = canvas.getContext("2d")!;
-
-  // Draw the image onto the canvas
-  ctx.drawImage(img, 0, 0);
-
-  // Extract the pixel data from the canvas
-  const imageData = ctx.getImageData(0, 0, canvas.width, canvas.height);
-  return {
-    imageData: imageData.data,
-    width: canvas.width,
-    height: canvas.height,
-  };
-}
-
-async function getImage(image: string): Promise<HTMLImageElement> {
-  const img = document.createElement("img");
-  img.src = `data:image/png;base64,${image}`;
-  await new Promise((resolve) => {
-    img.onload = resolve;
-  });
-  return img;
+interface FrameChanged {
+  timestamp: number;
+  width: number;
+  height: number;
+  score: number;
+  changed_pixels: number;
+  regions: { x: number; y: number; width: number; height: number }[];
+  /** Fetch the image with `getFrame`; null if the frame history is unavailable. */
+  frame_id: number | null;
 }
 
 const MIN_NUM_DIFF_PIXELS = 10000;
@@ -280,9 +232,37 @@ function HideOnHoverDiv(props: React.HTMLProps<HTMLDivElement>) {
   );
 }
 
+// Capture runs in the backend; keep it off unless watching is enabled.
+function useCaptureFollowsWatch() {
+  const preferences = useContext(PreferencesContext)!;
+  const preferencesManager = useContext(PreferencesManagerContext)!;
+  const watch = !!preferences.boolOptions.watch;
+
+  useEffect(() => {
+    (async () => {
+      if (!watch) {
+        await ScreenWatcher.instance.stopCapture();
+      } else if ((await ScreenWatcher.instance.captureStatus()) !== "paused") {
+        await ScreenWatcher.instance.startCapture();
+      }
+    })().catch((e) => console.error("Could not update capture", e));
+  }, [watch]);
+
+  // The tray can start and stop capture too.
+  useEffect(() => {
+    const unlistenPromise = listen<boolean>("set-watch", (event) => {
+      preferencesManager.setBoolOption("watch", event.payload);
+    });
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, [preferencesManager]);
+}
+
 function ScreenWatcherViewOuter() {
   const preferences = useContext(PreferencesContext)!;
   const { setUIState } = useContext(UIStateContext)!;
+  useCaptureFollowsWatch();
 
   const { activity, row } = useActiveActivity();
   useEffect(() => {
@@ -305,99 +285,58 @@ function ScreenWatcherViewOuter() {
 
 function ScreenWatcherView() {
   const { setUIState } = useContext(UIStateContext)!;
-  const [response, setResponse] = useState<
-    { description: string; activity: string; reason: string } | undefined
-  >(undefined);
+  const [response, setResponse] = useState<Verdict | undefined>(undefined);
 
   const natureRef = useRef<string | null>(null);
   useEffect(() => {
-    if (response?.activity) {
-      natureRef.current = response.activity;
+    if (response?.category) {
+      natureRef.current = response.category;
     }
   }, [response]);
 
-  const currentImageRef = useRef<string | null>(null);
-
-  const imageRef = useRef<string | null>(null);
-  const [image, setImage] = useState<string | null>(null);
+  const [frameId, setFrameId] = useState<number | null>(null);
+  const frameIdRef = useRef<number | null>(null);
   const [numDiffPixels, setNumDiffPixels] = useState<number | null>(null);
 
-  useLoop({
-    interval: 1000 / 2,
-    callback: async () => {
-      const image = await ScreenWatcher.instance.screenshot();
-
-      // need to compare the image to the old image to prevent unnecessary re-renders
-      if (currentImageRef.current != null) {
-        // convert to Uint8Array
-        const [oldImageInfo, newImageInfo] = await Promise.all([
-          getImageInfo(currentImageRef.current!),
-          getImageInfo(image!),
-        ]);
-
-        const numDiffPixels = pixelmatch(
-          oldImageInfo.imageData,
-          newImageInfo.imageData,
-          null,
-          oldImageInfo.width,
-          oldImageInfo.height,
-          { threshold: 0.1 }
-        );
-        setNumDiffPixels(numDiffPixels);
-
-        if (numDiffPixels > MIN_NUM_DIFF_PIXELS) {
-          setImage(image);
-          imageRef.current = image;
-        }
-      } else {
-        setImage(image);
-        imageRef.current = image;
-      }
-      currentImageRef.current = image;
-    },
-  });
-
-  const analyzedImageRef = useRef<string | null>(null);
-
-  const abortControllerRef = useRef<AbortController | null>(null);
-  const [lock, setLock] = useState(false);
+  // Change detection and classification run in the backend; we only hear about
+  // frames that differ and, once the screen settles, what the classifier made of them.
   useEffect(() => {
-    (async () => {
-      if (image && image !== analyzedImageRef.current) {
-        if (lock) {
-          abortControllerRef.current?.abort();
-          abortControllerRef.current = null;
-          return;
-        }
-        setLock(true);
-        analyzedImageRef.current = image;
-        setResponse(undefined);
-        try {
-          abortControllerRef.current = new AbortController();
-          const response =
-            await ScreenWatcher.instance.getScreenshotDescriptionMoondreamFastApi(
-              image,
-              abortControllerRef.current
-            );
-          // check if stale
-          if (imageRef.current === image) {
-            setResponse(response);
-          }
-        } catch (e) {
-          console.error(e);
-        } finally {
-          setLock(false);
-        }
+    const unlistenFrames = listen<FrameChanged>("frame-changed", (event) => {
+      setNumDiffPixels(event.payload.changed_pixels);
+      frameIdRef.current = event.payload.frame_id;
+      setFrameId(event.payload.frame_id);
+      setResponse(undefined);
+    });
+    const unlistenVerdicts = listen<VerdictEvent>("verdict", (event) => {
+      // The backend cancels stale requests, but one may finish as a frame arrives.
+      if (event.payload.frame_id === frameIdRef.current) {
+        setResponse(event.payload.verdict);
       }
-    })();
-  }, [image, lock]);
+    });
+    return () => {
+      unlistenFrames.then((unlisten) => unlisten());
+      unlistenVerdicts.then((unlisten) => unlisten());
+    };
+  }, []);
 
   const style: React.CSSProperties = {
     backgroundColor: "rgba(0, 0, 0, 0.9)",
   };
 
+  // Single verdicts flicker during window switches; the backend smooths them.
+  const [focus, setFocus] = useState<FocusState>("unknown");
+  useEffect(() => {
+    ScreenWatcher.instance.focusState().then(setFocus).catch(console.error);
+    const unlistenPromise = listen<FocusChange>("focus-state-changed", (event) => {
+      setFocus(event.payload.state);
+    });
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
   useEffect(() => {
-    if (response?.activity === "distraction") {
+    if (focus === "distracted") {
       setUIState(UIState.Distracted);
     } else {
       setUIState(state => {
@@ -407,7 +346,7 @@ function ScreenWatcherView() {
         return state;
       });
     }
-  }, [response?.activity]);
+  }, [focus]);
 
   const numDiffPixelsStyle: React.CSSProperties = {};
   if (numDiffPixels !== null && numDiffPixels > MIN_NUM_DIFF_PIXELS) {
@@ -415,7 +354,30 @@ function ScreenWatcherView() {
   }
 
   const preferences = useContext(PreferencesContext)!;
-  if (!preferences.boolOptions.debug) {
+  const debug = preferences.boolOptions.debug;
+
+  // Events carry only the frame id; the image is fetched when there is a view to show it in.
+  const [image, setImage] = useState<string | null>(null);
+  useEffect(() => {
+    if (!debug || frameId === null) {
+      setImage(null);
+      return;
+    }
+    let cancelled = false;
+    ScreenWatcher.instance
+      .getFrame(frameId)
+      .then((frame) => {
+        if (!cancelled && frame) {
+          setImage(`data:image/${frame.encoding.format};base64,${frame.bytes}`);
+        }
+      })
+      .catch(console.error);
+    return () => {
+      cancelled = true;
+    };
+  }, [debug, frameId]);
+
+  if (!debug) {
     return <></>;
   }
 
@@ -438,32 +400,27 @@ function ScreenWatcherView() {
           <>
             <div>last relevant frame:</div>
             <img
-              src={`data:image/png;base64,${image}`}
+              src={image}
               alt="screenshot"
               className="rounded-xl mt-2"
             />
           </>
         )}
       </div>
-      {analyzedImageRef.current && response && (
+      {response && (
         <div className="flex flex-col flex-1 space-y-4 justify-between h-full w-full">
           <pre className="text-sm whitespace-pre-wrap w-full flex-1">
             {response.description}
           </pre>
           <pre className="text-xl whitespace-pre-wrap w-full flex-1">
-            Verdict: <b>{response.activity}</b>
+            Verdict: <b>{response.category}</b>
           </pre>
           {/* <pre className="text-sm whitespace-pre-wrap w-full flex-1">
             Reason: {response.reason}
           </pre> */}
-          {/* <img
-            src={`data:image/png;base64,${analyzedImageRef.current}`}
-            alt="screenshot"
-            className="rounded-xl mt-2"
-          /> */}
         </div>
       )}
-      {(!analyzedImageRef.current || !response) && image && (
+      {!response && image && (
         <div className="flex-1 flex justify-center items-center w-full">
           <div className="animate-spin rounded-full h-32 w-32 border-t-2 border-b-2 border-purple-500"></div>
         </div>
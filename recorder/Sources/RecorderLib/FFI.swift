@@ -1,19 +1,198 @@
+import AppKit
+import Foundation
 import SwiftRs
 
+/// Values must match `ffi::STATUS_*` on the Rust side.
+enum RecorderStatus: Int {
+    case idle = 0
+    case starting = 1
+    case running = 2
+    case permissionDenied = 3
+    case failed = 4
+}
+
 @_cdecl("start")
 public func start() {
     // print("Starting the app")
     // return AppDelegate.start()
     Task { @MainActor in
+        ScreenRecorder.shared.status = .starting
         ScreenRecorder.shared.isAppExcluded = true
         if await ScreenRecorder.shared.canRecord {
             await ScreenRecorder.shared.start()
-        } 
+        } else {
+            ScreenRecorder.shared.status = .permissionDenied
+        }
+    }
+}
+
+@_cdecl("stop")
+public func stop() {
+    Task { @MainActor in
+        await ScreenRecorder.shared.stop()
+    }
+}
+
+/// Layout must match `ffi::RawFrame` on the Rust side.
+public class RawFrame: NSObject {
+    var width: Int
+    var height: Int
+    var bytesPerRow: Int
+    /// Increases by one for every frame delivered by the stream.
+    var sequence: Int
+    /// Seconds since 1970.
+    var timestamp: Double
+    var displayID: Int
+    /// The area of the screen the frame covers, in global screen points.
+    var screenX: Double
+    var screenY: Double
+    var screenWidth: Double
+    var screenHeight: Double
+    /// BGRA pixels, `bytesPerRow * height` bytes.
+    var data: SRData
+
+    init(width: Int, height: Int, bytesPerRow: Int, sequence: Int, timestamp: Double, displayID: Int, screenRect: CGRect, data: SRData) {
+        self.width = width
+        self.height = height
+        self.bytesPerRow = bytesPerRow
+        self.sequence = sequence
+        self.timestamp = timestamp
+        self.displayID = displayID
+        self.screenX = screenRect.origin.x
+        self.screenY = screenRect.origin.y
+        self.screenWidth = screenRect.size.width
+        self.screenHeight = screenRect.size.height
+        self.data = data
     }
 }
 
+@MainActor
+@_cdecl("get_status")
+public func getStatus() -> Int {
+    return ScreenRecorder.shared.status.rawValue
+}
+
 @MainActor
 @_cdecl("get_last_frame")
-public func getLastFrame() -> SRData? {
+public func getLastFrame() -> RawFrame? {
     return ScreenRecorder.shared.lastFrame
-}
\ No newline at end of file
+}
+
+/// Layout must match `ffi::RawForeground` on the Rust side. Missing values are empty strings.
+public class RawForeground: NSObject {
+    var pid: Int
+    var bundleID: SRString
+    var name: SRString
+    var title: SRString
+
+    init(pid: Int, bundleID: String, name: String, title: String) {
+        self.pid = pid
+        self.bundleID = SRString(bundleID)
+        self.name = SRString(name)
+        self.title = SRString(title)
+    }
+}
+
+/// The frontmost application and the title of its frontmost window. Window titles
+/// are only visible with screen recording permission.
+@_cdecl("get_foreground")
+public func getForeground() -> RawForeground? {
+    guard let app = NSWorkspace.shared.frontmostApplication else {
+        return nil
+    }
+    let pid = app.processIdentifier
+    let windows = CGWindowListCopyWindowInfo([.optionOnScreenOnly, .excludeDesktopElements], kCGNullWindowID) as? [[String: Any]] ?? []
+    // Front to back, so the first normal-layer window of the app is its frontmost one.
+    let window = windows.first {
+        ($0[kCGWindowOwnerPID as String] as? pid_t) == pid && ($0[kCGWindowLayer as String] as? Int) == 0
+    }
+    return RawForeground(
+        pid: Int(pid),
+        bundleID: app.bundleIdentifier ?? "",
+        name: app.localizedName ?? "",
+        title: window?[kCGWindowName as String] as? String ?? ""
+    )
+}
+
+/// The URL of the active tab, for browsers that expose it to AppleScript.
+/// Asks the user for Automation permission the first time.
+@_cdecl("get_browser_url")
+public func getBrowserURL(bundleID: SRString) -> SRString? {
+    let id = bundleID.toString()
+    let script: String
+    switch id {
+    case "com.apple.Safari":
+        script = "tell application id \"\(id)\" to get URL of front document"
+    case "com.google.Chrome", "com.brave.Browser", "com.microsoft.edgemac", "company.thebrowser.Browser":
+        script = "tell application id \"\(id)\" to get URL of active tab of front window"
+    default:
+        return nil
+    }
+    var error: NSDictionary?
+    guard let url = NSAppleScript(source: script)?.executeAndReturnError(&error).stringValue else {
+        return nil
+    }
+    return SRString(url)
+}
+
+/// On-screen windows, front to back, and the focused password field, as JSON shaped like
+/// `redaction::ScreenLayout`. Only normal windows and notification banners are listed.
+/// The password field needs Accessibility permission and is left out without it.
+@_cdecl("get_screen_layout")
+public func getScreenLayout() -> SRString {
+    let windows = CGWindowListCopyWindowInfo([.optionOnScreenOnly, .excludeDesktopElements], kCGNullWindowID) as? [[String: Any]] ?? []
+    var bundleIDs: [pid_t: String] = [:]
+    var list: [[String: Any]] = []
+    for window in windows {
+        guard let pid = window[kCGWindowOwnerPID as String] as? pid_t,
+              let bounds = window[kCGWindowBounds as String] as? NSDictionary,
+              let rect = CGRect(dictionaryRepresentation: bounds as CFDictionary)
+        else {
+            continue
+        }
+        let bundleID = bundleIDs[pid] ?? NSRunningApplication(processIdentifier: pid)?.bundleIdentifier ?? ""
+        bundleIDs[pid] = bundleID
+        let notification = bundleID == "com.apple.notificationcenterui"
+        // Higher layers are the menu bar, the Dock and other see-through overlays.
+        guard (window[kCGWindowLayer as String] as? Int) == 0 || notification else {
+            continue
+        }
+        list.append(["app_id": bundleID, "rect": rectJSON(rect), "notification": notification])
+    }
+    let passwordFields = AXIsProcessTrusted() ? focusedSecureField().map { [rectJSON($0)] } ?? [] : []
+    let layout: [String: Any] = ["windows": list, "password_fields": passwordFields]
+    let data = (try? JSONSerialization.data(withJSONObject: layout)) ?? Data()
+    return SRString(String(data: data, encoding: .utf8) ?? "{}")
+}
+
+private func rectJSON(_ rect: CGRect) -> [String: Double] {
+    ["x": Double(rect.origin.x), "y": Double(rect.origin.y), "width": Double(rect.width), "height": Double(rect.height)]
+}
+
+private func focusedSecureField() -> CGRect? {
+    let system = AXUIElementCreateSystemWide()
+    var focused: CFTypeRef?
+    guard AXUIElementCopyAttributeValue(system, kAXFocusedUIElementAttribute as CFString, &focused) == .success,
+          let focused, CFGetTypeID(focused) == AXUIElementGetTypeID()
+    else {
+        return nil
+    }
+    let field = focused as! AXUIElement
+    var subrole: CFTypeRef?
+    AXUIElementCopyAttributeValue(field, kAXSubroleAttribute as CFString, &subrole)
+    guard (subrole as? String) == kAXSecureTextFieldSubrole else {
+        return nil
+    }
+    var position: CFTypeRef?
+    var size: CFTypeRef?
+    guard AXUIElementCopyAttributeValue(field, kAXPositionAttribute as CFString, &position) == .success,
+          AXUIElementCopyAttributeValue(field, kAXSizeAttribute as CFString, &size) == .success
+    else {
+        return nil
+    }
+    var origin = CGPoint.zero
+    var extent = CGSize.zero
+    AXValueGetValue(position as! AXValue, .cgPoint, &origin)
+    AXValueGetValue(size as! AXValue, .cgSize, &extent)
+    return CGRect(origin: origin, size: extent)
+}
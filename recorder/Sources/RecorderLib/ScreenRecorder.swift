@@ -146,7 +146,9 @@ class ScreenRecorder: NSObject,
         .store(in: &subscriptions)
     }
 
-    var lastFrame: SRData?
+    var lastFrame: RawFrame?
+    private var frameSequence = 0
+    var status: RecorderStatus = .idle
 
     /// Starts capturing screen content.
     func start() async {
@@ -164,6 +166,7 @@ class ScreenRecorder: NSObject,
             let filter = contentFilter
             // Update the running state.
             isRunning = true
+            status = .running
             setPickerUpdate(false)
             // Start the stream and await new video frames.
             for try await frame in captureEngine.startCapture(configuration: config, filter: filter) {
@@ -172,31 +175,30 @@ class ScreenRecorder: NSObject,
                     // Update the content size if it changed.
                     contentSize = frame.size
                 }
-                guard let surface = frame.surface else { return }
-                let ciImage = CIImage(ioSurface: surface)
-                let context = CIContext(options: nil)
-                guard let cgImage = context.createCGImage(ciImage, from: ciImage.extent) else { return }
-                let size = NSSize(width: cgImage.width, height: cgImage.height)
-                let image = NSImage(cgImage: cgImage, size: size)
-                // Use nsImage here
-                // Assuming `frame` can be converted to `NSImage`
-                guard let tiffData = image.tiffRepresentation,
-                      let bitmapImage = NSBitmapImageRep(data: tiffData),
-                      let pngData = bitmapImage.representation(using: .png, properties: [:])
-                else {
-                    continue // Skip if the conversion fails
-                }
-
-                lastFrame = SRData([UInt8](pngData))
-
-                // let base64EncodedString = pngData.base64EncodedString()
-                // let base64Prefix = String(base64EncodedString.prefix(16))
-                // print(base64Prefix)
+                guard let surface = frame.surface else { continue }
+                // Hand the BGRA bytes straight to Rust, which decides how to scale and encode them.
+                surface.lock(options: .readOnly, seed: nil)
+                let bytesPerRow = surface.bytesPerRow
+                let bytes = [UInt8](UnsafeRawBufferPointer(start: surface.baseAddress, count: bytesPerRow * surface.height))
+                surface.unlock(options: .readOnly, seed: nil)
+
+                frameSequence += 1
+                lastFrame = RawFrame(
+                    width: surface.width,
+                    height: surface.height,
+                    bytesPerRow: bytesPerRow,
+                    sequence: frameSequence,
+                    timestamp: Date().timeIntervalSince1970,
+                    displayID: Int(selectedDisplay?.displayID ?? 0),
+                    screenRect: selectedDisplay?.frame ?? CGRect(x: 0, y: 0, width: surface.width, height: surface.height),
+                    data: SRData(bytes)
+                )
             }
         } catch {
             logger.error("\(error.localizedDescription)")
             // Unable to start the stream. Set the running state to false.
             isRunning = false
+            status = .failed
         }
     }
 
@@ -205,6 +207,7 @@ class ScreenRecorder: NSObject,
         guard isRunning else { return }
         await captureEngine.stopCapture()
         isRunning = false
+        status = .idle
     }
 
     /// - Tag: UpdateCaptureConfig
@@ -330,6 +333,7 @@ class ScreenRecorder: NSObject,
             streamConfig.height = display.height / 4
         }
         streamConfig.scalesToFit = true
+        streamConfig.pixelFormat = kCVPixelFormatType_32BGRA
 
         // Configure the window content width and height.
         if captureType == .window, let window = selectedWindow {